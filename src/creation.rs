@@ -1,5 +1,6 @@
 use crate::error::Error;
 use crate::id::NameId;
+use crate::validate::{validate_chars, validate_processing_instruction_data};
 use crate::xmlvalue::{Attribute, Comment, Element, Namespace, ProcessingInstruction, Text, Value};
 use crate::xotdata::{Node, Xot};
 use crate::{NamespaceId, PrefixId};
@@ -164,6 +165,26 @@ impl Xot {
         self.new_node(text)
     }
 
+    /// Create a new, unattached text node, checking that the text is valid.
+    ///
+    /// Like [`Xot::new_text`], but if the text contains a character that's
+    /// not allowed in XML you get [`Error::InvalidCharacter`].
+    ///
+    /// ```rust
+    /// use xot::{Error, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// assert!(xot.new_text_checked("Hello, world!").is_ok());
+    /// assert!(matches!(
+    ///     xot.new_text_checked("\u{7}"),
+    ///     Err(Error::InvalidCharacter('\u{7}'))
+    /// ));
+    /// ```
+    pub fn new_text_checked(&mut self, text: &str) -> Result<Node, Error> {
+        validate_chars(text)?;
+        Ok(self.new_text(text))
+    }
+
     /// Create a new, unattached comment node given comment text.
     ///
     /// ```rust
@@ -208,6 +229,24 @@ impl Xot {
         self.new_node(pi)
     }
 
+    /// Create a new, unattached processing instruction, checking that the
+    /// data is valid.
+    ///
+    /// Like [`Xot::new_processing_instruction`], but if the data contains
+    /// `?>` you get [`Error::InvalidProcessingInstructionData`], and if it
+    /// contains a character that's not allowed in XML you get
+    /// [`Error::InvalidCharacter`].
+    pub fn new_processing_instruction_checked(
+        &mut self,
+        target: impl Into<NameId>,
+        data: Option<&str>,
+    ) -> Result<Node, Error> {
+        if let Some(data) = data {
+            validate_processing_instruction_data(data)?;
+        }
+        Ok(self.new_processing_instruction(target, data))
+    }
+
     /// Create a new, unattached attribute node.
     ///
     /// You can then use [`Xot::append_attribute_node`] to add it to an element node.
@@ -236,6 +275,21 @@ impl Xot {
         self.new_node(attr)
     }
 
+    /// Create a new, unattached attribute node, checking that the value is
+    /// valid.
+    ///
+    /// Like [`Xot::new_attribute_node`], but if the value contains a
+    /// character that's not allowed in XML you get
+    /// [`Error::InvalidCharacter`].
+    pub fn new_attribute_node_checked(
+        &mut self,
+        name: impl Into<NameId>,
+        value: String,
+    ) -> Result<Node, Error> {
+        validate_chars(&value)?;
+        Ok(self.new_attribute_node(name, value))
+    }
+
     /// Create a new, unattached namespace declaration node.
     ///
     /// You can then use [`Xot::append_namespace_node`] to add it to an element
@@ -0,0 +1,208 @@
+use std::io::Write;
+
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::error::Error;
+use crate::output::xml::{Declaration, DocType, Version};
+use crate::xotdata::{Node, Xot};
+
+/// The document type declaration of a document.
+///
+/// This is retained when a document is parsed, and is used during XML
+/// serialization of the document unless
+/// [`Parameters::doctype`](crate::output::xml::Parameters::doctype) is set.
+///
+/// Example:
+///
+/// ```xml
+/// <!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd">
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentType {
+    /// The name of the document type, i.e. `html` in `<!DOCTYPE html>`.
+    pub name: String,
+    /// The public and system identifier, if any.
+    pub external_id: Option<DocType>,
+    /// The internal subset, if any, as it appears in the source between `[`
+    /// and `]`.
+    pub internal_subset: Option<String>,
+}
+
+impl DocumentType {
+    pub(crate) fn serialize(&self, w: &mut impl Write) -> Result<(), std::io::Error> {
+        w.write_all(b"<!DOCTYPE ")?;
+        w.write_all(self.name.as_bytes())?;
+        if let Some(external_id) = &self.external_id {
+            external_id.serialize_external_id(w)?;
+        }
+        if let Some(internal_subset) = &self.internal_subset {
+            w.write_all(b" [")?;
+            w.write_all(internal_subset.as_bytes())?;
+            w.write_all(b"]")?;
+        }
+        w.write_all(b">\n")?;
+        Ok(())
+    }
+}
+
+/// Metadata about how a document was stored: its XML declaration, and the
+/// encoding it was decoded from.
+///
+/// This is retained when a document is parsed. The XML declaration is used
+/// during XML serialization of the document unless
+/// [`Parameters::declaration`](crate::output::xml::Parameters::declaration)
+/// is set.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DocumentMetadata {
+    /// The declared XML version, i.e. `1.0` or `1.1`. This is `None` if the document
+    /// has no XML declaration.
+    pub version: Option<String>,
+    /// The declared encoding, taken literally from the XML declaration.
+    pub encoding: Option<String>,
+    /// The declared standalone value.
+    pub standalone: Option<bool>,
+    /// The name of the encoding that was used to decode the document. This
+    /// is only known if the document was parsed from bytes using
+    /// [`Xot::parse_bytes`].
+    pub detected_encoding: Option<String>,
+}
+
+impl DocumentMetadata {
+    /// The XML declaration to use to serialize this document, if it had one.
+    ///
+    /// This is what's written when you serialize the document without
+    /// [`Parameters::declaration`](crate::output::xml::Parameters::declaration).
+    /// The output is then UTF-8, so if another encoding was declared, the
+    /// declaration declares `UTF-8` instead. To write the document in
+    /// another encoding, set a declaration with that encoding in the
+    /// parameters: the output is then encoded accordingly, with characters
+    /// the encoding can't represent written as character references.
+    pub fn declaration(&self) -> Option<Declaration> {
+        let version = match self.version.as_deref()? {
+            "1.1" => Version::Xml11,
+            _ => Version::Xml10,
+        };
+        let encoding = self.encoding.as_ref().map(|encoding| {
+            if Encoding::for_label(encoding.as_bytes()) == Some(UTF_8) {
+                encoding.clone()
+            } else {
+                "UTF-8".to_string()
+            }
+        });
+        Some(Declaration {
+            version,
+            encoding,
+            standalone: self.standalone,
+        })
+    }
+}
+
+/// ## Document metadata
+///
+/// Information about a document that isn't part of its tree, such as its
+/// XML declaration and document type declaration. This is retained when you
+/// parse a document and is used during serialization.
+impl Xot {
+    /// Get the document type declaration of a document node.
+    ///
+    /// Returns `None` if the node is not a document node or if the document
+    /// has no document type declaration.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse(r#"<!DOCTYPE doc SYSTEM "doc.dtd"><doc/>"#)?;
+    ///
+    /// let document_type = xot.document_type(root).unwrap();
+    /// assert_eq!(document_type.name, "doc");
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn document_type(&self, node: Node) -> Option<&DocumentType> {
+        self.document_types.get(&node)
+    }
+
+    /// Set the document type declaration of a document node.
+    ///
+    /// This replaces any existing document type declaration. Fails with
+    /// [`Error::NotDocument`] if the node is not a document node.
+    ///
+    /// ```rust
+    /// use xot::{Xot, DocumentType, output::xml::DocType};
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse("<doc/>")?;
+    ///
+    /// xot.set_document_type(root, DocumentType {
+    ///     name: "doc".to_string(),
+    ///     external_id: Some(DocType::System { system: "doc.dtd".to_string() }),
+    ///     internal_subset: None,
+    /// })?;
+    /// assert_eq!(xot.to_string(root)?, "<!DOCTYPE doc SYSTEM \"doc.dtd\">\n<doc/>");
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn set_document_type(
+        &mut self,
+        node: Node,
+        document_type: DocumentType,
+    ) -> Result<(), Error> {
+        if !self.is_document(node) {
+            return Err(Error::NotDocument(node));
+        }
+        self.document_types.insert(node, document_type);
+        Ok(())
+    }
+
+    /// Remove the document type declaration of a document node, if any.
+    ///
+    /// After this the document is serialized without a document type
+    /// declaration.
+    pub fn remove_document_type(&mut self, node: Node) -> Option<DocumentType> {
+        self.document_types.remove(&node)
+    }
+
+    /// Get the metadata of a document node.
+    ///
+    /// Returns `None` if the node is not a document node or if no metadata
+    /// is known for it.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse_bytes(b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><doc/>")?;
+    ///
+    /// let metadata = xot.document_metadata(root).unwrap();
+    /// assert_eq!(metadata.version.as_deref(), Some("1.0"));
+    /// assert_eq!(metadata.encoding.as_deref(), Some("ISO-8859-1"));
+    /// assert_eq!(metadata.detected_encoding.as_deref(), Some("windows-1252"));
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn document_metadata(&self, node: Node) -> Option<&DocumentMetadata> {
+        self.document_metadata.get(&node)
+    }
+
+    /// Set the metadata of a document node.
+    ///
+    /// This replaces any existing metadata. Fails with [`Error::NotDocument`]
+    /// if the node is not a document node.
+    pub fn set_document_metadata(
+        &mut self,
+        node: Node,
+        metadata: DocumentMetadata,
+    ) -> Result<(), Error> {
+        if !self.is_document(node) {
+            return Err(Error::NotDocument(node));
+        }
+        self.document_metadata.insert(node, metadata);
+        Ok(())
+    }
+
+    /// Remove the metadata of a document node, if any.
+    ///
+    /// After this the document is serialized without an XML declaration,
+    /// unless you ask for one explicitly.
+    pub fn remove_document_metadata(&mut self, node: Node) -> Option<DocumentMetadata> {
+        self.document_metadata.remove(&node)
+    }
+}
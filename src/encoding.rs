@@ -1,9 +1,13 @@
-use encoding_rs::Encoding;
+use std::io::Write;
+
+use encoding_rs::{DecoderResult, EncoderResult, Encoding, UTF_16BE, UTF_16LE, UTF_8};
 use xhtmlchardet::detect;
 
-pub fn encoding(data: &[u8], hint: Option<String>) -> Option<&'static Encoding> {
+use crate::error::Error;
+
+pub fn encoding(data: &[u8], hint: Option<String>) -> Result<&'static Encoding, Error> {
     let mut cursor = std::io::Cursor::new(data);
-    let charsets = detect(&mut cursor, hint).ok()?;
+    let charsets = detect(&mut cursor, hint).unwrap_or_default();
     // no encoding detected
     let label = if charsets.is_empty() {
         "UTF-8"
@@ -11,12 +15,198 @@ pub fn encoding(data: &[u8], hint: Option<String>) -> Option<&'static Encoding>
         &charsets[0]
     };
     Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| Error::UnsupportedEncoding(label.to_string()))
+}
+
+pub(crate) fn decode(
+    data: &[u8],
+    hint: Option<String>,
+) -> Result<(String, &'static Encoding), Error> {
+    let enc = encoding(data, hint)?;
+    // decoding may switch encoding if there's a BOM
+    let (s, enc, _) = enc.decode(data);
+    Ok((s.into_owned(), enc))
+}
+
+// Determine the encoding, and the length of the byte order mark if there is
+// one. A byte order mark takes priority over an external encoding (like one
+// from an HTTP header), which takes priority over what's in the document.
+// https://www.w3.org/TR/xml/#sec-guessing
+fn strict_encoding(data: &[u8], hint: Option<&str>) -> Result<(&'static Encoding, usize), Error> {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(data) {
+        return Ok((encoding, bom_length));
+    }
+    if let Some(hint) = hint {
+        let encoding = Encoding::for_label(hint.as_bytes())
+            .ok_or_else(|| Error::UnsupportedEncoding(hint.to_string()))?;
+        return Ok((encoding, 0));
+    }
+    // UTF-16 without a byte order mark, starting with `<?`
+    if data.starts_with(&[0x3C, 0x00, 0x3F, 0x00]) {
+        return Ok((UTF_16LE, 0));
+    }
+    if data.starts_with(&[0x00, 0x3C, 0x00, 0x3F]) {
+        return Ok((UTF_16BE, 0));
+    }
+    Ok((encoding(data, None)?, 0))
 }
 
-pub(crate) fn decode(data: &[u8], hint: Option<String>) -> String {
-    let enc = encoding(data, hint).unwrap();
-    let (s, _, _) = enc.decode(data);
-    s.into_owned()
+// Decode the data, failing on the first byte sequence that's malformed in the
+// encoding instead of replacing it.
+pub(crate) fn decode_strict(
+    data: &[u8],
+    hint: Option<&str>,
+) -> Result<(String, &'static Encoding), Error> {
+    let (encoding, bom_length) = strict_encoding(data, hint)?;
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut s = String::new();
+    let mut offset = bom_length;
+    loop {
+        let src = &data[offset..];
+        if let Some(length) = decoder.max_utf8_buffer_length_without_replacement(src.len()) {
+            s.reserve(length);
+        }
+        let (result, read) = decoder.decode_to_string_without_replacement(src, &mut s, true);
+        offset += read;
+        match result {
+            DecoderResult::InputEmpty => return Ok((s, encoding)),
+            // we reserve enough, but just in case
+            DecoderResult::OutputFull => s.reserve(src.len().max(4)),
+            DecoderResult::Malformed(malformed, consumed) => {
+                let position = offset - malformed as usize - consumed as usize;
+                return Err(Error::MalformedEncoding(
+                    encoding.name().to_string(),
+                    position,
+                ));
+            }
+        }
+    }
+}
+
+// The labels of ISO-8859-1. encoding_rs treats these as windows-1252, but a
+// reader that takes the label literally decodes bytes 0x80-0x9F as C1
+// controls, so we encode ISO-8859-1 ourselves.
+const LATIN1_LABELS: &[&str] = &[
+    "cp819",
+    "csisolatin1",
+    "ibm819",
+    "iso-8859-1",
+    "iso-ir-100",
+    "iso8859-1",
+    "iso88591",
+    "iso_8859-1",
+    "iso_8859-1:1987",
+    "l1",
+    "latin1",
+];
+
+// An encoding we serialize to, other than UTF-8.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum OutputEncoding {
+    Latin1,
+    Other(&'static Encoding),
+}
+
+impl OutputEncoding {
+    // The encoding for the label in the XML declaration, or `None` for UTF-8,
+    // which needs no encoding. We can only write encodings that are ASCII
+    // compatible, as markup is written without going through an encoder.
+    pub(crate) fn for_label(label: &str) -> Result<Option<Self>, Error> {
+        let unsupported = || Error::UnsupportedEncoding(label.to_string());
+        let trimmed = label.trim().to_ascii_lowercase();
+        if LATIN1_LABELS.contains(&trimmed.as_str()) {
+            return Ok(Some(OutputEncoding::Latin1));
+        }
+        let encoding = Encoding::for_label(label.as_bytes()).ok_or_else(unsupported)?;
+        if encoding == UTF_8 {
+            Ok(None)
+        } else if encoding.output_encoding() != encoding || !encoding.is_ascii_compatible() {
+            // UTF-16, ISO-2022-JP and the replacement encoding
+            Err(unsupported())
+        } else {
+            Ok(Some(OutputEncoding::Other(encoding)))
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            OutputEncoding::Latin1 => "ISO-8859-1",
+            OutputEncoding::Other(encoding) => encoding.name(),
+        }
+    }
+
+    pub(crate) fn can_encode(&self, c: char) -> bool {
+        if c.is_ascii() {
+            return true;
+        }
+        match self {
+            OutputEncoding::Latin1 => (c as u32) <= 0xFF,
+            OutputEncoding::Other(encoding) => {
+                let mut input = [0; 4];
+                // enough for a character with escape sequences around it
+                let mut output = [0; 16];
+                let (result, _, _) = encoding.new_encoder().encode_from_utf8_without_replacement(
+                    c.encode_utf8(&mut input),
+                    &mut output,
+                    true,
+                );
+                result == EncoderResult::InputEmpty
+            }
+        }
+    }
+
+    // Write the string in this encoding. A character that can't be encoded
+    // is an error: where a character reference is allowed, the serializer has
+    // already replaced it with one.
+    pub(crate) fn write(&self, w: &mut impl Write, s: &str) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(s.len());
+        match self {
+            OutputEncoding::Latin1 => {
+                for c in s.chars() {
+                    let b = u8::try_from(c as u32)
+                        .map_err(|_| Error::UnencodableCharacter(c, self.name().to_string()))?;
+                    bytes.push(b);
+                }
+            }
+            OutputEncoding::Other(encoding) => {
+                let mut encoder = encoding.new_encoder();
+                let mut offset = 0;
+                loop {
+                    if let Some(length) =
+                        encoder.max_buffer_length_from_utf8_without_replacement(s.len() - offset)
+                    {
+                        bytes.reserve(length);
+                    }
+                    let (result, read) = encoder.encode_from_utf8_to_vec_without_replacement(
+                        &s[offset..],
+                        &mut bytes,
+                        true,
+                    );
+                    offset += read;
+                    match result {
+                        EncoderResult::InputEmpty => break,
+                        // we reserve enough, but just in case
+                        EncoderResult::OutputFull => bytes.reserve((s.len() - offset).max(16)),
+                        EncoderResult::Unmappable(c) => {
+                            return Err(Error::UnencodableCharacter(c, self.name().to_string()))
+                        }
+                    }
+                }
+            }
+        }
+        w.write_all(&bytes)?;
+        Ok(())
+    }
+
+    // Decode what we wrote, for when a string is requested.
+    pub(crate) fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            OutputEncoding::Latin1 => bytes.iter().map(|b| *b as char).collect(),
+            OutputEncoding::Other(encoding) => {
+                encoding.decode_without_bom_handling(bytes).0.into_owned()
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -52,4 +242,84 @@ mod tests {
         // windows-1252 is a superset of 8859-1
         assert_eq!(enc.name(), "windows-1252");
     }
+
+    #[test]
+    fn test_unsupported() {
+        let data = b"<?xml version=\"1.0\" encoding=\"foo\"?><a/>";
+        assert!(matches!(
+            encoding(data, None),
+            Err(Error::UnsupportedEncoding(label)) if label == "foo"
+        ));
+    }
+
+    #[test]
+    fn test_strict_bom() {
+        let (enc, bom_length) = strict_encoding(b"\xfe\xff\0<\0a", Some("utf-8")).unwrap();
+        assert_eq!(enc.name(), "UTF-16BE");
+        assert_eq!(bom_length, 2);
+    }
+
+    #[test]
+    fn test_strict_utf16_without_bom() {
+        let (enc, _) = strict_encoding(b"<\0?\0x\0m\0l\0", None).unwrap();
+        assert_eq!(enc.name(), "UTF-16LE");
+        let (enc, _) = strict_encoding(b"\0<\0?\0x\0m\0l", None).unwrap();
+        assert_eq!(enc.name(), "UTF-16BE");
+    }
+
+    #[test]
+    fn test_decode_strict_malformed() {
+        let data = b"<a>caf\xe9</a>";
+        assert!(matches!(
+            decode_strict(data, None),
+            Err(Error::MalformedEncoding(_, 6))
+        ));
+        let (s, enc) = decode_strict(data, Some("latin1")).unwrap();
+        assert_eq!(s, "<a>caf\u{e9}</a>");
+        assert_eq!(enc.name(), "windows-1252");
+    }
+
+    #[test]
+    fn test_output_encoding_for_label() {
+        assert!(OutputEncoding::for_label("UTF-8").unwrap().is_none());
+        assert!(matches!(
+            OutputEncoding::for_label("ISO-8859-1"),
+            Ok(Some(OutputEncoding::Latin1))
+        ));
+        assert!(matches!(
+            OutputEncoding::for_label("Shift_JIS"),
+            Ok(Some(OutputEncoding::Other(_)))
+        ));
+        assert!(matches!(
+            OutputEncoding::for_label("UTF-16"),
+            Err(Error::UnsupportedEncoding(_))
+        ));
+        assert!(matches!(
+            OutputEncoding::for_label("foo"),
+            Err(Error::UnsupportedEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_output_encoding_write() {
+        let latin1 = OutputEncoding::for_label("latin1").unwrap().unwrap();
+        assert!(latin1.can_encode('\u{e9}'));
+        assert!(latin1.can_encode('\u{80}'));
+        assert!(!latin1.can_encode('\u{20ac}'));
+        let mut bytes = Vec::new();
+        latin1.write(&mut bytes, "caf\u{e9}\u{80}").unwrap();
+        assert_eq!(bytes, b"caf\xe9\x80");
+        assert!(matches!(
+            latin1.write(&mut bytes, "\u{20ac}"),
+            Err(Error::UnencodableCharacter('\u{20ac}', _))
+        ));
+
+        let shift_jis = OutputEncoding::for_label("Shift_JIS").unwrap().unwrap();
+        assert!(shift_jis.can_encode('\u{3042}'));
+        assert!(!shift_jis.can_encode('\u{e9}'));
+        let mut bytes = Vec::new();
+        shift_jis.write(&mut bytes, "a\u{3042}").unwrap();
+        assert_eq!(bytes, b"a\x82\xa0");
+        assert_eq!(shift_jis.decode(&bytes), "a\u{3042}");
+    }
 }
@@ -1,24 +1,206 @@
 use std::borrow::Cow;
+use std::cell::Cell;
+
+use ahash::{HashMap, HashMapExt};
 
 use crate::error::Error;
+use crate::html5entities::html5_entity;
 use crate::output::Normalizer;
+use crate::parse::{ParseLimit, ParseOptions, Span};
+use crate::validate::{is_xml_char, validate_chars};
+
+// The maximum total length of the replacement text of the entities declared
+// in the DTD that a document may expand. This guards against entities that
+// expand exponentially, such as "billion laughs".
+pub(crate) const MAX_ENTITY_EXPANSION: usize = 10_000_000;
+
+/// General entities declared in the internal DTD subset, by name. The value
+/// is the replacement text of the entity.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Entities {
+    map: HashMap<String, String>,
+    // the total length of the replacement text expanded so far
+    expanded: Cell<usize>,
+    // whether the entities are expanded in an XML 1.1 document, which has
+    // more line ends and allows more characters in character references
+    xml11: bool,
+    // entities given in the parse options, with a replacement text that's
+    // taken literally
+    custom: HashMap<String, String>,
+    // whether to resolve the HTML 5 named character references
+    html5: bool,
+}
+
+impl Entities {
+    pub(crate) fn new() -> Self {
+        Entities {
+            map: HashMap::new(),
+            expanded: Cell::new(0),
+            xml11: false,
+            custom: HashMap::new(),
+            html5: false,
+        }
+    }
+
+    // The replacement text of the entities in the options is taken
+    // literally, so it must consist of XML characters.
+    pub(crate) fn from_options(options: &ParseOptions) -> Result<Self, Error> {
+        for value in options.entities.values() {
+            validate_chars(value)?;
+        }
+        Ok(Entities {
+            custom: options
+                .entities
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            html5: options.html5_entities,
+            ..Self::new()
+        })
+    }
+
+    pub(crate) fn set_xml11(&mut self, xml11: bool) {
+        self.xml11 = xml11;
+    }
+
+    // Declare an internal general entity given its literal value. Character
+    // references are expanded immediately, entity references are expanded
+    // when the entity is used. The offset is the position of the literal
+    // in the source.
+    // https://www.w3.org/TR/xml/#intern-replacement
+    pub(crate) fn declare(
+        &mut self,
+        name: &str,
+        literal: &str,
+        offset: usize,
+    ) -> Result<(), Error> {
+        // if an entity is declared more than once, the first declaration
+        // is binding
+        if self.map.contains_key(name) {
+            return Ok(());
+        }
+        let replacement = parse_entity_value(literal, offset, self.xml11)?;
+        self.map.insert(name.to_string(), replacement);
+        Ok(())
+    }
+}
+
+// The offset is the position of the content in the source, so that errors
+// can report where they happened. If the result would be longer than
+// `max_length`, this fails as soon as it is, so that entities that expand to
+// a lot of text don't use up memory first.
+pub(crate) fn parse_text<'a>(
+    content: Cow<'a, str>,
+    entities: &Entities,
+    offset: usize,
+    max_length: Option<usize>,
+) -> Result<Cow<'a, str>, Error> {
+    parse_content(content, entities, false, offset, max_length, None)
+}
 
-pub(crate) fn parse_text(content: Cow<str>) -> Result<Cow<str>, Error> {
-    parse_content(content, false)
+pub(crate) fn parse_attribute<'a>(
+    content: Cow<'a, str>,
+    entities: &Entities,
+    offset: usize,
+    max_length: Option<usize>,
+) -> Result<Cow<'a, str>, Error> {
+    parse_content(content, entities, true, offset, max_length, None)
 }
 
-pub(crate) fn parse_attribute(content: Cow<str>) -> Result<Cow<str>, Error> {
-    parse_content(content, true)
+// Like `parse_text`, but a reference that cannot be expanded is left as it
+// is, and the error is added to `errors`. Exceeding a limit is not
+// recovered from.
+pub(crate) fn parse_text_recovering<'a>(
+    content: Cow<'a, str>,
+    entities: &Entities,
+    offset: usize,
+    max_length: Option<usize>,
+    errors: &mut Vec<Error>,
+) -> Result<Cow<'a, str>, Error> {
+    parse_content(content, entities, false, offset, max_length, Some(errors))
 }
 
-fn parse_content(content: Cow<str>, attribute: bool) -> Result<Cow<str>, Error> {
+// Like `parse_attribute`, but a reference that cannot be expanded is left as
+// it is, and the error is added to `errors`. Exceeding a limit is not
+// recovered from.
+pub(crate) fn parse_attribute_recovering<'a>(
+    content: Cow<'a, str>,
+    entities: &Entities,
+    offset: usize,
+    max_length: Option<usize>,
+    errors: &mut Vec<Error>,
+) -> Result<Cow<'a, str>, Error> {
+    parse_content(content, entities, true, offset, max_length, Some(errors))
+}
+
+fn parse_content<'a>(
+    content: Cow<'a, str>,
+    entities: &Entities,
+    attribute: bool,
+    offset: usize,
+    max_length: Option<usize>,
+    mut errors: Option<&mut Vec<Error>>,
+) -> Result<Cow<'a, str>, Error> {
+    let expansion = Expansion {
+        entities,
+        attribute,
+        max_length,
+    };
     let mut result = String::new();
-    let mut chars = content.chars().peekable();
+    let change = expand_content(
+        &content,
+        &expansion,
+        &mut result,
+        &mut Vec::new(),
+        &|start, end| Span::new(offset + start, offset + end),
+        &mut errors,
+    )?;
+    if !change {
+        Ok(content)
+    } else {
+        Ok(result.into())
+    }
+}
+
+// How content is expanded: in text or in an attribute value, and the maximum
+// length of the result.
+struct Expansion<'a> {
+    entities: &'a Entities,
+    attribute: bool,
+    max_length: Option<usize>,
+}
+
+// expands the content into result, returning true if anything was changed.
+// `expanding` is the stack of entities we are expanding, so we can detect
+// recursion. `span` gives the source span for a range in the content; in
+// the replacement text of an entity this is always the span of the
+// reference to the entity. If we have `errors`, a reference that cannot be
+// expanded is added to the result as it is, and the error is added to
+// `errors`, unless it's a limit that was exceeded. If the result gets longer
+// than `max_length`, this fails right away.
+fn expand_content<'a>(
+    content: &str,
+    expansion: &Expansion<'a>,
+    result: &mut String,
+    expanding: &mut Vec<&'a str>,
+    span: &dyn Fn(usize, usize) -> Span,
+    errors: &mut Option<&mut Vec<Error>>,
+) -> Result<bool, Error> {
+    let Expansion {
+        entities,
+        attribute,
+        max_length,
+    } = *expansion;
+    let mut chars = content.char_indices().peekable();
     let mut change = false;
-    while let Some(c) = chars.next() {
+    while let Some((start, c)) = chars.next() {
         // https://www.w3.org/TR/xml/#sec-line-ends
-        if c == '\r' {
-            if chars.peek() == Some(&'\n') {
+        // https://www.w3.org/TR/xml11/#sec-line-ends
+        let is_xml11_line_end = entities.xml11 && (c == '\u{85}' || c == '\u{2028}');
+        if c == '\r' || is_xml11_line_end {
+            if c == '\r'
+                && matches!(chars.peek(), Some((_, next)) if *next == '\n' || (entities.xml11 && *next == '\u{85}'))
+            {
                 // consume next char
                 chars.next();
             }
@@ -31,41 +213,36 @@ fn parse_content(content: Cow<str>, attribute: bool) -> Result<Cow<str>, Error>
             change = true;
         } else if c == '&' {
             let mut entity = String::new();
-            let mut is_complete = false;
-            for c in chars.by_ref() {
+            let mut end = None;
+            for (i, c) in chars.by_ref() {
                 if c == ';' {
-                    is_complete = true;
+                    end = Some(i + 1);
                     break;
                 }
                 entity.push(c);
             }
-            if !is_complete {
-                return Err(Error::UnclosedEntity(entity));
-            }
             change = true;
-
-            if let Some(entity) = entity.strip_prefix('#') {
-                let first_char = entity
-                    .chars()
-                    .next()
-                    .ok_or_else(|| Error::InvalidEntity(entity.to_string()))?;
-                let code = if first_char == 'x' {
-                    u32::from_str_radix(&entity[1..], 16)
-                } else {
-                    entity.parse::<u32>()
-                };
-                let code = code.map_err(|_| Error::InvalidEntity(entity.to_string()))?;
-                let c = std::char::from_u32(code)
-                    .ok_or_else(|| Error::InvalidEntity(entity.to_string()))?;
-                result.push(c);
-            } else {
-                match entity.as_str() {
-                    "amp" => result.push('&'),
-                    "apos" => result.push('\''),
-                    "gt" => result.push('>'),
-                    "lt" => result.push('<'),
-                    "quot" => result.push('"'),
-                    _ => return Err(Error::InvalidEntity(entity)),
+            let expanded = match end {
+                Some(end) => expand_reference(
+                    &entity,
+                    span(start, end),
+                    expansion,
+                    result,
+                    expanding,
+                    errors,
+                ),
+                None => Err(Error::UnclosedEntity(
+                    entity.clone(),
+                    span(start, content.len()),
+                )),
+            };
+            if let Err(error) = expanded {
+                match errors {
+                    Some(errors) if !matches!(error, Error::LimitExceeded(..)) => {
+                        errors.push(error);
+                        result.push_str(&content[start..end.unwrap_or(content.len())]);
+                    }
+                    _ => return Err(error),
                 }
             }
         } else if attribute && (c == '\t' || c == '\n') {
@@ -76,13 +253,128 @@ fn parse_content(content: Cow<str>, attribute: bool) -> Result<Cow<str>, Error>
         } else {
             result.push(c);
         }
+        if max_length.is_some_and(|max_length| result.len() > max_length) {
+            return Err(Error::LimitExceeded(
+                ParseLimit::TextLength,
+                span(0, content.len()),
+            ));
+        }
     }
+    Ok(change)
+}
 
-    if !change {
-        Ok(content)
+// expands a reference to an entity (without `&` and `;`) into result
+fn expand_reference<'a>(
+    entity: &str,
+    entity_span: Span,
+    expansion: &Expansion<'a>,
+    result: &mut String,
+    expanding: &mut Vec<&'a str>,
+    errors: &mut Option<&mut Vec<Error>>,
+) -> Result<(), Error> {
+    let entities = expansion.entities;
+    if let Some(entity) = entity.strip_prefix('#') {
+        let c = parse_character_reference(entity, entities.xml11)
+            .ok_or_else(|| Error::InvalidEntity(entity.to_string(), entity_span))?;
+        result.push(c);
+        return Ok(());
+    }
+    match entity {
+        "amp" => result.push('&'),
+        "apos" => result.push('\''),
+        "gt" => result.push('>'),
+        "lt" => result.push('<'),
+        "quot" => result.push('"'),
+        _ => {
+            let Some((name, replacement)) = entities.map.get_key_value(entity) else {
+                // entities declared in the document take precedence, and
+                // these are taken literally, like character references
+                let replacement = entities
+                    .custom
+                    .get(entity)
+                    .map(|replacement| replacement.as_str())
+                    .or_else(|| entities.html5.then(|| html5_entity(entity)).flatten())
+                    .ok_or_else(|| Error::InvalidEntity(entity.to_string(), entity_span))?;
+                result.push_str(replacement);
+                return Ok(());
+            };
+            if expanding.contains(&name.as_str()) {
+                return Err(Error::RecursiveEntity(entity.to_string(), entity_span));
+            }
+            // we don't support markup in entities
+            if replacement.contains('<') {
+                return Err(Error::InvalidEntity(entity.to_string(), entity_span));
+            }
+            // all text that results from expansion comes from replacement
+            // text, so this also bounds the length of the result
+            let expanded = entities.expanded.get() + replacement.len();
+            if expanded > MAX_ENTITY_EXPANSION {
+                return Err(Error::LimitExceeded(
+                    ParseLimit::EntityExpansion,
+                    entity_span,
+                ));
+            }
+            entities.expanded.set(expanded);
+            expanding.push(name);
+            expand_content(
+                replacement,
+                expansion,
+                result,
+                expanding,
+                &|_, _| entity_span,
+                errors,
+            )?;
+            expanding.pop();
+        }
+    }
+    Ok(())
+}
+
+// A character reference must refer to a character that's allowed in the
+// document; XML 1.1 allows all control characters except NUL.
+// https://www.w3.org/TR/xml11/#NT-Char
+fn parse_character_reference(entity: &str, xml11: bool) -> Option<char> {
+    let first_char = entity.chars().next()?;
+    let code = if first_char == 'x' {
+        u32::from_str_radix(&entity[1..], 16)
     } else {
-        Ok(result.into())
+        entity.parse::<u32>()
+    };
+    let c = std::char::from_u32(code.ok()?)?;
+    (is_xml_char(c) || (xml11 && c != '\0' && c.is_control())).then_some(c)
+}
+
+// Construct the replacement text of an internal entity from its literal
+// value: character references are expanded, but entity references are
+// left alone.
+fn parse_entity_value(literal: &str, offset: usize, xml11: bool) -> Result<String, Error> {
+    let mut result = String::new();
+    let mut position = 0;
+    while let Some(index) = literal[position..].find('&') {
+        let start = position + index;
+        result.push_str(&literal[position..start]);
+        let end = literal[start..]
+            .find(';')
+            .map(|i| start + i + 1)
+            .ok_or_else(|| {
+                Error::UnclosedEntity(
+                    literal[start + 1..].to_string(),
+                    Span::new(offset + start, offset + literal.len()),
+                )
+            })?;
+        let entity = &literal[start + 1..end - 1];
+        if let Some(entity) = entity.strip_prefix('#') {
+            let c = parse_character_reference(entity, xml11).ok_or_else(|| {
+                Error::InvalidEntity(entity.to_string(), Span::new(offset + start, offset + end))
+            })?;
+            result.push(c);
+        } else {
+            result.push_str(&literal[start..end]);
+        }
+        position = end;
     }
+    result.push_str(&literal[position..]);
+    Ok(result)
 }
 
 pub(crate) fn serialize_text<'a, N: Normalizer>(
@@ -223,6 +515,35 @@ pub(crate) fn serialize_attribute<'a, N: Normalizer>(
     }
 }
 
+// Replace characters, such as those in a character map: a character with a
+// replacement is output as that string as is, and the runs of characters in
+// between are serialized with `serialize`. A replaced character in a CDATA
+// section ends up between two CDATA sections.
+pub(crate) fn serialize_mapped<'a, 'r>(
+    content: Cow<'a, str>,
+    replacement: impl Fn(char) -> Option<Cow<'r, str>>,
+    serialize: impl Fn(Cow<'a, str>) -> Cow<'a, str>,
+) -> Cow<'a, str> {
+    if !content.chars().any(|c| replacement(c).is_some()) {
+        return serialize(content);
+    }
+    let mut result = String::new();
+    let mut start = 0;
+    for (i, c) in content.char_indices() {
+        if let Some(replacement) = replacement(c) {
+            if start < i {
+                result.push_str(&serialize(content[start..i].to_string().into()));
+            }
+            result.push_str(&replacement);
+            start = i + c.len_utf8();
+        }
+    }
+    if start < content.len() {
+        result.push_str(&serialize(content[start..].to_string().into()));
+    }
+    result.into()
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -233,20 +554,27 @@ mod tests {
     #[test]
     fn test_parse() {
         let text = "A &amp; B";
-        assert_eq!(parse_text(text.into()).unwrap(), "A & B");
+        assert_eq!(
+            parse_text(text.into(), &Entities::new(), 0, None).unwrap(),
+            "A & B"
+        );
     }
 
     #[test]
     fn test_parse_multiple() {
         let text = "&amp;&apos;&gt;&lt;&quot;";
-        assert_eq!(parse_text(text.into()).unwrap(), "&'><\"");
+        assert_eq!(
+            parse_text(text.into(), &Entities::new(), 0, None).unwrap(),
+            "&'><\""
+        );
     }
 
     #[test]
     fn test_parse_unknown_entity() {
         let text = "&unknown;";
-        let err = parse_text(text.into());
-        if let Err(Error::InvalidEntity(entity)) = err {
+        let err = parse_text(text.into(), &Entities::new(), 0, None);
+        if let Err(Error::InvalidEntity(entity, span)) = err {
+            assert_eq!(span, Span::new(0, 9));
             assert_eq!(entity, "unknown");
         } else {
             unreachable!();
@@ -256,8 +584,9 @@ mod tests {
     #[test]
     fn test_parse_unfinished_entity() {
         let text = "&amp";
-        let err = parse_text(text.into());
-        if let Err(Error::UnclosedEntity(entity)) = err {
+        let err = parse_text(text.into(), &Entities::new(), 0, None);
+        if let Err(Error::UnclosedEntity(entity, span)) = err {
+            assert_eq!(span, Span::new(0, 4));
             assert_eq!(entity, "amp");
         } else {
             unreachable!();
@@ -267,7 +596,7 @@ mod tests {
     #[test]
     fn test_parse_no_entities() {
         let text = "hello";
-        let result = parse_text(text.into()).unwrap();
+        let result = parse_text(text.into(), &Entities::new(), 0, None).unwrap();
         // this is the same slice
         assert!(std::ptr::eq(text, result.as_ref()));
     }
@@ -275,49 +604,177 @@ mod tests {
     #[test]
     fn test_parse_newline_r() {
         let text = "A \r B";
-        assert_eq!(parse_text(text.into()).unwrap(), "A \n B");
+        assert_eq!(
+            parse_text(text.into(), &Entities::new(), 0, None).unwrap(),
+            "A \n B"
+        );
     }
 
     #[test]
     fn test_parse_newline_rn() {
         let text = "A \r\n B";
-        assert_eq!(parse_text(text.into()).unwrap(), "A \n B");
+        assert_eq!(
+            parse_text(text.into(), &Entities::new(), 0, None).unwrap(),
+            "A \n B"
+        );
     }
 
     #[test]
     fn test_do_not_normalize_text_tab() {
         let text = "A \t B";
-        assert_eq!(parse_text(text.into()).unwrap(), "A \t B");
+        assert_eq!(
+            parse_text(text.into(), &Entities::new(), 0, None).unwrap(),
+            "A \t B"
+        );
     }
 
     #[test]
     fn test_do_not_normalize_text_newline() {
         let text = "A \n B";
-        assert_eq!(parse_text(text.into()).unwrap(), "A \n B");
+        assert_eq!(
+            parse_text(text.into(), &Entities::new(), 0, None).unwrap(),
+            "A \n B"
+        );
     }
 
     #[test]
     fn test_normalize_attribute_tab() {
         let text = "A \t B";
-        assert_eq!(parse_attribute(text.into()).unwrap(), "A   B");
+        assert_eq!(
+            parse_attribute(text.into(), &Entities::new(), 0, None).unwrap(),
+            "A   B"
+        );
     }
 
     #[test]
     fn test_normalize_attribute_r_newline() {
         let text = "A \r B";
-        assert_eq!(parse_attribute(text.into()).unwrap(), "A   B");
+        assert_eq!(
+            parse_attribute(text.into(), &Entities::new(), 0, None).unwrap(),
+            "A   B"
+        );
     }
 
     #[test]
     fn test_normalize_attribute_rn_newline() {
         let text = "A \r\n B";
-        assert_eq!(parse_attribute(text.into()).unwrap(), "A   B");
+        assert_eq!(
+            parse_attribute(text.into(), &Entities::new(), 0, None).unwrap(),
+            "A   B"
+        );
     }
 
     #[test]
     fn test_normalize_attribute_newline() {
         let text = "A \n B";
-        assert_eq!(parse_attribute(text.into()).unwrap(), "A   B");
+        assert_eq!(
+            parse_attribute(text.into(), &Entities::new(), 0, None).unwrap(),
+            "A   B"
+        );
+    }
+
+    #[test]
+    fn test_parse_declared_entity() {
+        let mut entities = Entities::new();
+        entities.declare("custom", "Custom", 0).unwrap();
+        let text = "A &custom; B";
+        assert_eq!(
+            parse_text(text.into(), &entities, 0, None).unwrap(),
+            "A Custom B"
+        );
+    }
+
+    #[test]
+    fn test_parse_declared_entity_nested() {
+        let mut entities = Entities::new();
+        entities.declare("inner", "Inner", 0).unwrap();
+        entities.declare("outer", "[&inner;]", 0).unwrap();
+        let text = "&outer;";
+        assert_eq!(
+            parse_text(text.into(), &entities, 0, None).unwrap(),
+            "[Inner]"
+        );
+    }
+
+    #[test]
+    fn test_parse_declared_entity_first_declaration_binding() {
+        let mut entities = Entities::new();
+        entities.declare("custom", "first", 0).unwrap();
+        entities.declare("custom", "second", 0).unwrap();
+        let text = "&custom;";
+        assert_eq!(
+            parse_text(text.into(), &entities, 0, None).unwrap(),
+            "first"
+        );
+    }
+
+    #[test]
+    fn test_parse_declared_entity_character_reference() {
+        // character references are expanded when the entity is declared,
+        // so this is an escaped ampersand
+        let mut entities = Entities::new();
+        entities.declare("custom", "&#38;amp;", 0).unwrap();
+        let text = "&custom;";
+        assert_eq!(parse_text(text.into(), &entities, 0, None).unwrap(), "&");
+    }
+
+    #[test]
+    fn test_parse_declared_entity_attribute_normalization() {
+        let mut entities = Entities::new();
+        entities.declare("custom", "A&#9;B", 0).unwrap();
+        let text = "&custom;";
+        assert_eq!(
+            parse_attribute(text.into(), &entities, 0, None).unwrap(),
+            "A B"
+        );
+    }
+
+    #[test]
+    fn test_parse_declared_entity_recursive() {
+        let mut entities = Entities::new();
+        entities.declare("a", "&b;", 0).unwrap();
+        entities.declare("b", "&a;", 0).unwrap();
+        let text = "&a;";
+        let err = parse_text(text.into(), &entities, 0, None);
+        assert!(matches!(err, Err(Error::RecursiveEntity(_, _))));
+    }
+
+    #[test]
+    fn test_parse_declared_entity_markup() {
+        let mut entities = Entities::new();
+        entities.declare("custom", "<p/>", 0).unwrap();
+        let text = "&custom;";
+        let err = parse_text(text.into(), &entities, 0, None);
+        assert!(matches!(err, Err(Error::InvalidEntity(_, _))));
+    }
+
+    fn laughs() -> Entities {
+        let mut entities = Entities::new();
+        entities.declare("a0", "lol", 0).unwrap();
+        for i in 1..10 {
+            let literal = format!("&a{};", i - 1).repeat(10);
+            entities.declare(&format!("a{}", i), &literal, 0).unwrap();
+        }
+        entities
+    }
+
+    #[test]
+    fn test_parse_declared_entity_expansion_limit() {
+        let text = "x&a9;";
+        let err = parse_text(text.into(), &laughs(), 0, None);
+        assert!(matches!(
+            err,
+            Err(Error::LimitExceeded(ParseLimit::EntityExpansion, span)) if span == Span::new(1, 5)
+        ));
+    }
+
+    #[test]
+    fn test_parse_declared_entity_expansion_limit_cumulative() {
+        // the limit applies to all expansions together
+        let entities = laughs();
+        let text = "&a5;";
+        assert!(parse_text(text.into(), &entities, 0, None).is_ok());
+        assert!((0..100).any(|_| parse_text(text.into(), &entities, 0, None).is_err()));
     }
 
     #[test]
@@ -438,31 +895,37 @@ mod tests {
     #[test]
     fn test_parse_character_hex_entity() {
         let text = "A &#x26; B";
-        assert_eq!(parse_text(text.into()).unwrap(), "A & B");
+        assert_eq!(
+            parse_text(text.into(), &Entities::new(), 0, None).unwrap(),
+            "A & B"
+        );
     }
 
     #[test]
     fn test_parse_character_decimal_entity() {
         let text = "A &#38; B";
-        assert_eq!(parse_text(text.into()).unwrap(), "A & B");
+        assert_eq!(
+            parse_text(text.into(), &Entities::new(), 0, None).unwrap(),
+            "A & B"
+        );
     }
 
     #[test]
     fn test_parse_character_empty_entity() {
         let text = "A &#; B";
-        assert!(parse_text(text.into()).is_err());
+        assert!(parse_text(text.into(), &Entities::new(), 0, None).is_err());
     }
 
     #[test]
     fn test_parse_character_empty_hex_entity() {
         let text = "A &x#; B";
-        assert!(parse_text(text.into()).is_err());
+        assert!(parse_text(text.into(), &Entities::new(), 0, None).is_err());
     }
 
     #[test]
     fn test_parse_character_broken_hex_entity() {
         let text = "A &xflub#; B";
-        assert!(parse_text(text.into()).is_err());
+        assert!(parse_text(text.into(), &Entities::new(), 0, None).is_err());
     }
 
     #[test]
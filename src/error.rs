@@ -1,3 +1,4 @@
+use crate::parse::{ParseLimit, Span};
 use crate::xotdata::Node;
 
 /// Xot errors
@@ -19,6 +20,19 @@ pub enum Error {
     /// You aren't allowed to use this string as a processing instruction
     /// target. Happens if you use `XML` or any case variation of this.
     InvalidTarget(String),
+    /// The string is not a valid name. A local name must be an `NCName`,
+    /// and a name with a prefix must be a `QName`, as defined by Namespaces
+    /// in XML.
+    InvalidName(String),
+    /// The string is not a valid prefix: it must be empty or an `NCName`.
+    InvalidPrefix(String),
+    /// The character is not allowed in XML content, as it's not matched by
+    /// the `Char` production of the XML specification. Most control
+    /// characters are not allowed.
+    InvalidCharacter(char),
+    /// You aren't allowed to use this string as processing instruction
+    /// data, as it contains `?>`.
+    InvalidProcessingInstructionData(String),
     /// The node you tried to act on is not an element.
     NotElement(Node),
     /// Indextree error that can happen during manipulation.
@@ -38,28 +52,67 @@ pub enum Error {
     NamespaceInProcessingInstruction,
 
     // parser errors
-    /// The XML is not well-formed - a tag is opened and never closed.
-    UnclosedTag,
+    //
+    // These carry the span in the source where the error occurred. You can
+    // use `Span::line_column` to turn this into a line and column.
+    /// The XML is not well-formed - a tag is opened and never closed. The
+    /// span is that of the name of the unclosed element.
+    UnclosedTag(Span),
     /// The XML is not well-formed - a tag is closed that was never opened.
-    InvalidCloseTag(String, String),
+    InvalidCloseTag(String, String, Span),
     /// The XML is not well-formed - you use `&` to open an entity without
     /// closing it with `;`.
-    UnclosedEntity(String),
-    /// The entity is not known. Only the basic entities are supported
-    /// right now, not any user defined ones.
-    InvalidEntity(String),
+    UnclosedEntity(String, Span),
+    /// The entity is not known. Besides the predefined entities, only general
+    /// entities declared in the internal DTD subset are supported, and those
+    /// you enable with [`ParseOptions`](crate::ParseOptions). Entities with a
+    /// replacement text that contains markup are not supported either.
+    InvalidEntity(String, Span),
+    /// The entity refers to itself, directly or indirectly.
+    RecursiveEntity(String, Span),
     /// You used a namespace prefix that is not declared.
-    UnknownPrefix(String),
+    ///
+    /// The span is `None` if this happens when you create a name outside of
+    /// the parser.
+    UnknownPrefix(String, Option<Span>),
     /// You declared an attribute of the same name twice.
-    DuplicateAttribute(String),
-    /// Unsupported XML version. Only 1.0 is supported.
-    UnsupportedVersion(String),
-    /// Unsupported XML encoding. Only UTF-8 is supported.
+    ///
+    /// The span is `None` if this happens in a
+    /// [`TreeBuilder`](crate::TreeBuilder), as is the case for the namespace
+    /// errors below.
+    DuplicateAttribute(String, Option<Span>),
+    /// You declared two attributes with different prefixes that have the
+    /// same expanded name, because the prefixes are bound to the same
+    /// namespace.
+    DuplicateExpandedAttribute(String, Option<Span>),
+    /// You declared the `xmlns` prefix, or bound the `xml` prefix to a
+    /// namespace other than the XML namespace.
+    ReservedPrefix(String, Option<Span>),
+    /// You bound the XML namespace to a prefix other than `xml`, or declared
+    /// the `xmlns` namespace.
+    ReservedNamespace(String, Option<Span>),
+    /// You declared a prefix with an empty namespace URI, like
+    /// `xmlns:p=""`. This is not allowed in XML 1.0.
+    EmptyPrefixedNamespace(String, Option<Span>),
+    /// The document exceeds one of the
+    /// [`ParseLimits`](crate::ParseLimits) you set. The span is that of the
+    /// markup or text where the limit was exceeded.
+    LimitExceeded(ParseLimit, Span),
+    /// Unsupported XML version. Only 1.0 and 1.1 are supported.
+    UnsupportedVersion(String, Span),
+    /// In XML 1.1, this control character may only appear as a character
+    /// reference.
+    RestrictedCharacter(char, Span),
+    /// Unsupported XML encoding. The encoding is not known, or it can't be
+    /// used for output.
     UnsupportedEncoding(String),
-    /// Unsupported standalone declaration. Only `yes` is supported.
-    UnsupportedNotStandalone,
-    /// XML DTD is not supported.
-    DtdUnsupported,
+    /// The character can't be represented in the output encoding, and it
+    /// occurs where a character reference isn't allowed, such as in a name,
+    /// a comment or a processing instruction.
+    UnencodableCharacter(char, String),
+    /// The bytes are not valid in the encoding. The position is the byte
+    /// offset of the first malformed byte sequence.
+    MalformedEncoding(String, usize),
     /// xmlparser error
     Parser(xmlparser::Error),
 
@@ -67,6 +120,69 @@ pub enum Error {
     Io(std::io::Error),
 }
 
+impl Error {
+    /// The span in the source text where a parse error occurred.
+    ///
+    /// Returns `None` for errors that don't originate in the parser, and for
+    /// errors reported by the underlying tokenizer, which carry their own
+    /// position.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let xml = "<a>\n  <foo:b/>\n</a>";
+    /// let err = xot.parse(xml).unwrap_err();
+    /// let span = err.span().unwrap();
+    /// assert_eq!(span.line_column(xml), (2, 4));
+    /// ```
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::UnclosedTag(span)
+            | Error::InvalidCloseTag(_, _, span)
+            | Error::UnclosedEntity(_, span)
+            | Error::InvalidEntity(_, span)
+            | Error::RecursiveEntity(_, span)
+            | Error::UnsupportedVersion(_, span)
+            | Error::RestrictedCharacter(_, span)
+            | Error::LimitExceeded(_, span) => Some(*span),
+            Error::UnknownPrefix(_, span)
+            | Error::DuplicateAttribute(_, span)
+            | Error::DuplicateExpandedAttribute(_, span)
+            | Error::ReservedPrefix(_, span)
+            | Error::ReservedNamespace(_, span)
+            | Error::EmptyPrefixedNamespace(_, span) => *span,
+            _ => None,
+        }
+    }
+
+    // The same error without its span, for errors that don't come from a
+    // source text even though we use the parser's machinery to produce them.
+    pub(crate) fn without_span(self) -> Self {
+        match self {
+            Error::UnknownPrefix(s, _) => Error::UnknownPrefix(s, None),
+            Error::DuplicateAttribute(s, _) => Error::DuplicateAttribute(s, None),
+            Error::DuplicateExpandedAttribute(s, _) => Error::DuplicateExpandedAttribute(s, None),
+            Error::ReservedPrefix(s, _) => Error::ReservedPrefix(s, None),
+            Error::ReservedNamespace(s, _) => Error::ReservedNamespace(s, None),
+            Error::EmptyPrefixedNamespace(s, _) => Error::EmptyPrefixedNamespace(s, None),
+            error => error,
+        }
+    }
+}
+
+// Where an error occurred, if we know.
+struct At(Option<Span>);
+
+impl std::fmt::Display for At {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.0 {
+            Some(span) => write!(f, " at {}", span),
+            None => Ok(()),
+        }
+    }
+}
+
 impl From<indextree::NodeError> for Error {
     #[inline]
     fn from(e: indextree::NodeError) -> Self {
@@ -95,6 +211,12 @@ impl std::fmt::Display for Error {
             Error::InvalidOperation(s) => write!(f, "Invalid operation: {}", s),
             Error::InvalidComment(s) => write!(f, "Invalid comment: {}", s),
             Error::InvalidTarget(s) => write!(f, "Invalid target: {}", s),
+            Error::InvalidName(s) => write!(f, "Invalid name: {}", s),
+            Error::InvalidPrefix(s) => write!(f, "Invalid prefix: {}", s),
+            Error::InvalidCharacter(c) => write!(f, "Invalid character: {:?}", c),
+            Error::InvalidProcessingInstructionData(s) => {
+                write!(f, "Invalid processing instruction data: {}", s)
+            }
             Error::NotElement(_) => write!(f, "Not an element"),
             Error::NodeError(e) => write!(f, "Node error: {}", e),
             Error::MissingPrefix(_) => write!(f, "Missing prefix"),
@@ -104,16 +226,43 @@ impl std::fmt::Display for Error {
             Error::NamespaceInProcessingInstruction => {
                 write!(f, "Namespace in processing instruction target")
             }
-            Error::UnclosedTag => write!(f, "Unclosed tag"),
-            Error::InvalidCloseTag(s, s2) => write!(f, "Invalid close tag: {} {}", s, s2),
-            Error::UnclosedEntity(s) => write!(f, "Unclosed entity: {}", s),
-            Error::InvalidEntity(s) => write!(f, "Invalid entity: {}", s),
-            Error::UnknownPrefix(s) => write!(f, "Unknown prefix: {}", s),
-            Error::DuplicateAttribute(s) => write!(f, "Duplicate attribute: {}", s),
-            Error::UnsupportedVersion(s) => write!(f, "Unsupported version: {}", s),
+            Error::UnclosedTag(span) => write!(f, "Unclosed tag at {}", span),
+            Error::InvalidCloseTag(s, s2, span) => {
+                write!(f, "Invalid close tag: {} {} at {}", s, s2, span)
+            }
+            Error::UnclosedEntity(s, span) => write!(f, "Unclosed entity: {} at {}", s, span),
+            Error::InvalidEntity(s, span) => write!(f, "Invalid entity: {} at {}", s, span),
+            Error::RecursiveEntity(s, span) => write!(f, "Recursive entity: {} at {}", s, span),
+            Error::UnknownPrefix(s, span) => write!(f, "Unknown prefix: {}{}", s, At(*span)),
+            Error::DuplicateAttribute(s, span) => {
+                write!(f, "Duplicate attribute: {}{}", s, At(*span))
+            }
+            Error::DuplicateExpandedAttribute(s, span) => {
+                write!(f, "Duplicate expanded attribute name: {}{}", s, At(*span))
+            }
+            Error::ReservedPrefix(s, span) => write!(f, "Reserved prefix: {}{}", s, At(*span)),
+            Error::ReservedNamespace(s, span) => {
+                write!(f, "Reserved namespace: {}{}", s, At(*span))
+            }
+            Error::EmptyPrefixedNamespace(s, span) => {
+                write!(f, "Empty namespace for prefix: {}{}", s, At(*span))
+            }
+            Error::UnsupportedVersion(s, span) => {
+                write!(f, "Unsupported version: {} at {}", s, span)
+            }
+            Error::LimitExceeded(limit, span) => {
+                write!(f, "Limit exceeded: {} at {}", limit, span)
+            }
             Error::UnsupportedEncoding(s) => write!(f, "Unsupported encoding: {}", s),
-            Error::UnsupportedNotStandalone => write!(f, "Unsupported standalone"),
-            Error::DtdUnsupported => write!(f, "DTD is not supported"),
+            Error::UnencodableCharacter(c, s) => {
+                write!(f, "Character {:?} cannot be encoded in {}", c, s)
+            }
+            Error::MalformedEncoding(s, position) => {
+                write!(f, "Malformed {} at byte {}", s, position)
+            }
+            Error::RestrictedCharacter(c, span) => {
+                write!(f, "Restricted character {:?} at {}", c, span)
+            }
             Error::Parser(e) => write!(f, "Parser error: {}", e),
             Error::Io(e) => write!(f, "IO error: {}", e),
         }
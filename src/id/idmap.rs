@@ -46,6 +46,11 @@ impl<K: Copy + IdIndex<K>, V: Eq + std::hash::Hash + Clone> IdMap<K, V> {
         self.by_value.get(value).copied()
     }
 
+    // the number of values in the map
+    pub(crate) fn len(&self) -> usize {
+        self.by_id.len()
+    }
+
     #[inline]
     pub(crate) fn get_value(&self, id: K) -> &V {
         &self.by_id[K::from_id(id)]
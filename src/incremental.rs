@@ -0,0 +1,593 @@
+// Incremental parsing: the document is given in chunks, and we only keep the
+// part of the text we haven't processed yet.
+//
+// xmlparser needs a complete text to tokenize, so each time we receive more
+// text we tokenize what we have, and process the tokens up to the last one
+// that we know is complete. The text up to that point is dropped, and the
+// rest is tokenized again once more text arrives.
+//
+// Before the document element we don't process anything until we've seen its
+// start, so that the prolog is tokenized in one go. After this we tokenize
+// text as a fragment, which means we have to reject what comes after the
+// document element ourselves.
+//
+// A tokenizer error may be caused by a token that's incomplete, so we usually
+// wait for more text. But once there's enough text after the error that it
+// can't be caused by that, we report it right away.
+use encoding_rs::Decoder;
+use xmlparser::{StreamError, TextPos, Token, Tokenizer};
+
+use crate::encoding::encoding;
+use crate::error::Error;
+use crate::parse::{DocumentBuilder, ParseOptions};
+use crate::xotdata::{Node, Xot};
+
+// the amount of bytes we look at to detect the encoding
+const DETECT_ENCODING_LENGTH: usize = 1024;
+
+// the amount of bytes after the position of a tokenizer error that rules out
+// that the error is caused by an incomplete token; the tokenizer never looks
+// this far ahead
+const ERROR_LOOKAHEAD: usize = 64;
+
+enum Phase {
+    Prolog,
+    Content,
+}
+
+pub(crate) struct IncrementalParser {
+    builder: DocumentBuilder,
+    phase: Phase,
+    // text that we haven't processed yet
+    buffer: String,
+    // if we couldn't make progress, we wait until the buffer is this long
+    // before we try again, so we don't tokenize the same text over and over
+    retry_length: usize,
+    // the line and column of the start of the buffer
+    line: u32,
+    column: u32,
+    // bytes we've received before we know the encoding
+    bytes: Vec<u8>,
+    decoder: Option<Decoder>,
+    text_fed: bool,
+}
+
+impl IncrementalParser {
+    pub(crate) fn new(xot: &mut Xot, options: ParseOptions) -> Result<Self, Error> {
+        Ok(Self {
+            builder: DocumentBuilder::new(xot, options, false)?,
+            phase: Phase::Prolog,
+            buffer: String::new(),
+            retry_length: 0,
+            line: 1,
+            column: 1,
+            bytes: Vec::new(),
+            decoder: None,
+            text_fed: false,
+        })
+    }
+
+    pub(crate) fn feed_str(&mut self, text: &str, xot: &mut Xot) -> Result<(), Error> {
+        if self.decoder.is_some() || !self.bytes.is_empty() {
+            return Err(Error::InvalidOperation(
+                "Cannot feed text after bytes".to_string(),
+            ));
+        }
+        self.text_fed = true;
+        self.buffer.push_str(text);
+        self.process(false, xot)
+    }
+
+    pub(crate) fn feed_bytes(&mut self, bytes: &[u8], xot: &mut Xot) -> Result<(), Error> {
+        if self.text_fed {
+            return Err(Error::InvalidOperation(
+                "Cannot feed bytes after text".to_string(),
+            ));
+        }
+        if self.decoder.is_none() {
+            self.bytes.extend_from_slice(bytes);
+            if self.bytes.len() < DETECT_ENCODING_LENGTH {
+                return Ok(());
+            }
+            self.start_decoding()?;
+        } else {
+            self.decode(bytes, false);
+        }
+        self.process(false, xot)
+    }
+
+    pub(crate) fn finish(mut self, xot: &mut Xot) -> Result<Node, Error> {
+        self.finish_input(xot)?;
+        self.finish_document(xot)
+    }
+
+    // Process what remains once there is no more input.
+    pub(crate) fn finish_input(&mut self, xot: &mut Xot) -> Result<(), Error> {
+        if self.decoder.is_none() && !self.bytes.is_empty() {
+            self.start_decoding()?;
+        }
+        if self.decoder.is_some() {
+            self.decode(&[], true);
+        }
+        self.process(true, xot)
+    }
+
+    // Get the document node once all input is processed.
+    pub(crate) fn finish_document(self, xot: &mut Xot) -> Result<Node, Error> {
+        let end = self.builder.offset + self.buffer.len();
+        let (document, _) = self.builder.finish(end, xot)?;
+        if let Some(decoder) = self.decoder {
+            xot.document_metadata
+                .entry(document)
+                .or_default()
+                .detected_encoding = Some(decoder.encoding().name().to_string());
+        }
+        Ok(document)
+    }
+
+    pub(crate) fn builder_mut(&mut self) -> &mut DocumentBuilder {
+        &mut self.builder
+    }
+
+    fn start_decoding(&mut self) -> Result<(), Error> {
+        let bytes = std::mem::take(&mut self.bytes);
+        // the decoder switches encoding if there's a BOM
+        self.decoder = Some(encoding(&bytes, None)?.new_decoder());
+        self.decode(&bytes, false);
+        Ok(())
+    }
+
+    fn decode(&mut self, bytes: &[u8], last: bool) {
+        let decoder = self.decoder.as_mut().unwrap();
+        if let Some(length) = decoder.max_utf8_buffer_length(bytes.len()) {
+            self.buffer.reserve(length);
+        }
+        let _ = decoder.decode_to_string(bytes, &mut self.buffer, last);
+    }
+
+    fn process(&mut self, last: bool, xot: &mut Xot) -> Result<(), Error> {
+        if !last && self.buffer.len() < self.retry_length {
+            return Ok(());
+        }
+        if let Phase::Prolog = self.phase {
+            match self.process_prolog(last, xot)? {
+                Some(end) => {
+                    self.consume(end);
+                    self.phase = Phase::Content;
+                }
+                None => {
+                    self.retry_length = self.buffer.len() * 2;
+                    return Ok(());
+                }
+            }
+        }
+        let end = self.process_content(last, xot)?;
+        self.retry_length = if end == 0 { self.buffer.len() * 2 } else { 0 };
+        self.consume(end);
+        Ok(())
+    }
+
+    // Process the prolog if it's complete, returning the position of the
+    // start of the document element.
+    fn process_prolog(&mut self, last: bool, xot: &mut Xot) -> Result<Option<usize>, Error> {
+        let mut tokens = Vec::new();
+        let mut document_element_start = None;
+        let mut error = None;
+        for token in Tokenizer::from(self.buffer.as_str()) {
+            match token {
+                Ok(Token::ElementStart { span, .. }) => {
+                    document_element_start = Some(span.start());
+                    break;
+                }
+                Ok(token) => tokens.push(token),
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+        let is_final = error
+            .as_ref()
+            .is_some_and(|error| is_final_error(error, &self.buffer));
+        if !last && !is_final && (error.is_some() || document_element_start.is_none()) {
+            return Ok(None);
+        }
+        for token in tokens {
+            self.builder.token(token, &self.buffer, xot)?;
+        }
+        if let Some(error) = error {
+            return Err(shift_error(error, self.line, self.column).into());
+        }
+        Ok(Some(document_element_start.unwrap_or(self.buffer.len())))
+    }
+
+    // Process the complete tokens in the buffer, returning the position
+    // after the last one.
+    fn process_content(&mut self, last: bool, xot: &mut Xot) -> Result<usize, Error> {
+        let length = self.buffer.len();
+        let mut pending = Vec::new();
+        let mut end = 0;
+        let mut error = None;
+        for token in Tokenizer::from_fragment(&self.buffer, 0..length) {
+            match token {
+                Ok(token) => {
+                    let complete_end = complete_token_end(&token, length, last);
+                    pending.push(token);
+                    if let Some(complete_end) = complete_end {
+                        for token in pending.drain(..) {
+                            content_token(
+                                &mut self.builder,
+                                &self.buffer,
+                                self.line,
+                                self.column,
+                                token,
+                                xot,
+                            )?;
+                        }
+                        end = complete_end;
+                    }
+                }
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+        if !last
+            && !error
+                .as_ref()
+                .is_some_and(|error| is_final_error(error, &self.buffer))
+        {
+            // an error may be caused by an incomplete token, so we try again
+            // once we have more text
+            return Ok(end);
+        }
+        for token in pending {
+            content_token(
+                &mut self.builder,
+                &self.buffer,
+                self.line,
+                self.column,
+                token,
+                xot,
+            )?;
+        }
+        if let Some(error) = error {
+            return Err(shift_error(error, self.line, self.column).into());
+        }
+        Ok(length)
+    }
+
+    // drop text we've processed
+    fn consume(&mut self, end: usize) {
+        let consumed = &self.buffer[..end];
+        let pos = text_pos(consumed, self.line, self.column);
+        self.line = pos.row;
+        self.column = pos.col;
+        self.builder.offset += end;
+        self.buffer.drain(..end);
+    }
+}
+
+// Process a token in the content, i.e. from the start of the document
+// element.
+fn content_token(
+    builder: &mut DocumentBuilder,
+    buffer: &str,
+    line: u32,
+    column: u32,
+    token: Token<'_>,
+    xot: &mut Xot,
+) -> Result<(), Error> {
+    if builder.is_document_element_closed(xot) {
+        // only whitespace, comments and processing instructions are allowed
+        // after the document element
+        let unexpected = match token {
+            Token::Comment { .. } | Token::ProcessingInstruction { .. } => None,
+            Token::Text { text } => {
+                match text
+                    .as_str()
+                    .find(|c| !matches!(c, ' ' | '\t' | '\r' | '\n'))
+                {
+                    Some(index) => Some(text.start() + index),
+                    None => return Ok(()),
+                }
+            }
+            Token::ElementStart { span, .. } | Token::Cdata { span, .. } => Some(span.start()),
+            _ => Some(0),
+        };
+        if let Some(position) = unexpected {
+            let pos = text_pos(&buffer[..position], line, column);
+            return Err(xmlparser::Error::UnknownToken(pos).into());
+        }
+    }
+    builder.token(token, buffer, xot)
+}
+
+/// A parser that you push XML into in chunks, as it becomes available.
+///
+/// You get one with [`Xot::push_parser`]. You then give it the XML in chunks
+/// with [`PushParser::feed`] (for bytes) or [`PushParser::feed_str`] (for
+/// text), and call [`PushParser::finish`] when there is no more to get the
+/// document node.
+///
+/// The result is the same as if you parsed the whole XML with
+/// [`Xot::parse_bytes`] or [`Xot::parse`] respectively, including the
+/// positions in errors. The chunks may be split anywhere, even in the middle
+/// of a tag or, for bytes, in the middle of a character. Only the part of the
+/// text that couldn't be processed yet is kept in memory.
+///
+/// An error may be reported by `feed` or by `finish`. Malformed markup is
+/// reported by `feed` as soon as the text after it shows that it's not just
+/// incomplete, so you don't have to feed the rest of the input to learn that
+/// it's broken. Once you have an error, the parser shouldn't be used any
+/// further.
+///
+/// ```rust
+/// use xot::Xot;
+///
+/// let mut xot = Xot::new();
+/// let mut parser = xot.push_parser();
+/// parser.feed(b"<doc><p>Hel")?;
+/// parser.feed(b"lo</p></d")?;
+/// parser.feed(b"oc>")?;
+/// let document = parser.finish()?;
+///
+/// assert_eq!(xot.to_string(document)?, "<doc><p>Hello</p></doc>");
+/// # Ok::<(), xot::Error>(())
+/// ```
+pub struct PushParser<'a> {
+    xot: &'a mut Xot,
+    parser: IncrementalParser,
+}
+
+impl<'a> PushParser<'a> {
+    pub(crate) fn new(xot: &'a mut Xot, options: ParseOptions) -> Result<Self, Error> {
+        let parser = IncrementalParser::new(xot, options)?;
+        Ok(Self { xot, parser })
+    }
+
+    /// Feed a chunk of bytes.
+    ///
+    /// The encoding is detected in the same way as [`Xot::parse_bytes`]
+    /// does, and is recorded in the [`Xot::document_metadata`] of the
+    /// document. You cannot mix this with [`PushParser::feed_str`].
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.parser.feed_bytes(bytes, self.xot)
+    }
+
+    /// Feed a chunk of text.
+    ///
+    /// You cannot mix this with [`PushParser::feed`].
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let mut parser = xot.push_parser();
+    /// parser.feed_str("<doc a=\"")?;
+    /// parser.feed_str("A\"/>")?;
+    /// let document = parser.finish()?;
+    ///
+    /// assert_eq!(xot.to_string(document)?, r#"<doc a="A"/>"#);
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn feed_str(&mut self, text: &str) -> Result<(), Error> {
+        self.parser.feed_str(text, self.xot)
+    }
+
+    /// Finish parsing, and get the document node.
+    ///
+    /// This reports an error if the XML is incomplete, for instance if
+    /// an element is unclosed.
+    pub fn finish(self) -> Result<Node, Error> {
+        self.parser.finish(self.xot)
+    }
+}
+
+// The end of a token if we know it's complete. Text is only complete once we
+// see what comes after it.
+fn complete_token_end(token: &Token<'_>, length: usize, last: bool) -> Option<usize> {
+    match token {
+        Token::Text { text } => (text.end() < length || last).then(|| text.end()),
+        Token::ElementEnd { span, .. }
+        | Token::Comment { span, .. }
+        | Token::Cdata { span, .. }
+        | Token::ProcessingInstruction { span, .. } => Some(span.end()),
+        _ => None,
+    }
+}
+
+// The position after the given text, if it starts at line and column.
+fn text_pos(text: &str, line: u32, column: u32) -> TextPos {
+    match text.rfind('\n') {
+        Some(index) => TextPos::new(
+            line + text.matches('\n').count() as u32,
+            text[index + 1..].chars().count() as u32 + 1,
+        ),
+        None => TextPos::new(line, column + text.chars().count() as u32),
+    }
+}
+
+// Whether a tokenizer error can't be caused by the text ending early: the
+// tokenizer didn't run out of text, and there's enough text after the
+// position of the error.
+fn is_final_error(error: &xmlparser::Error, buffer: &str) -> bool {
+    use xmlparser::Error::*;
+
+    let stream_error = match error {
+        InvalidDeclaration(e, _)
+        | InvalidComment(e, _)
+        | InvalidPI(e, _)
+        | InvalidDoctype(e, _)
+        | InvalidEntity(e, _)
+        | InvalidElement(e, _)
+        | InvalidAttribute(e, _)
+        | InvalidCdata(e, _)
+        | InvalidCharData(e, _) => Some(e),
+        UnknownToken(_) => None,
+    };
+    // the position of the stream error is more precise, if there is one
+    let pos = match stream_error {
+        Some(StreamError::UnexpectedEndOfStream) => return false,
+        Some(
+            StreamError::NonXmlChar(_, pos)
+            | StreamError::InvalidChar(_, _, pos)
+            | StreamError::InvalidCharMultiple(_, _, pos)
+            | StreamError::InvalidQuote(_, pos)
+            | StreamError::InvalidSpace(_, pos)
+            | StreamError::InvalidString(_, pos),
+        ) => *pos,
+        _ => error.pos(),
+    };
+    buffer.len() - text_offset(buffer, pos) >= ERROR_LOOKAHEAD
+}
+
+// The byte offset of a position in the text.
+fn text_offset(text: &str, pos: TextPos) -> usize {
+    let line_start: usize = text
+        .split_inclusive('\n')
+        .take(pos.row as usize - 1)
+        .map(str::len)
+        .sum();
+    let column: usize = text[line_start..]
+        .chars()
+        .take(pos.col as usize - 1)
+        .map(char::len_utf8)
+        .sum();
+    line_start + column
+}
+
+// xmlparser reports positions relative to the text it tokenizes, so we make
+// them relative to the document.
+fn shift_error(error: xmlparser::Error, line: u32, column: u32) -> xmlparser::Error {
+    use xmlparser::Error::*;
+
+    let shift = |pos: TextPos| {
+        if pos.row == 1 {
+            TextPos::new(line, column + pos.col - 1)
+        } else {
+            TextPos::new(line + pos.row - 1, pos.col)
+        }
+    };
+    let shift_stream = |error: StreamError| match error {
+        StreamError::NonXmlChar(c, pos) => StreamError::NonXmlChar(c, shift(pos)),
+        StreamError::InvalidChar(actual, expected, pos) => {
+            StreamError::InvalidChar(actual, expected, shift(pos))
+        }
+        StreamError::InvalidCharMultiple(actual, expected, pos) => {
+            StreamError::InvalidCharMultiple(actual, expected, shift(pos))
+        }
+        StreamError::InvalidQuote(c, pos) => StreamError::InvalidQuote(c, shift(pos)),
+        StreamError::InvalidSpace(c, pos) => StreamError::InvalidSpace(c, shift(pos)),
+        StreamError::InvalidString(s, pos) => StreamError::InvalidString(s, shift(pos)),
+        error => error,
+    };
+    match error {
+        InvalidDeclaration(e, pos) => InvalidDeclaration(shift_stream(e), shift(pos)),
+        InvalidComment(e, pos) => InvalidComment(shift_stream(e), shift(pos)),
+        InvalidPI(e, pos) => InvalidPI(shift_stream(e), shift(pos)),
+        InvalidDoctype(e, pos) => InvalidDoctype(shift_stream(e), shift(pos)),
+        InvalidEntity(e, pos) => InvalidEntity(shift_stream(e), shift(pos)),
+        InvalidElement(e, pos) => InvalidElement(shift_stream(e), shift(pos)),
+        InvalidAttribute(e, pos) => InvalidAttribute(shift_stream(e), shift(pos)),
+        InvalidCdata(e, pos) => InvalidCdata(shift_stream(e), shift(pos)),
+        InvalidCharData(e, pos) => InvalidCharData(shift_stream(e), shift(pos)),
+        UnknownToken(pos) => UnknownToken(shift(pos)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // parse xml in chunks of the given size, and compare with a regular
+    // parse
+    fn assert_same_in_chunks(xml: &str) {
+        let mut xot = Xot::new();
+        let expected = xot.parse(xml).map(|root| xot.to_string(root).unwrap());
+        for size in 1..=xml.len() {
+            let mut parser = IncrementalParser::new(&mut xot, ParseOptions::default()).unwrap();
+            let mut result = Ok(());
+            for chunk in xml.as_bytes().chunks(size) {
+                result = parser.feed_str(std::str::from_utf8(chunk).unwrap(), &mut xot);
+                if result.is_err() {
+                    break;
+                }
+            }
+            let result = result
+                .and_then(|_| parser.finish(&mut xot))
+                .map(|root| xot.to_string(root).unwrap());
+            match (&expected, &result) {
+                (Ok(expected), Ok(result)) => assert_eq!(expected, result, "chunk size {}", size),
+                (Err(expected), Err(result)) => assert_eq!(
+                    expected.to_string(),
+                    result.to_string(),
+                    "chunk size {}",
+                    size
+                ),
+                _ => panic!(
+                    "chunk size {}: expected {:?}, got {:?}",
+                    size, expected, result
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunks() {
+        assert_same_in_chunks(
+            r#"<?xml version="1.0"?><!DOCTYPE doc [<!ENTITY e "entity">]><!--c--><doc a="&e;" xmlns:x="http://example.com"><x:p>text &amp; &e;</x:p><![CDATA[<cdata>]]><?pi data?><empty/></doc><!--after-->"#,
+        );
+    }
+
+    #[test]
+    fn test_chunks_whitespace() {
+        assert_same_in_chunks("\n<doc>\n  <p>a</p>\n</doc>\n<!--after-->\n");
+    }
+
+    #[test]
+    fn test_chunks_errors() {
+        assert_same_in_chunks("<doc>\n<p>&unknown;</p></doc>");
+        assert_same_in_chunks("<doc>\n<p></q></doc>");
+        assert_same_in_chunks("<doc>\n <p a='1' a='2'/></doc>");
+        assert_same_in_chunks("<doc>\n <p><!-- -- --></p></doc>");
+        assert_same_in_chunks("<doc>\n <p>\n</doc>");
+        assert_same_in_chunks("<doc/>\n text");
+        assert_same_in_chunks("<doc/>\n<doc/>");
+        assert_same_in_chunks("<doc/>\n<![CDATA[x]]>");
+    }
+
+    #[test]
+    fn test_chunks_errors_with_text_after() {
+        let after = "<p>text</p>".repeat(10);
+        assert_same_in_chunks(&format!("<doc>\n<p a=1/>{}</doc>", after));
+        assert_same_in_chunks(&format!("<doc>\n<p a='<'/>{}</doc>", after));
+        assert_same_in_chunks(&format!("<doc>\n<!- x -->{}</doc>", after));
+        assert_same_in_chunks(&format!("<doc>\n<![CDAT[x]]>{}</doc>", after));
+        assert_same_in_chunks(&format!("<!DOCTYPE doc [<!ENTTY e 'x'>]>{}<doc/>", after));
+        assert_same_in_chunks(&format!("<!- x -->{}<doc/>", after));
+    }
+
+    #[test]
+    fn test_bytes_detect_encoding() {
+        let mut xot = Xot::new();
+        let mut parser = IncrementalParser::new(&mut xot, ParseOptions::default()).unwrap();
+        let xml = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>\xe9</p>";
+        for chunk in xml.chunks(3) {
+            parser.feed_bytes(chunk, &mut xot).unwrap();
+        }
+        let root = parser.finish(&mut xot).unwrap();
+        assert_eq!(
+            xot.to_string(root).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<p>\u{e9}</p>"
+        );
+        assert_eq!(
+            xot.document_metadata(root)
+                .unwrap()
+                .detected_encoding
+                .as_deref(),
+            Some("windows-1252")
+        );
+    }
+}
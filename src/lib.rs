@@ -61,11 +61,14 @@
 
 mod access;
 mod creation;
+mod document;
 mod encoding;
 mod entity;
 mod error;
 pub mod fixed;
+mod html5entities;
 mod id;
+mod incremental;
 mod levelorder;
 mod manipulation;
 mod nameaccess;
@@ -74,23 +77,30 @@ pub mod output;
 mod parse;
 #[cfg(feature = "proptest")]
 pub mod proptest;
+mod reader;
 mod serialize;
+mod treebuilder;
 
 mod unpretty;
+mod validate;
 mod valueaccess;
 pub mod xmlname;
 mod xmlvalue;
 mod xotdata;
 
 pub use access::{Axis, NodeEdge};
+pub use document::{DocumentMetadata, DocumentType};
 pub use error::Error;
 pub use id::{NameId, NamespaceId, PrefixId};
+pub use incremental::PushParser;
 pub use levelorder::LevelOrder;
 pub use nodemap::{
     Attributes, Entry, MutableAttributes, MutableNamespaces, MutableNodeMap, Namespaces, NodeMap,
 };
-pub use parse::{Span, SpanInfo, SpanInfoKey};
+pub use parse::{Diagnostic, ParseLimit, ParseLimits, ParseOptions, Span, SpanInfo, SpanInfoKey};
+pub use reader::{Event, Reader};
 pub use serialize::Html5;
+pub use treebuilder::TreeBuilder;
 pub use xmlvalue::{
     Attribute, Comment, Element, Namespace, Prefixes, ProcessingInstruction, Text, Value, ValueType,
 };
@@ -29,7 +29,8 @@ use crate::xmlvalue::{Value, ValueCategory, ValueType};
 /// second text node is removed.
 ///
 /// During parsing it's also guaranteed that text and CDATA content that is
-/// adjacent is consolidated into a single node.
+/// adjacent is consolidated into a single node, unless you turn this off
+/// with [`ParseOptions`](crate::ParseOptions).
 ///
 /// You can disable and enable text consolidation using
 /// [`Xot::set_text_consolidation`].
@@ -477,6 +478,10 @@ impl Xot {
         let next_node = self.next_sibling(node);
         node.get().remove_subtree(self.arena_mut());
         self.remove_consolidate_text_nodes(prev_node, next_node);
+        // the node may be reused for a new document, which shouldn't get
+        // the document type declaration or metadata of this one
+        self.document_types.remove(&node);
+        self.document_metadata.remove(&node);
         Ok(())
     }
 
@@ -637,6 +642,12 @@ impl Xot {
             }
         }
         if self.is_document(node) {
+            if let Some(document_type) = self.document_type(node).cloned() {
+                self.document_types.insert(top, document_type);
+            }
+            if let Some(metadata) = self.document_metadata(node).cloned() {
+                self.document_metadata.insert(top, metadata);
+            }
             top
         } else {
             // remove the temporary element unless we cloned the document node
@@ -999,28 +1010,31 @@ impl Xot {
             return false;
         }
         let added_text = if let Value::Text(t) = self.value(node) {
-            Some(t.get().to_string())
+            Some((t.get().to_string(), t.is_cdata()))
         } else {
             None
         };
         if added_text.is_none() {
             return false;
         }
-        let added_text = added_text.unwrap();
+        // text is only consolidated with text that has the same CDATA flag,
+        // so that it's serialized the same way
+        let (added_text, cdata) = added_text.unwrap();
 
         // if consolidation is turned off, then we could have two adjacent
         // text nodes. Prefer to consolidate with the previous node.
         let consolidated = if let Some(prev_node) = prev_node {
-            if let Value::Text(prev) = self.value_mut(prev_node) {
-                let mut s = prev.get().to_string();
-                s.push_str(&added_text);
-                prev.set(s);
-                // remove the text node we wanted to insert as it's now consolidated
-                // we can always remove text nodes safely.
-                self.remove_dangerously(node);
-                true
-            } else {
-                false
+            match self.value_mut(prev_node) {
+                Value::Text(prev) if prev.is_cdata() == cdata => {
+                    let mut s = prev.get().to_string();
+                    s.push_str(&added_text);
+                    prev.set(s);
+                    // remove the text node we wanted to insert as it's now consolidated
+                    // we can always remove text nodes safely.
+                    self.remove_dangerously(node);
+                    true
+                }
+                _ => false,
             }
         } else {
             false
@@ -1031,16 +1045,17 @@ impl Xot {
         // we couldn't consolidate with the previous node, try to consolidate
         // with the next node
         if let Some(next_node) = next_node {
-            if let Value::Text(next) = self.value_mut(next_node) {
-                let mut s = added_text;
-                s.push_str(next.get());
-                next.set(s);
-                // remove the text node we wanted to insert as it's now consolidated
-                // we can always remove text nodes safely.
-                self.remove_dangerously(node);
-                true
-            } else {
-                false
+            match self.value_mut(next_node) {
+                Value::Text(next) if next.is_cdata() == cdata => {
+                    let mut s = added_text;
+                    s.push_str(next.get());
+                    next.set(s);
+                    // remove the text node we wanted to insert as it's now consolidated
+                    // we can always remove text nodes safely.
+                    self.remove_dangerously(node);
+                    true
+                }
+                _ => false,
             }
         } else {
             false
@@ -1068,6 +1083,9 @@ impl Xot {
         if prev_text.is_none() || next_text.is_none() {
             return false;
         }
+        if prev_text.unwrap().is_cdata() != next_text.unwrap().is_cdata() {
+            return false;
+        }
         let to_add = next_text.unwrap().get().to_string();
 
         let prev_text_mut = self.text_mut(prev_node).unwrap();
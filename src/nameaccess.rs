@@ -6,6 +6,7 @@ use crate::access::NodeEdge;
 use crate::error::Error;
 use crate::id::{Name, NameId, NamespaceId, PrefixId};
 use crate::output::FullnameSerializer;
+use crate::validate::{validate_name, validate_prefix};
 use crate::xmlvalue::Prefixes;
 use crate::xotdata::{Node, Xot};
 use crate::{xmlname, Value};
@@ -85,6 +86,24 @@ impl Xot {
         self.add_name_ns(name, self.no_namespace_id)
     }
 
+    /// Add name without a namespace, checking that it's valid.
+    ///
+    /// Like [`Xot::add_name`], but if the name is not an `NCName` you get
+    /// [`Error::InvalidName`].
+    ///
+    /// ```rust
+    /// use xot::{Error, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    ///
+    /// assert!(xot.add_name_checked("a").is_ok());
+    /// assert!(matches!(xot.add_name_checked("a b"), Err(Error::InvalidName(_))));
+    /// ```
+    pub fn add_name_checked(&mut self, name: &str) -> Result<NameId, Error> {
+        validate_name(name)?;
+        Ok(self.add_name(name))
+    }
+
     /// Look up name with a namespace.
     ///
     /// ```rust
@@ -148,6 +167,19 @@ impl Xot {
         self.name_lookup.get_id_mut(&Name::new(name, namespace_id))
     }
 
+    /// Add name with a namespace, checking that it's valid.
+    ///
+    /// Like [`Xot::add_name_ns`], but if the name is not an `NCName` you get
+    /// [`Error::InvalidName`].
+    pub fn add_name_ns_checked(
+        &mut self,
+        name: &str,
+        namespace_id: NamespaceId,
+    ) -> Result<NameId, Error> {
+        validate_name(name)?;
+        Ok(self.add_name_ns(name, namespace_id))
+    }
+
     /// Look up namespace.
     ///
     /// This is the immutable version of [`Xot::add_namespace`]; it returns
@@ -178,6 +210,15 @@ impl Xot {
         self.prefix_lookup.get_id_mut(prefix)
     }
 
+    /// Add prefix, checking that it's valid.
+    ///
+    /// Like [`Xot::add_prefix`], but if the prefix is not empty and not an
+    /// `NCName` you get [`Error::InvalidPrefix`].
+    pub fn add_prefix_checked(&mut self, prefix: &str) -> Result<PrefixId, Error> {
+        validate_prefix(prefix)?;
+        Ok(self.add_prefix(prefix))
+    }
+
     /// No namespace
     ///
     /// Returns the namespace id used when an element or attribute
@@ -0,0 +1,64 @@
+//! Canonical XML output.
+//!
+//! The main entry point is [`Parameters`], which you can pass into
+//! [`Xot::serialize_c14n_string`] and [`Xot::serialize_c14n_write`].
+//!
+//! This implements [Canonical XML 1.0](https://www.w3.org/TR/xml-c14n) and
+//! [Exclusive XML Canonicalization 1.0](https://www.w3.org/TR/xml-exc-c14n/),
+//! both with and without comments. Canonical XML is byte-exact, so you can
+//! use it to sign documents or to hash their content.
+//!
+//! If you serialize a node other than the document node, the result is the
+//! canonical form of the document subset that consists of that node and its
+//! descendants.
+
+// Here is how we diverge from the specifications:
+//
+// * There is no DTD processing beyond what happens when Xot parses, so
+//   attribute defaults declared in a DTD are not added.
+// * Xot doesn't keep text outside of the document element, so there is no
+//   whitespace to drop there.
+// * Document subsets are limited to a node and its descendants; there is no
+//   XPath node-set selection.
+
+#[cfg(doc)]
+use crate::Xot;
+
+/// Parameters for canonical XML.
+///
+/// ```rust
+/// use xot::{output, Xot};
+///
+/// let mut xot = Xot::new();
+/// let root = xot.parse(r#"<doc b="2" a="1"><!--c--><e/></doc>"#)?;
+///
+/// let c14n = xot.serialize_c14n_string(output::c14n::Parameters::default(), root)?;
+/// assert_eq!(c14n, r#"<doc a="1" b="2"><e></e></doc>"#);
+/// # Ok::<(), xot::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Parameters {
+    /// The canonicalization method.
+    pub method: Method,
+    /// Whether to include comments. By default comments are left out.
+    pub with_comments: bool,
+}
+
+/// A canonicalization method.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Method {
+    /// Canonical XML 1.0. All namespace declarations in scope are rendered
+    /// where they're not already in effect, and the `xml:` attributes of the
+    /// ancestors of the serialized element are inherited by it.
+    #[default]
+    Inclusive,
+    /// Exclusive XML Canonicalization 1.0. A namespace declaration is only
+    /// rendered on an element whose name or attributes use its prefix,
+    /// unless the prefix is in the inclusive namespaces prefix list.
+    Exclusive {
+        /// The inclusive namespaces prefix list. The prefixes in this list
+        /// are treated as in Canonical XML 1.0. Use `#default` for the
+        /// default namespace.
+        inclusive_prefixes: Vec<String>,
+    },
+}
@@ -0,0 +1,284 @@
+use std::io;
+
+use crate::error::Error;
+use crate::id::{NameId, NamespaceId, PrefixId};
+use crate::xotdata::{Node, Xot};
+
+use super::c14n::{Method, Parameters};
+use super::fullname::FullnameSerializer;
+use super::Output;
+
+pub(crate) struct C14nSerializer<'a> {
+    xot: &'a Xot,
+    top: Node,
+    with_comments: bool,
+    // the inclusive namespaces prefix list for exclusive canonicalization;
+    // `None` for inclusive canonicalization
+    inclusive_prefixes: Option<Vec<PrefixId>>,
+    fullname_serializer: FullnameSerializer<'a>,
+    // the namespaces in effect in the output, for each open element
+    rendered: Vec<Vec<(PrefixId, NamespaceId)>>,
+    // the start tag we're in, and its attributes
+    element: Option<(Node, NameId)>,
+    attributes: Vec<(NameId, &'a str)>,
+    after_document_element: bool,
+}
+
+impl<'a> C14nSerializer<'a> {
+    pub(crate) fn new(xot: &'a Xot, node: Node, parameters: &Parameters) -> Self {
+        let inclusive_prefixes = match &parameters.method {
+            Method::Inclusive => None,
+            Method::Exclusive { inclusive_prefixes } => Some(
+                inclusive_prefixes
+                    .iter()
+                    .filter_map(|prefix| {
+                        if prefix == "#default" {
+                            Some(xot.empty_prefix())
+                        } else {
+                            xot.prefix(prefix)
+                        }
+                    })
+                    .collect(),
+            ),
+        };
+        let extra_declarations = xot.namespaces_in_scope(node).collect();
+        Self {
+            xot,
+            top: node,
+            with_comments: parameters.with_comments,
+            inclusive_prefixes,
+            fullname_serializer: FullnameSerializer::new(xot, extra_declarations),
+            rendered: vec![Vec::new()],
+            element: None,
+            attributes: Vec::new(),
+            after_document_element: false,
+        }
+    }
+
+    pub(crate) fn serialize<W: io::Write>(
+        &mut self,
+        w: &mut W,
+        outputs: impl Iterator<Item = (Node, Output<'a>)>,
+    ) -> Result<(), Error> {
+        for (node, output) in outputs {
+            let text = self.render_output(node, output)?;
+            w.write_all(text.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn render_output(&mut self, node: Node, output: Output<'a>) -> Result<String, Error> {
+        use Output::*;
+        let r = match output {
+            StartTagOpen(element) => {
+                self.fullname_serializer
+                    .push(self.xot.namespace_declarations(node));
+                self.element = Some((node, element.name()));
+                format!(
+                    "<{}",
+                    self.fullname_serializer.element_fullname(element.name())?
+                )
+            }
+            // we determine the namespace declarations to render ourselves
+            Prefix(..) => String::new(),
+            Attribute(name_id, value) => {
+                self.attributes.push((name_id, value));
+                String::new()
+            }
+            StartTagClose => {
+                let (node, name_id) = self.element.take().unwrap();
+                let mut s = String::new();
+                for (prefix_id, namespace_id) in self.namespaces(name_id)? {
+                    let prefix = self.xot.prefix_str(prefix_id);
+                    let namespace = escape_attribute(self.xot.namespace_str(namespace_id));
+                    if prefix.is_empty() {
+                        s.push_str(&format!(" xmlns=\"{}\"", namespace));
+                    } else {
+                        s.push_str(&format!(" xmlns:{}=\"{}\"", prefix, namespace));
+                    }
+                }
+                for (name_id, value) in self.attributes(node) {
+                    s.push_str(&format!(
+                        " {}=\"{}\"",
+                        self.fullname_serializer.attribute_fullname(name_id)?,
+                        escape_attribute(value)
+                    ));
+                }
+                s.push('>');
+                s
+            }
+            EndTag(element) => {
+                // an empty element is written with a start and end tag
+                let s = format!(
+                    "</{}>",
+                    self.fullname_serializer.element_fullname(element.name())?
+                );
+                self.fullname_serializer
+                    .pop(self.xot.has_namespace_declarations(node));
+                self.rendered.pop();
+                if self.is_top_level(node) {
+                    self.after_document_element = true;
+                }
+                s
+            }
+            Text(text) => escape_text(text),
+            Comment(text) => {
+                if self.with_comments {
+                    self.top_level(node, format!("<!--{}-->", text))
+                } else {
+                    String::new()
+                }
+            }
+            ProcessingInstruction(target, data) => {
+                let (target, ns) = self.xot.name_ns_str(target);
+                if !ns.is_empty() {
+                    return Err(Error::NamespaceInProcessingInstruction);
+                }
+                let s = match data {
+                    Some(data) if !data.is_empty() => format!("<?{} {}?>", target, data),
+                    _ => format!("<?{}?>", target),
+                };
+                self.top_level(node, s)
+            }
+        };
+        Ok(r)
+    }
+
+    fn is_top_level(&self, node: Node) -> bool {
+        self.xot
+            .parent(node)
+            .is_some_and(|parent| self.xot.is_document(parent))
+    }
+
+    // comments and processing instructions outside of the document element
+    // are separated from it by a line feed
+    fn top_level(&self, node: Node, s: String) -> String {
+        if !self.is_top_level(node) {
+            s
+        } else if self.after_document_element {
+            format!("\n{}", s)
+        } else {
+            format!("{}\n", s)
+        }
+    }
+
+    // The namespace declarations to render on the element, sorted by prefix,
+    // with the default namespace first. We also record what's in effect for
+    // the content of the element.
+    fn namespaces(&mut self, name_id: NameId) -> Result<Vec<(PrefixId, NamespaceId)>, Error> {
+        let xot = self.xot;
+        let empty_prefix = xot.empty_prefix();
+        let mut in_scope: Vec<(PrefixId, NamespaceId)> = self
+            .fullname_serializer
+            .namespaces()
+            .iter()
+            .filter(|(_, namespace_id)| *namespace_id != xot.xml_namespace())
+            .copied()
+            .collect();
+        // an element in no namespace is written without a prefix, so the
+        // default namespace is undeclared for it
+        let default = if xot.namespace_for_name(name_id) == xot.no_namespace() {
+            Some(xot.no_namespace())
+        } else {
+            in_scope
+                .iter()
+                .find(|(prefix_id, _)| *prefix_id == empty_prefix)
+                .map(|(_, namespace_id)| *namespace_id)
+        };
+        in_scope.retain(|(prefix_id, _)| *prefix_id != empty_prefix);
+        in_scope.push((empty_prefix, default.unwrap_or(xot.no_namespace())));
+
+        if let Some(inclusive_prefixes) = &self.inclusive_prefixes {
+            // only the prefixes that are visibly utilized
+            let mut utilized = vec![self
+                .fullname_serializer
+                .element_prefix(name_id)?
+                .unwrap_or(empty_prefix)];
+            for (name_id, _) in &self.attributes {
+                if let Some(prefix_id) = self.fullname_serializer.attribute_prefix(*name_id)? {
+                    utilized.push(prefix_id);
+                }
+            }
+            in_scope.retain(|(prefix_id, _)| {
+                utilized.contains(prefix_id) || inclusive_prefixes.contains(prefix_id)
+            });
+        }
+
+        let mut in_effect = self.rendered.last().unwrap().clone();
+        let mut namespaces = Vec::new();
+        for (prefix_id, namespace_id) in in_scope {
+            let current = in_effect
+                .iter()
+                .find(|(p, _)| *p == prefix_id)
+                .map(|(_, n)| *n)
+                .or((prefix_id == empty_prefix).then_some(xot.no_namespace()));
+            if current != Some(namespace_id) {
+                namespaces.push((prefix_id, namespace_id));
+                in_effect.retain(|(p, _)| *p != prefix_id);
+                in_effect.push((prefix_id, namespace_id));
+            }
+        }
+        self.rendered.push(in_effect);
+        namespaces.sort_by_key(|(prefix_id, _)| xot.prefix_str(*prefix_id));
+        Ok(namespaces)
+    }
+
+    // The attributes of the element, sorted by namespace URI and then by
+    // local name, with the attributes in no namespace first.
+    fn attributes(&mut self, node: Node) -> Vec<(NameId, &'a str)> {
+        let xot = self.xot;
+        let mut attributes = std::mem::take(&mut self.attributes);
+        // the element we serialize inherits the xml: attributes of its
+        // ancestors in Canonical XML 1.0
+        if node == self.top && self.inclusive_prefixes.is_none() {
+            for ancestor in xot.ancestors(node).skip(1) {
+                if !xot.is_element(ancestor) {
+                    break;
+                }
+                for (name_id, value) in xot.attributes(ancestor).iter() {
+                    if xot.namespace_for_name(name_id) == xot.xml_namespace()
+                        && !attributes.iter().any(|(n, _)| *n == name_id)
+                    {
+                        attributes.push((name_id, value.as_str()));
+                    }
+                }
+            }
+        }
+        attributes.sort_by_key(|(name_id, _)| {
+            let (local_name, namespace) = xot.name_ns_str(*name_id);
+            (namespace, local_name)
+        });
+        attributes
+    }
+}
+
+// https://www.w3.org/TR/xml-c14n#ProcessingModel
+fn escape_text(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    for c in content.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '\r' => result.push_str("&#xD;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+fn escape_attribute(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    for c in content.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '"' => result.push_str("&quot;"),
+            '\t' => result.push_str("&#x9;"),
+            '\n' => result.push_str("&#xA;"),
+            '\r' => result.push_str("&#xD;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
@@ -1,10 +1,39 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::xotdata::Xot;
 use crate::NameId;
 
 /// Indentation: pretty-print XML or HTML.
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Indentation {
     /// A list of element names where indentation changes are suppressed.
     pub suppress: Vec<NameId>,
+    /// The string used for each level of indentation. By default this is
+    /// two spaces.
+    pub indent: String,
+    /// The maximum width of a line, in characters. This is used to decide
+    /// whether to wrap attributes, see [`Indentation::wrap_attributes`].
+    /// Text is never wrapped.
+    pub max_width: Option<usize>,
+    /// Whether to wrap the attributes of a start tag that would make its line
+    /// wider than [`Indentation::max_width`]. Each attribute and namespace
+    /// declaration is then put on a line of its own, indented one level
+    /// deeper than the element.
+    pub wrap_attributes: bool,
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Self {
+            suppress: Vec::new(),
+            indent: "  ".to_string(),
+            max_width: None,
+            wrap_attributes: false,
+        }
+    }
 }
 
 /// Parameters used when serializing tokens.
@@ -19,4 +48,100 @@ pub struct TokenSerializeParameters {
     /// `>` is not escaped, except for the special case of `]]>` outside of CDATA,
     /// which is mandated by the XML specification to always be escaped.
     pub unescaped_gt: bool,
+
+    /// A character map: characters in text and attribute values that are
+    /// replaced by a string, which is output as is, without escaping.
+    pub character_map: HashMap<char, String>,
+
+    /// The order in which attributes are serialized.
+    pub attribute_order: AttributeOrder,
+
+    /// The order in which namespace declarations are serialized.
+    pub namespace_order: NamespaceOrder,
+}
+
+/// A comparison function for [`AttributeOrder::Custom`].
+pub type AttributeCompare = Arc<dyn Fn(&Xot, NameId, NameId) -> Ordering + Send + Sync>;
+
+/// The order in which the attributes of an element are serialized.
+///
+/// Attributes are always serialized after the namespace declarations of
+/// the element.
+#[derive(Clone, Default)]
+pub enum AttributeOrder {
+    /// The order of the attributes in the tree, which is the order in which
+    /// they were parsed or added.
+    #[default]
+    Insertion,
+    /// Sorted by expanded name: by namespace URI first, and then by local
+    /// name. Attributes in no namespace come first.
+    ExpandedName,
+    /// Sorted by qualified name as it's serialized, such as `a` or `x:a`.
+    QualifiedName,
+    /// Sorted by a comparison function of your own. Attributes that compare
+    /// equal keep their order in the tree.
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use xot::{output, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse(r#"<doc b="B" id="1" a="A"/>"#)?;
+    /// let id = xot.add_name("id");
+    ///
+    /// // the id attribute first, then the rest by local name
+    /// let compare: output::AttributeCompare = Arc::new(move |xot, a, b| {
+    ///     (a != id)
+    ///         .cmp(&(b != id))
+    ///         .then_with(|| xot.local_name_str(a).cmp(xot.local_name_str(b)))
+    /// });
+    /// let xml = xot.serialize_xml_string(
+    ///     output::xml::Parameters {
+    ///         attribute_order: output::AttributeOrder::Custom(compare),
+    ///         ..Default::default()
+    ///     },
+    ///     root,
+    /// )?;
+    /// assert_eq!(xml, r#"<doc id="1" a="A" b="B"/>"#);
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    Custom(AttributeCompare),
+}
+
+impl fmt::Debug for AttributeOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeOrder::Insertion => write!(f, "Insertion"),
+            AttributeOrder::ExpandedName => write!(f, "ExpandedName"),
+            AttributeOrder::QualifiedName => write!(f, "QualifiedName"),
+            AttributeOrder::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+// custom orders are only equal if they use the same function
+impl PartialEq for AttributeOrder {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (AttributeOrder::Insertion, AttributeOrder::Insertion)
+            | (AttributeOrder::ExpandedName, AttributeOrder::ExpandedName)
+            | (AttributeOrder::QualifiedName, AttributeOrder::QualifiedName) => true,
+            (AttributeOrder::Custom(a), AttributeOrder::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for AttributeOrder {}
+
+/// The order in which the namespace declarations of an element are
+/// serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NamespaceOrder {
+    /// The order of the namespace declarations in the tree, which is the
+    /// order in which they were parsed or added.
+    #[default]
+    Insertion,
+    /// Sorted by prefix. The default namespace declaration comes first.
+    Prefix,
 }
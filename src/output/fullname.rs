@@ -200,6 +200,11 @@ impl<'a> FullnameSerializer<'a> {
         }
     }
 
+    // all namespaces in scope, each prefix once
+    pub(crate) fn namespaces(&self) -> &[(PrefixId, NamespaceId)] {
+        &self.top().all_namespaces
+    }
+
     pub(crate) fn is_namespace_known(&self, namespace_id: NamespaceId) -> bool {
         self.top()
             .all_namespaces
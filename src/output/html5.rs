@@ -8,9 +8,11 @@
 #[cfg(doc)]
 use crate::Xot;
 
+use std::collections::HashMap;
+
 use crate::NameId;
 
-use super::Indentation;
+use super::{AttributeOrder, Indentation, NamespaceOrder};
 
 /// Parameters for HTML generation.
 ///
@@ -49,5 +51,18 @@ pub struct Parameters {
     /// These should only be used for elements in non-XML content, like MathML
     /// or SVG.
     pub cdata_section_elements: Vec<NameId>,
-    // TODO: character maps
+    /// A character map, as with `use-character-maps` in the serialization
+    /// specification.
+    ///
+    /// Each character in this map is replaced by its string wherever it
+    /// occurs in text or in an attribute value, including the text of
+    /// elements such as `script` that isn't escaped. The string is output as
+    /// is, without escaping.
+    pub character_map: HashMap<char, String>,
+    /// The order in which attributes are serialized. By default this is the
+    /// order of the attributes in the tree.
+    pub attribute_order: AttributeOrder,
+    /// The order in which namespace declarations are serialized. By default
+    /// this is the order of the declarations in the tree.
+    pub namespace_order: NamespaceOrder,
 }
@@ -1,20 +1,26 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::io;
 
-use crate::entity::{serialize_attribute, serialize_cdata, serialize_text};
+use crate::entity::{serialize_attribute, serialize_cdata, serialize_mapped, serialize_text};
 use crate::error::Error;
 use crate::id::NameId;
 use crate::output::Normalizer;
 use crate::xotdata::{Node, Xot};
 
 use super::fullname::FullnameSerializer;
+use super::html5::Parameters;
 use super::html5elements::Html5Elements;
-use super::{Output, OutputToken, Pretty};
+use super::{
+    AttributeWrapper, Indentation, Output, OutputToken, Pretty, PrettyOutputToken, StartTagOrder,
+};
 
 pub(crate) struct Html5Serializer<'a, N: Normalizer> {
     xot: &'a Xot,
     html5_elements: &'a Html5Elements,
     cdata_section_names: &'a [NameId],
+    character_map: &'a HashMap<char, String>,
+    order: StartTagOrder<'a>,
     fullname_serializer: FullnameSerializer<'a>,
     normalizer: N,
 }
@@ -54,7 +60,7 @@ impl<'a, N: Normalizer> Html5Serializer<'a, N> {
         xot: &'a Xot,
         html5_elements: &'a Html5Elements,
         node: Node,
-        cdata_section_names: &'a [NameId],
+        parameters: &'a Parameters,
         normalizer: N,
     ) -> Self {
         let extra_declarations = xot.namespaces_in_scope(node).collect();
@@ -62,19 +68,39 @@ impl<'a, N: Normalizer> Html5Serializer<'a, N> {
         Self {
             xot,
             html5_elements,
-            cdata_section_names,
+            cdata_section_names: &parameters.cdata_section_elements,
+            character_map: &parameters.character_map,
+            order: StartTagOrder::new(
+                parameters.attribute_order.clone(),
+                parameters.namespace_order,
+            ),
             fullname_serializer,
             normalizer,
         }
     }
 
+    // The outputs that are ready to be serialized, with the namespace
+    // declarations and attributes of a start tag in order.
+    fn order(&mut self, node: Node, output: Output<'a>) -> Result<Vec<(Node, Output<'a>)>, Error> {
+        self.order
+            .push(node, output, self.xot, &self.fullname_serializer)
+    }
+
+    fn map_character(&self, c: char) -> Option<Cow<'a, str>> {
+        self.character_map
+            .get(&c)
+            .map(|replacement| Cow::Borrowed(replacement.as_str()))
+    }
+
     pub(crate) fn serialize<W: io::Write>(
         &mut self,
         w: &mut W,
         outputs: impl Iterator<Item = (Node, Output<'a>)>,
     ) -> Result<(), Error> {
         for (node, output) in outputs {
-            self.serialize_node(w, node, output)?;
+            for (node, output) in self.order(node, output)? {
+                self.serialize_node(w, node, output)?;
+            }
         }
         Ok(())
     }
@@ -83,8 +109,9 @@ impl<'a, N: Normalizer> Html5Serializer<'a, N> {
         &mut self,
         w: &mut W,
         outputs: impl Iterator<Item = (Node, Output<'a>)>,
-        suppress: &[NameId],
+        indentation: &Indentation,
     ) -> Result<(), Error> {
+        let suppress = &indentation.suppress;
         // we have to do the relatively slow html_matches_suppress call here,
         // as we cannot make an efficient HtmlNames at this point (as this
         // needs a mutable Xot)
@@ -96,14 +123,20 @@ impl<'a, N: Normalizer> Html5Serializer<'a, N> {
         };
         let is_inline = |name_id| self.html5_elements.is_inline(self.xot, name_id);
         let mut pretty = Pretty::new(self.xot, is_suppressed, is_inline);
+        let mut wrapper = AttributeWrapper::new(indentation);
         for (node, output) in outputs {
-            let (indentation, newline) = pretty.prettify(node, &output);
-            if indentation > 0 {
-                w.write_all(" ".repeat(indentation * 2).as_bytes())?;
-            }
-            self.serialize_node(w, node, output)?;
-            if newline {
-                w.write_all(b"\n")?;
+            for (node, output) in self.order(node, output)? {
+                let (indentation_level, newline) = pretty.prettify(node, &output);
+                let rendered = self.render_output(node, &output)?;
+                let token = PrettyOutputToken {
+                    text: rendered.text,
+                    space: rendered.space,
+                    indentation: indentation_level,
+                    newline,
+                };
+                for (_, _, token) in wrapper.push(node, output, token) {
+                    w.write_all(token.render(&indentation.indent).as_bytes())?;
+                }
             }
         }
         Ok(())
@@ -242,11 +275,17 @@ impl<'a, N: Normalizer> Html5Serializer<'a, N> {
                         });
                     }
                 }
-                let value = if namespace != self.xot.no_namespace() {
-                    serialize_attribute((*value).into(), &self.normalizer)
-                } else {
-                    serialize_attribute_html((*value).into(), &self.normalizer)
-                };
+                let value = serialize_mapped(
+                    (*value).into(),
+                    |c| self.map_character(c),
+                    |value| {
+                        if namespace != self.xot.no_namespace() {
+                            serialize_attribute(value, &self.normalizer)
+                        } else {
+                            serialize_attribute_html(value, &self.normalizer)
+                        }
+                    },
+                );
                 OutputToken {
                     space: true,
                     text: format!("{}=\"{}\"", fullname, value),
@@ -256,22 +295,35 @@ impl<'a, N: Normalizer> Html5Serializer<'a, N> {
                 // a text node is always a child of an element
                 let parent = self.xot.parent(node).unwrap();
                 let element = self.xot.element(parent).unwrap();
-                let value = if self
-                    .html5_elements
-                    .no_escape_names
-                    .matches(self.xot, element.name())
-                {
-                    serialize_text_no_escape((*text).into(), &self.normalizer).to_string()
-                } else if self.cdata_section_names.contains(&element.name()) {
-                    serialize_cdata((*text).into(), &self.normalizer).to_string()
-                } else if self
-                    .html5_elements
-                    .is_html_element(self.xot, element.name())
-                {
-                    serialize_text_html((*text).into(), &self.normalizer).to_string()
-                } else {
-                    serialize_text((*text).into(), &self.normalizer, false).to_string()
-                };
+                let value = serialize_mapped(
+                    (*text).into(),
+                    |c| self.map_character(c),
+                    |text| {
+                        if self
+                            .html5_elements
+                            .no_escape_names
+                            .matches(self.xot, element.name())
+                        {
+                            serialize_text_no_escape(text, &self.normalizer)
+                        } else if self.cdata_section_names.contains(&element.name())
+                        // CDATA sections are only allowed in foreign content
+                        || (self.xot.text(node).is_some_and(|text| text.is_cdata())
+                            && !self
+                                .html5_elements
+                                .is_html_element(self.xot, element.name()))
+                        {
+                            serialize_cdata(text, &self.normalizer)
+                        } else if self
+                            .html5_elements
+                            .is_html_element(self.xot, element.name())
+                        {
+                            serialize_text_html(text, &self.normalizer)
+                        } else {
+                            serialize_text(text, &self.normalizer, false)
+                        }
+                    },
+                )
+                .to_string();
                 OutputToken {
                     space: false,
                     text: value,
@@ -388,7 +440,7 @@ pub(crate) fn serialize_text_no_escape<'a, N: Normalizer>(
 
 #[cfg(test)]
 mod tests {
-    use crate::output::{html5::Parameters, html5elements::XHTML_NS, Indentation};
+    use crate::output::{html5elements::XHTML_NS, Indentation};
 
     use super::*;
 
@@ -820,6 +872,7 @@ mod tests {
                 Parameters {
                     indentation: Some(Indentation {
                         suppress: vec![body],
+                        ..Default::default()
                     }),
                     ..Default::default()
                 },
@@ -846,6 +899,7 @@ mod tests {
                 Parameters {
                     indentation: Some(Indentation {
                         suppress: vec![body],
+                        ..Default::default()
                     }),
                     ..Default::default()
                 },
@@ -877,6 +931,7 @@ mod tests {
                 Parameters {
                     indentation: Some(Indentation {
                         suppress: vec![foo],
+                        ..Default::default()
                     }),
                     ..Default::default()
                 },
@@ -936,6 +991,7 @@ mod tests {
                 Parameters {
                     indentation: Some(Indentation {
                         suppress: vec![body],
+                        ..Default::default()
                     }),
                     ..Default::default()
                 },
@@ -961,7 +1017,10 @@ mod tests {
             .html5()
             .serialize_string(
                 Parameters {
-                    indentation: Some(Indentation { suppress: vec![] }),
+                    indentation: Some(Indentation {
+                        suppress: vec![],
+                        ..Default::default()
+                    }),
                     ..Default::default()
                 },
                 root,
@@ -975,4 +1034,76 @@ mod tests {
 "#
         );
     }
+
+    #[test]
+    fn test_cdata_text_only_in_xml_island() {
+        let mut xot = Xot::new();
+        let root = xot
+            .parse_with_options(
+                "<html><body><p><![CDATA[a<b]]></p><island xmlns=\"island\"><![CDATA[a<b]]></island></body></html>",
+                crate::ParseOptions {
+                    cdata_boundaries: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let s = xot.html5().to_string(root).unwrap();
+        assert_eq!(
+            s,
+            "<!DOCTYPE html><html><body><p>a&lt;b</p><island xmlns=\"island\"><![CDATA[a<b]]></island></body></html>"
+        );
+    }
+
+    #[test]
+    fn test_character_map() {
+        let mut xot = Xot::new();
+        let root = xot
+            .parse("<html><body><p title=\"a\u{e9}b\">{x}&amp;\u{e9}</p><script>\u{e9}</script></body></html>")
+            .unwrap();
+        let s = xot
+            .html5()
+            .serialize_string(
+                Parameters {
+                    character_map: [('\u{e9}', "&eacute;".to_string()), ('{', "{{".to_string())]
+                        .into_iter()
+                        .collect(),
+                    ..Default::default()
+                },
+                root,
+            )
+            .unwrap();
+        assert_eq!(
+            s,
+            "<!DOCTYPE html><html><body><p title=\"a&eacute;b\">{{x}&amp;&eacute;</p><script>&eacute;</script></body></html>"
+        );
+    }
+
+    #[test]
+    fn test_pretty_indent_and_wrap_attributes() {
+        let mut xot = Xot::new();
+        let root = xot
+            .parse(
+                r#"<html><body><div class="container" id="main"><p>Hello</p></div></body></html>"#,
+            )
+            .unwrap();
+        let s = xot
+            .html5()
+            .serialize_string(
+                Parameters {
+                    indentation: Some(Indentation {
+                        indent: "\t".to_string(),
+                        max_width: Some(20),
+                        wrap_attributes: true,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                root,
+            )
+            .unwrap();
+        assert_eq!(
+            s,
+            "<!DOCTYPE html><html>\n\t<body>\n\t\t<div\n\t\t\tclass=\"container\"\n\t\t\tid=\"main\">\n\t\t\t<p>Hello</p>\n\t\t</div>\n\t</body>\n</html>\n"
+        );
+    }
 }
@@ -1,6 +1,8 @@
 //! Xot offers functionality to serialize XML data in different ways.
 //!
 //! This module lets you control serialization in various ways.
+pub mod c14n;
+mod c14n_serializer;
 mod common;
 mod fullname;
 pub mod html5;
@@ -9,19 +11,24 @@ mod html5elements;
 #[cfg(feature = "icu")]
 mod icu_normalization;
 mod normalizer;
+mod order;
 mod pretty;
 mod serializer;
 pub mod xml;
 mod xml_serializer;
 
-pub use common::{Indentation, TokenSerializeParameters};
+pub(crate) use c14n_serializer::C14nSerializer;
+pub use common::{
+    AttributeCompare, AttributeOrder, Indentation, NamespaceOrder, TokenSerializeParameters,
+};
 pub(crate) use fullname::FullnameSerializer;
 pub(crate) use fullname::NamespaceDeclarations;
 pub(crate) use html5_serializer::Html5Serializer;
 pub(crate) use html5elements::Html5Elements;
 pub use normalizer::{NoopNormalizer, Normalizer};
-pub(crate) use pretty::Pretty;
+pub(crate) use order::StartTagOrder;
 pub use pretty::PrettyOutputToken;
+pub(crate) use pretty::{AttributeWrapper, Pretty};
 pub(crate) use serializer::gen_outputs;
 pub use serializer::{Output, OutputToken};
 pub(crate) use xml_serializer::XmlSerializer;
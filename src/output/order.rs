@@ -0,0 +1,105 @@
+// Ordering of the namespace declarations and attributes of a start tag. They
+// are held back until the start tag is complete, and then sorted.
+use crate::error::Error;
+use crate::xotdata::{Node, Xot};
+
+use super::fullname::FullnameSerializer;
+use super::{AttributeOrder, NamespaceOrder, Output};
+
+pub(crate) struct StartTagOrder<'a> {
+    attribute_order: AttributeOrder,
+    namespace_order: NamespaceOrder,
+    held: Vec<(Node, Output<'a>)>,
+}
+
+impl<'a> StartTagOrder<'a> {
+    pub(crate) fn new(attribute_order: AttributeOrder, namespace_order: NamespaceOrder) -> Self {
+        Self {
+            attribute_order,
+            namespace_order,
+            held: Vec::new(),
+        }
+    }
+
+    // Add an output. This returns the outputs that are ready to be
+    // serialized. The start tag open is serialized right away, so that the
+    // full name serializer knows the namespaces of the element by the time
+    // we sort its attributes by qualified name.
+    pub(crate) fn push(
+        &mut self,
+        node: Node,
+        output: Output<'a>,
+        xot: &Xot,
+        fullname_serializer: &FullnameSerializer,
+    ) -> Result<Vec<(Node, Output<'a>)>, Error> {
+        if self.attribute_order == AttributeOrder::Insertion
+            && self.namespace_order == NamespaceOrder::Insertion
+        {
+            return Ok(vec![(node, output)]);
+        }
+        match output {
+            Output::Prefix(..) | Output::Attribute(..) => {
+                self.held.push((node, output));
+                Ok(Vec::new())
+            }
+            Output::StartTagClose => {
+                // namespace declarations come before the attributes
+                let (mut namespaces, attributes): (Vec<_>, Vec<_>) = std::mem::take(&mut self.held)
+                    .into_iter()
+                    .partition(|(_, output)| matches!(output, Output::Prefix(..)));
+                self.sort_namespaces(&mut namespaces, xot);
+                let mut outputs = namespaces;
+                outputs.extend(self.sort_attributes(attributes, xot, fullname_serializer)?);
+                outputs.push((node, output));
+                Ok(outputs)
+            }
+            _ => Ok(vec![(node, output)]),
+        }
+    }
+
+    fn sort_namespaces(&self, namespaces: &mut [(Node, Output<'a>)], xot: &Xot) {
+        if self.namespace_order == NamespaceOrder::Prefix {
+            namespaces.sort_by_key(|(_, output)| match output {
+                Output::Prefix(prefix_id, _) => xot.prefix_str(*prefix_id),
+                _ => unreachable!(),
+            });
+        }
+    }
+
+    // Sorting is stable, so attributes that compare equal keep their order.
+    fn sort_attributes(
+        &self,
+        mut attributes: Vec<(Node, Output<'a>)>,
+        xot: &Xot,
+        fullname_serializer: &FullnameSerializer,
+    ) -> Result<Vec<(Node, Output<'a>)>, Error> {
+        let name = |output: &Output| match output {
+            Output::Attribute(name_id, _) => *name_id,
+            _ => unreachable!(),
+        };
+        match &self.attribute_order {
+            AttributeOrder::Insertion => {}
+            AttributeOrder::ExpandedName => {
+                attributes.sort_by_key(|(_, output)| {
+                    let (local_name, namespace) = xot.name_ns_str(name(output));
+                    (namespace, local_name)
+                });
+            }
+            AttributeOrder::QualifiedName => {
+                let mut keyed = attributes
+                    .into_iter()
+                    .map(|(node, output)| {
+                        let fullname = fullname_serializer.attribute_fullname(name(&output))?;
+                        Ok((fullname, (node, output)))
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+                return Ok(keyed.into_iter().map(|(_, item)| item).collect());
+            }
+            AttributeOrder::Custom(compare) => {
+                attributes.sort_by(|(_, a), (_, b)| compare(xot, name(a), name(b)));
+            }
+        }
+        Ok(attributes)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::output::Output;
+use crate::output::{Indentation, Output};
 use crate::xotdata::{Node, Xot};
 use crate::{NameId, Value};
 
@@ -19,6 +19,21 @@ pub struct PrettyOutputToken {
     pub newline: bool,
 }
 
+impl PrettyOutputToken {
+    // The text of the token with its indentation, space and newline.
+    pub(crate) fn render(&self, indent: &str) -> String {
+        let mut s = indent.repeat(self.indentation);
+        if self.space {
+            s.push(' ');
+        }
+        s.push_str(&self.text);
+        if self.newline {
+            s.push('\n');
+        }
+        s
+    }
+}
+
 // we need to track where we are in xml:space, so that we can know when to
 // insert newlines and indentation
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -195,6 +210,67 @@ where
     }
 }
 
+// Holds back the tokens of a start tag until it's complete, so that if it's
+// wider than the maximum width, its attributes and namespace declarations can
+// each be put on a line of their own, indented one level deeper than the
+// element.
+pub(crate) struct AttributeWrapper<'a> {
+    indent_width: usize,
+    max_width: Option<usize>,
+    start_tag: Vec<(Node, Output<'a>, PrettyOutputToken)>,
+}
+
+impl<'a> AttributeWrapper<'a> {
+    pub(crate) fn new(indentation: &Indentation) -> Self {
+        AttributeWrapper {
+            indent_width: indentation.indent.chars().count(),
+            max_width: if indentation.wrap_attributes {
+                indentation.max_width
+            } else {
+                None
+            },
+            start_tag: Vec::new(),
+        }
+    }
+
+    // Add a token. This returns the tokens that are ready to be written.
+    pub(crate) fn push(
+        &mut self,
+        node: Node,
+        output: Output<'a>,
+        token: PrettyOutputToken,
+    ) -> Vec<(Node, Output<'a>, PrettyOutputToken)> {
+        let Some(max_width) = self.max_width else {
+            return vec![(node, output, token)];
+        };
+        let is_start_tag_close = matches!(output, Output::StartTagClose);
+        if self.start_tag.is_empty() && !matches!(output, Output::StartTagOpen(_)) {
+            return vec![(node, output, token)];
+        }
+        self.start_tag.push((node, output, token));
+        if !is_start_tag_close {
+            return Vec::new();
+        }
+        let mut start_tag = std::mem::take(&mut self.start_tag);
+        let level = start_tag[0].2.indentation;
+        let width = level * self.indent_width
+            + start_tag
+                .iter()
+                .map(|(_, _, token)| token.space as usize + token.text.chars().count())
+                .sum::<usize>();
+        if width > max_width {
+            // everything between the start tag open and close
+            for i in 1..start_tag.len() - 1 {
+                start_tag[i - 1].2.newline = true;
+                let token = &mut start_tag[i].2;
+                token.space = false;
+                token.indentation = level + 1;
+            }
+        }
+        start_tag
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -234,7 +310,10 @@ mod tests {
         let output_xml = xot
             .serialize_xml_string(
                 output::xml::Parameters {
-                    indentation: Some(output::Indentation { suppress }),
+                    indentation: Some(output::Indentation {
+                        suppress,
+                        ..Default::default()
+                    }),
                     ..Default::default()
                 },
                 document,
@@ -17,26 +17,26 @@
 // * `normalization-form` is not directly supported, but if you enable the `icu`
 //   feature you can use [`Xot::serialize_xml_string_with_normalizer`] to control
 //   normalization with an ICU normalizer.
-// * There is no way to declare the `version` parameter, as only XML 1.0 is
-//   permitted at this time.
-// * You can only influence encoding parameter of the XML declaration, and
-//   this does not trigger actual encoding; output is always UTF-8 and it's up
-//   to you to do any further re-encoding.
+// * The `version` parameter is part of [`Declaration`], so that without a
+//   declaration the output is always an XML 1.0 document.
+// * The `encoding` parameter is part of [`Declaration`], so that the
+//   encoding of the output is always declared when it's not UTF-8.
 // * The `item-separator` parameter is specific to XPath/XSLT sequences and is
 //   not supported directly by Xot.
 // * The `media-type` property is only meaningful in the context of a larger
 //   system and is not supported directly by Xot.
-// * `undeclare-prefixes` is only supported by XML 1.1, which Xot does not
-//   support at present.
+// * `undeclare-prefixes` is only meaningful in XML 1.1, and Xot does not
+//   represent prefix undeclarations in its tree.
 
 #[cfg(doc)]
 use crate::Xot;
 
+use std::collections::HashMap;
 use std::io::Write;
 
 use crate::NameId;
 
-use super::Indentation;
+use super::{AttributeOrder, Indentation, NamespaceOrder};
 
 /// Parameters for XML generation.
 ///
@@ -48,17 +48,45 @@ pub struct Parameters {
     pub indentation: Option<Indentation>,
     /// Elements that should have their text content be serialized as CDATA
     /// sections.
+    ///
+    /// Text that is marked as CDATA is always serialized as a CDATA section,
+    /// see [`Text::is_cdata`](crate::Text::is_cdata).
     pub cdata_section_elements: Vec<NameId>,
     /// The XML declaration, if any.
+    ///
+    /// If this is not set and a document node is serialized, the XML
+    /// declaration the document was parsed with is used, if it had one. See
+    /// [`Xot::document_metadata`].
     pub declaration: Option<Declaration>,
     /// The doctype declaration, if any.
+    ///
+    /// If this is not set and a document node is serialized, the document
+    /// type declaration of that document is used, if it has one. See
+    /// [`Xot::document_type`].
     pub doctype: Option<DocType>,
     /// Whether to escape the `>` character in text content. By default this is
     /// true, which means that `>` is escaped as `&gt;`. If you set this to true,
     /// `>` is not escaped, except for the special case of `]]>` outside of CDATA,
     /// which is mandated by the XML specification to always be escaped.
     pub unescaped_gt: bool,
-    // TODO: character maps
+    /// A character map, as with `use-character-maps` in the serialization
+    /// specification.
+    ///
+    /// Each character in this map is replaced by its string wherever it
+    /// occurs in text or in an attribute value. The string is output as is,
+    /// without escaping, so you can use this to generate entity references
+    /// such as `&nbsp;`, or markup that isn't otherwise allowed in a text
+    /// node. If the character occurs in a CDATA section, the section is closed
+    /// before the string and reopened after it.
+    ///
+    /// It's up to you to make sure the result is well-formed.
+    pub character_map: HashMap<char, String>,
+    /// The order in which attributes are serialized. By default this is the
+    /// order of the attributes in the tree.
+    pub attribute_order: AttributeOrder,
+    /// The order in which namespace declarations are serialized. By default
+    /// this is the order of the declarations in the tree.
+    pub namespace_order: NamespaceOrder,
 }
 
 /// How to format the XML declaration.
@@ -76,22 +104,64 @@ pub struct Parameters {
 /// ```xml
 /// <?xml version="1.0" encoding="UTF-8"?>
 /// ```
+///
+/// ```xml
+/// <?xml version="1.1"?>
+/// ```
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Declaration {
+    /// The XML version to declare. This is XML 1.0 by default.
+    pub version: Version,
     /// This causes an encoding declaration to be included in the XML declaration.
-    /// The text given here is taken literally. It does not affect the encoding of
-    /// the output of serialization; that is always UTF-8.
+    /// The text given here is taken literally.
+    ///
+    /// The output is written in this encoding, such as `ISO-8859-1` or
+    /// `Shift_JIS`. A character in text or an attribute value that can't be
+    /// represented in the encoding is written as a numeric character
+    /// reference; if it appears elsewhere, such as in a name, a comment or a
+    /// processing instruction, serialization fails with
+    /// [`Error::UnencodableCharacter`](crate::Error::UnencodableCharacter).
+    /// An encoding that isn't known, or that isn't ASCII compatible such as
+    /// UTF-16, results in
+    /// [`Error::UnsupportedEncoding`](crate::Error::UnsupportedEncoding).
+    ///
+    /// When you serialize to a string, the string contains what you get when
+    /// you decode the encoded output, so characters that can't be encoded are
+    /// character references there as well.
     pub encoding: Option<String>,
     /// This causes a standalone declaration to be included in the XML declaration.
     pub standalone: Option<bool>,
-    // TODO: if in the future we add XML 1.1 support, we can add the version here.
-    // This way without a declaration it is automatically an XML 1.0 document. Potentially
-    // also include `undeclare-prefixes` here, as that's only supported in XML 1.1.
+}
+
+/// The XML version declared in the XML declaration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Version {
+    /// XML 1.0
+    #[default]
+    Xml10,
+    /// XML 1.1
+    ///
+    /// The control characters that XML 1.1 only allows as character
+    /// references are serialized as such in text and attribute values, and
+    /// so are NEL and LINE SEPARATOR, which would otherwise be read as line
+    /// ends.
+    Xml11,
+}
+
+impl Version {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Version::Xml10 => "1.0",
+            Version::Xml11 => "1.1",
+        }
+    }
 }
 
 impl Declaration {
     pub(crate) fn serialize(&self, w: &mut impl Write) -> Result<(), std::io::Error> {
-        w.write_all(b"<?xml version=\"1.0\"")?;
+        w.write_all(b"<?xml version=\"")?;
+        w.write_all(self.version.as_str().as_bytes())?;
+        w.write_all(b"\"")?;
         if let Some(encoding) = &self.encoding {
             w.write_all(b" encoding=\"")?;
             w.write_all(encoding.as_bytes())?;
@@ -138,25 +208,38 @@ impl DocType {
     pub(crate) fn serialize(&self, name: &str, w: &mut impl Write) -> Result<(), std::io::Error> {
         w.write_all(b"<!DOCTYPE ")?;
         w.write_all(name.as_bytes())?;
+        self.serialize_external_id(w)?;
+        w.write_all(b">\n")?;
+        Ok(())
+    }
+
+    pub(crate) fn serialize_external_id(&self, w: &mut impl Write) -> Result<(), std::io::Error> {
         match self {
             DocType::Public { public, system } => {
-                w.write_all(b" PUBLIC \"")?;
-                w.write_all(public.as_bytes())?;
-                w.write_all(b"\" \"")?;
-                w.write_all(system.as_bytes())?;
-                w.write_all(b"\"")?;
+                w.write_all(b" PUBLIC ")?;
+                serialize_literal(public, w)?;
+                w.write_all(b" ")?;
+                serialize_literal(system, w)?;
             }
             DocType::System { system } => {
-                w.write_all(b" SYSTEM \"")?;
-                w.write_all(system.as_bytes())?;
-                w.write_all(b"\"")?;
+                w.write_all(b" SYSTEM ")?;
+                serialize_literal(system, w)?;
             }
         }
-        w.write_all(b">\n")?;
         Ok(())
     }
 }
 
+// a literal cannot contain the quote character it's delimited by, and there
+// is no escaping, so we use single quotes if we have to
+fn serialize_literal(literal: &str, w: &mut impl Write) -> Result<(), std::io::Error> {
+    let quote: &[u8] = if literal.contains('"') { b"'" } else { b"\"" };
+    w.write_all(quote)?;
+    w.write_all(literal.as_bytes())?;
+    w.write_all(quote)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{output::Indentation, Xot};
@@ -200,7 +283,10 @@ mod tests {
         let mut xot = Xot::new();
 
         let m = Parameters {
-            indentation: Some(Indentation { suppress: vec![] }),
+            indentation: Some(Indentation {
+                suppress: vec![],
+                ..Default::default()
+            }),
             ..Default::default()
         };
         let doc = xot.parse("<doc><p><k>foo</k></p></doc>").unwrap();
@@ -216,12 +302,84 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_xml_output_indent_string() {
+        let mut xot = Xot::new();
+        let m = Parameters {
+            indentation: Some(Indentation {
+                indent: "\t".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let doc = xot.parse("<doc><p><k>foo</k></p></doc>").unwrap();
+
+        assert_eq!(
+            xot.serialize_xml_string(m, doc).unwrap(),
+            "<doc>\n\t<p>\n\t\t<k>foo</k>\n\t</p>\n</doc>\n"
+        );
+    }
+
+    #[test]
+    fn test_xml_output_wrap_attributes() {
+        let mut xot = Xot::new();
+        let m = Parameters {
+            indentation: Some(Indentation {
+                indent: "    ".to_string(),
+                max_width: Some(30),
+                wrap_attributes: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let doc = xot
+            .parse(r#"<doc xmlns="http://example.com/ns" a="1"><p a="1" b="2"/><p first="1" second="2" third="3"/></doc>"#)
+            .unwrap();
+
+        assert_eq!(
+            xot.serialize_xml_string(m, doc).unwrap(),
+            r#"<doc
+    xmlns="http://example.com/ns"
+    a="1">
+    <p a="1" b="2"/>
+    <p
+        first="1"
+        second="2"
+        third="3"/>
+</doc>
+"#
+        );
+    }
+
+    #[test]
+    fn test_xml_output_max_width_without_wrap_attributes() {
+        let mut xot = Xot::new();
+        let m = Parameters {
+            indentation: Some(Indentation {
+                max_width: Some(10),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let doc = xot
+            .parse(r#"<doc><p first="1" second="2"/></doc>"#)
+            .unwrap();
+
+        assert_eq!(
+            xot.serialize_xml_string(m, doc).unwrap(),
+            "<doc>\n  <p first=\"1\" second=\"2\"/>\n</doc>\n"
+        );
+    }
+
     #[test]
     fn test_xml_output_indent_suppress() {
         let mut xot = Xot::new();
         let p = xot.add_name("p");
         let m = Parameters {
-            indentation: Some(Indentation { suppress: vec![p] }),
+            indentation: Some(Indentation {
+                suppress: vec![p],
+                ..Default::default()
+            }),
             ..Default::default()
         };
         let doc = xot.parse("<doc><p><k>foo</k></p></doc>").unwrap();
@@ -270,6 +428,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_xml_output_declaration_version() {
+        let m = Parameters {
+            declaration: Some(Declaration {
+                version: Version::Xml11,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut xot = Xot::new();
+        let doc = xot.parse("<doc/>").unwrap();
+
+        assert_eq!(
+            xot.serialize_xml_string(m, doc).unwrap(),
+            r#"<?xml version="1.1"?>
+<doc/>"#
+        );
+    }
+
     #[test]
     fn test_xml_output_doctype_public() {
         let m = Parameters {
@@ -372,4 +549,43 @@ mod tests {
             r#"<doc><p><![CDATA[hello]]]]><![CDATA[> world]]></p></doc>"#
         );
     }
+
+    #[test]
+    fn test_character_map() {
+        let mut xot = Xot::new();
+        let m = Parameters {
+            character_map: [
+                ('\u{a0}', "&nbsp;".to_string()),
+                ('$', "<?php echo $x; ?>".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let doc = xot
+            .parse("<doc a=\"1\u{a0}&amp;\u{a0}2\">$ &lt; \u{a0}</doc>")
+            .unwrap();
+
+        assert_eq!(
+            xot.serialize_xml_string(m, doc).unwrap(),
+            r#"<doc a="1&nbsp;&amp;&nbsp;2"><?php echo $x; ?> &lt; &nbsp;</doc>"#
+        );
+    }
+
+    #[test]
+    fn test_character_map_cdata_sections_elements() {
+        let mut xot = Xot::new();
+        let p = xot.add_name("p");
+        let m = Parameters {
+            cdata_section_elements: vec![p],
+            character_map: [('\u{a0}', "&nbsp;".to_string())].into_iter().collect(),
+            ..Default::default()
+        };
+        let doc = xot.parse("<doc><p>a&lt;\u{a0}b</p></doc>").unwrap();
+
+        assert_eq!(
+            xot.serialize_xml_string(m, doc).unwrap(),
+            r#"<doc><p><![CDATA[a<]]>&nbsp;<![CDATA[b]]></p></doc>"#
+        );
+    }
 }
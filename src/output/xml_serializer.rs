@@ -1,19 +1,29 @@
+use std::borrow::Cow;
 use std::io;
 
-use crate::entity::{serialize_attribute, serialize_cdata, serialize_text};
+use crate::encoding::OutputEncoding;
+use crate::entity::{serialize_attribute, serialize_cdata, serialize_mapped, serialize_text};
 use crate::error::Error;
 use crate::id::NameId;
+use crate::output::xml::Version;
 use crate::output::Normalizer;
+use crate::validate::is_restricted_char;
 use crate::xotdata::{Node, Xot};
 
 use super::fullname::FullnameSerializer;
-use super::{Output, OutputToken, Pretty, TokenSerializeParameters};
+use super::{
+    AttributeWrapper, Indentation, Output, OutputToken, Pretty, PrettyOutputToken, StartTagOrder,
+    TokenSerializeParameters,
+};
 
 pub(crate) struct XmlSerializer<'a, N: Normalizer> {
     xot: &'a Xot,
     fullname_serializer: FullnameSerializer<'a>,
     normalizer: N,
     parameters: TokenSerializeParameters,
+    version: Version,
+    encoding: Option<OutputEncoding>,
+    order: StartTagOrder<'a>,
 }
 
 impl<'a, N: Normalizer> XmlSerializer<'a, N> {
@@ -21,15 +31,56 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
         xot: &'a Xot,
         node: Node,
         parameters: TokenSerializeParameters,
+        version: Version,
+        encoding: Option<OutputEncoding>,
         normalizer: N,
     ) -> Self {
         let extra_declarations = xot.namespaces_in_scope(node).collect();
         let fullname_serializer = FullnameSerializer::new(xot, extra_declarations);
+        let order = StartTagOrder::new(
+            parameters.attribute_order.clone(),
+            parameters.namespace_order,
+        );
         Self {
             xot,
             fullname_serializer,
             normalizer,
             parameters,
+            version,
+            encoding,
+            order,
+        }
+    }
+
+    // The outputs that are ready to be serialized, with the namespace
+    // declarations and attributes of a start tag in order.
+    pub(crate) fn order(
+        &mut self,
+        node: Node,
+        output: Output<'a>,
+    ) -> Result<Vec<(Node, Output<'a>)>, Error> {
+        self.order
+            .push(node, output, self.xot, &self.fullname_serializer)
+    }
+
+    // A character in text or an attribute value is replaced if it's in the
+    // character map, or by a character reference if it can't be encoded. In
+    // XML 1.1, the restricted characters are only allowed as character
+    // references, and NEL and LINE SEPARATOR would be read as line ends.
+    fn map_character(&self, c: char) -> Option<Cow<'_, str>> {
+        if let Some(replacement) = self.parameters.character_map.get(&c) {
+            return Some(Cow::Borrowed(replacement.as_str()));
+        }
+        if self.version == Version::Xml11
+            && (is_restricted_char(c) || c == '\u{85}' || c == '\u{2028}')
+        {
+            return Some(Cow::Owned(format!("&#x{:X};", c as u32)));
+        }
+        match self.encoding {
+            Some(encoding) if !encoding.can_encode(c) => {
+                Some(Cow::Owned(format!("&#x{:X};", c as u32)))
+            }
+            _ => None,
         }
     }
 
@@ -39,7 +90,9 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
         outputs: impl Iterator<Item = (Node, Output<'a>)>,
     ) -> Result<(), Error> {
         for (node, output) in outputs {
-            self.serialize_node(w, node, output)?;
+            for (node, output) in self.order(node, output)? {
+                self.serialize_node(w, node, output)?;
+            }
         }
         Ok(())
     }
@@ -48,23 +101,51 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
         &mut self,
         w: &mut W,
         outputs: impl Iterator<Item = (Node, Output<'a>)>,
-        suppress: &[NameId],
+        indentation: &Indentation,
     ) -> Result<(), Error> {
-        let is_suppressed = |name_id| suppress.contains(&name_id);
+        let is_suppressed = |name_id| indentation.suppress.contains(&name_id);
         let mut pretty = Pretty::new(self.xot, is_suppressed, |_| false);
+        let mut wrapper = AttributeWrapper::new(indentation);
         for (node, output) in outputs {
-            let (indentation, newline) = pretty.prettify(node, &output);
-            if indentation > 0 {
-                w.write_all(" ".repeat(indentation * 2).as_bytes())?;
-            }
-            self.serialize_node(w, node, output)?;
-            if newline {
-                w.write_all(b"\n")?;
+            for (node, output) in self.order(node, output)? {
+                let token = self.render_pretty_output(&mut pretty, node, &output)?;
+                for (_, _, token) in wrapper.push(node, output, token) {
+                    self.write(w, &token.render(&indentation.indent))?;
+                }
             }
         }
         Ok(())
     }
 
+    pub(crate) fn render_pretty_output<IsSuppressed, IsInline>(
+        &mut self,
+        pretty: &mut Pretty<IsSuppressed, IsInline>,
+        node: Node,
+        output: &Output<'a>,
+    ) -> Result<PrettyOutputToken, Error>
+    where
+        IsSuppressed: Fn(NameId) -> bool,
+        IsInline: Fn(NameId) -> bool,
+    {
+        let (indentation, newline) = pretty.prettify(node, output);
+        let rendered = self.render_output(node, output)?;
+        Ok(PrettyOutputToken {
+            text: rendered.text,
+            space: rendered.space,
+            indentation,
+            newline,
+        })
+    }
+
+    fn write<W: io::Write>(&self, w: &mut W, s: &str) -> Result<(), Error> {
+        if let Some(encoding) = self.encoding {
+            encoding.write(w, s)
+        } else {
+            w.write_all(s.as_bytes())?;
+            Ok(())
+        }
+    }
+
     pub(crate) fn serialize_node<W: io::Write>(
         &mut self,
         w: &mut W,
@@ -75,8 +156,7 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
         if data.space {
             w.write_all(b" ").unwrap();
         }
-        w.write_all(data.text.as_bytes()).unwrap();
-        Ok(())
+        self.write(w, &data.text)
     }
 
     pub(crate) fn render_output(
@@ -158,7 +238,11 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
                     text: format!(
                         "{}=\"{}\"",
                         fullname,
-                        serialize_attribute((*value).into(), &self.normalizer)
+                        serialize_mapped(
+                            (*value).into(),
+                            |c| self.map_character(c),
+                            |value| { serialize_attribute(value, &self.normalizer) }
+                        )
                     ),
                 }
             }
@@ -166,22 +250,31 @@ impl<'a, N: Normalizer> XmlSerializer<'a, N> {
                 // a text node is always a child of an element
                 let parent = self.xot.parent(node).unwrap();
                 let element = self.xot.element(parent).unwrap();
-                if self
-                    .parameters
-                    .cdata_section_elements
-                    .contains(&element.name())
+                let is_cdata = self.xot.text(node).is_some_and(|text| text.is_cdata());
+                if is_cdata
+                    || self
+                        .parameters
+                        .cdata_section_elements
+                        .contains(&element.name())
                 {
                     OutputToken {
                         space: false,
-                        text: serialize_cdata((*text).into(), &self.normalizer).to_string(),
+                        text: serialize_mapped(
+                            (*text).into(),
+                            |c| self.map_character(c),
+                            |text| serialize_cdata(text, &self.normalizer),
+                        )
+                        .to_string(),
                     }
                 } else {
                     OutputToken {
                         space: false,
-                        text: serialize_text(
+                        text: serialize_mapped(
                             (*text).into(),
-                            &self.normalizer,
-                            self.parameters.unescaped_gt,
+                            |c| self.map_character(c),
+                            |text| {
+                                serialize_text(text, &self.normalizer, self.parameters.unescaped_gt)
+                            },
                         )
                         .to_string(),
                     }
@@ -1,104 +1,572 @@
+use std::io::Read;
+
 use ahash::{HashMap, HashMapExt};
 use indextree::NodeId;
-use xmlparser::{ElementEnd, StrSpan, Token, Tokenizer};
+use xmlparser::{EntityDefinition, ExternalId, StrSpan, TextPos, Token, Tokenizer};
 
-use crate::encoding::decode;
-use crate::entity::{parse_attribute, parse_text};
+use crate::document::{DocumentMetadata, DocumentType};
+use crate::encoding::{decode, decode_strict};
+use crate::entity::{
+    parse_attribute, parse_attribute_recovering, parse_text, parse_text_recovering, Entities,
+};
 use crate::error::Error;
 use crate::id::{Name, NameId, PrefixId};
+use crate::incremental::{IncrementalParser, PushParser};
+use crate::output::xml::DocType;
+use crate::reader::Reader;
+use crate::treebuilder::TreeBuilder;
+use crate::unpretty::is_whitespace;
+use crate::validate::is_restricted_char;
 use crate::xmlvalue::{Attribute, Comment, Element, Namespace, ProcessingInstruction, Text, Value};
 use crate::xotdata::{Node, Xot};
 use crate::NamespaceId;
 
-type Namespaces = Vec<(PrefixId, NamespaceId)>;
+pub(crate) type Namespaces = Vec<(PrefixId, NamespaceId)>;
+
+// https://www.w3.org/TR/xml-names/#xmlReserved
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+const XMLNS_NAMESPACE: &str = "http://www.w3.org/2000/xmlns/";
 
-struct AttributeBuilder {
-    prefix: String,
-    name: String,
-    value: String,
-    name_span: Span,
-    value_span: Span,
+// the amount of bytes we read at a time in `Xot::parse_reader`
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+pub(crate) struct AttributeBuilder {
+    pub(crate) prefix: String,
+    pub(crate) name: String,
+    pub(crate) value: String,
+    pub(crate) name_span: Span,
+    pub(crate) value_span: Span,
 }
 
-struct ElementBuilder {
-    prefix: String,
-    name: String,
-    namespaces: Namespaces,
-    attributes: Vec<AttributeBuilder>,
-    span: Span,
+pub(crate) struct ElementBuilder {
+    pub(crate) prefix: String,
+    pub(crate) name: String,
+    pub(crate) namespaces: Namespaces,
+    pub(crate) attributes: Vec<AttributeBuilder>,
+    pub(crate) span: Span,
 }
 
 impl ElementBuilder {
-    fn new(prefix: StrSpan<'_>, name: StrSpan<'_>) -> Self {
+    pub(crate) fn new(prefix: StrSpan<'_>, name: StrSpan<'_>, offset: usize) -> Self {
         ElementBuilder {
             prefix: prefix.to_string(),
             name: name.to_string(),
             namespaces: Namespaces::new(),
             attributes: Vec::new(),
-            span: Span::from_prefix_name(prefix, name),
+            span: Span::from_prefix_name(prefix, name, offset),
+        }
+    }
+
+    // Declare a prefix on the element. The name span is that of the
+    // declaring attribute, the value span that of the namespace URI.
+    pub(crate) fn declare_prefix(
+        &mut self,
+        prefix: &str,
+        namespace_uri: &str,
+        name_span: Option<Span>,
+        value_span: Option<Span>,
+        xml11: bool,
+        xot: &mut Xot,
+    ) -> Result<(), Error> {
+        check_prefix_declaration(prefix, namespace_uri, name_span, value_span, xml11)?;
+        let prefix_id = xot.prefix_lookup.get_id_mut(prefix);
+        let namespace_id = xot.namespace_lookup.get_id_mut(namespace_uri);
+        self.namespaces.push((prefix_id, namespace_id));
+        Ok(())
+    }
+
+    // An attribute may only occur once in a start tag, by its name as
+    // written.
+    pub(crate) fn check_duplicate_attribute(
+        &self,
+        prefix: &str,
+        name: &str,
+        name_span: Span,
+    ) -> Result<(), Error> {
+        let is_duplicate = self
+            .attributes
+            .iter()
+            .any(|attribute| attribute.prefix == prefix && attribute.name == name);
+        if is_duplicate {
+            return Err(Error::DuplicateAttribute(
+                qualified_name(prefix, name),
+                Some(name_span),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl AttributeBuilder {
+    // Attributes with different prefixes may still have the same expanded
+    // name, which isn't allowed either. The previous names are those of the
+    // attributes before this one.
+    // https://www.w3.org/TR/xml-names/#uniqAttrs
+    pub(crate) fn check_expanded_name(
+        &self,
+        name_id: NameId,
+        mut previous: impl Iterator<Item = NameId>,
+    ) -> Result<(), Error> {
+        if previous.any(|previous| previous == name_id) {
+            return Err(Error::DuplicateExpandedAttribute(
+                qualified_name(&self.prefix, &self.name),
+                Some(self.name_span),
+            ));
+        }
+        Ok(())
+    }
+}
+
+// The prefix a namespace declaration declares, or `None` if the attribute
+// isn't one. The default namespace is declared with the empty prefix.
+pub(crate) fn declared_prefix<'a>(prefix: &'a str, name: &'a str) -> Option<&'a str> {
+    if prefix == "xmlns" {
+        Some(name)
+    } else if name == "xmlns" {
+        Some("")
+    } else {
+        None
+    }
+}
+
+pub(crate) fn qualified_name(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}:{}", prefix, name)
+    }
+}
+
+/// Options that control how XML is parsed.
+///
+/// You pass these to [`Xot::parse_with_options`]. The default options parse
+/// the same way as [`Xot::parse`].
+///
+/// Whatever the options, the entities declared in the DTD of a document may
+/// expand to at most 10 MB of replacement text in total, so that a document
+/// with entities that expand exponentially, such as "billion laughs", fails
+/// with [`ParseLimit::EntityExpansion`] instead of using up memory.
+///
+/// ```rust
+/// use xot::{Xot, ParseOptions};
+///
+/// let mut xot = Xot::new();
+/// let root = xot.parse_with_options(
+///     "<doc>\n  <p>Hello</p><!--comment-->\n</doc>",
+///     ParseOptions {
+///         strip_whitespace: true,
+///         strip_comments: true,
+///         ..Default::default()
+///     },
+/// )?;
+/// assert_eq!(xot.to_string(root)?, "<doc><p>Hello</p></doc>");
+/// # Ok::<(), xot::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Strip insignificant whitespace, in the same way as
+    /// [`Xot::remove_insignificant_whitespace`]: whitespace-only text is
+    /// removed unless its element also contains text that isn't whitespace,
+    /// or unless it's in the scope of `xml:space="preserve"`.
+    pub strip_whitespace: bool,
+    /// Don't create comment nodes.
+    pub strip_comments: bool,
+    /// Don't create processing instruction nodes.
+    pub strip_processing_instructions: bool,
+    /// Each CDATA section becomes a text node of its own, and isn't
+    /// consolidated with adjacent text. These text nodes are marked as CDATA,
+    /// see [`Text::is_cdata`](crate::Text::is_cdata), so that they're
+    /// serialized as CDATA sections again.
+    pub cdata_boundaries: bool,
+    /// Consolidate adjacent text, so that two text nodes never appear
+    /// consecutively. This is on by default. Text is adjacent if it's only
+    /// separated by CDATA markers, or by comments and processing
+    /// instructions that are stripped.
+    pub text_consolidation: bool,
+    /// Limits on the resources the parsed document may use. By default there
+    /// are no limits.
+    pub limits: ParseLimits,
+    /// Resolve the HTML 5 named character references, such as `&nbsp;` and
+    /// `&eacute;`, in text and attribute values, without a DTD that declares
+    /// them.
+    pub html5_entities: bool,
+    /// Named entities to resolve in text and attribute values, by name
+    /// (without `&` and `;`). The replacement text is taken literally. If it
+    /// contains a character that's not allowed in XML, parsing fails with
+    /// [`Error::InvalidCharacter`].
+    ///
+    /// Entities declared in the document's DTD take precedence over these,
+    /// and these take precedence over the HTML 5 named character references.
+    pub entities: std::collections::HashMap<String, String>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            strip_whitespace: false,
+            strip_comments: false,
+            strip_processing_instructions: false,
+            cdata_boundaries: false,
+            text_consolidation: true,
+            limits: ParseLimits::default(),
+            html5_entities: false,
+            entities: std::collections::HashMap::new(),
         }
     }
 }
 
-struct DocumentBuilder {
+/// Limits on the resources a parsed document may use.
+///
+/// When you parse XML from an untrusted source, you can use these to guard
+/// against documents that are built to use up memory or the id space of
+/// names. If a document exceeds a limit, parsing is aborted with
+/// [`Error::LimitExceeded`](crate::Error::LimitExceeded), which tells you
+/// which limit was exceeded and where.
+///
+/// Each limit is `None` by default, which means there is no limit.
+///
+/// ```rust
+/// use xot::{Error, ParseLimit, ParseLimits, ParseOptions, Xot};
+///
+/// let mut xot = Xot::new();
+/// let options = ParseOptions {
+///     limits: ParseLimits {
+///         max_depth: Some(2),
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+/// let err = xot
+///     .parse_with_options("<a><b><c/></b></a>", options)
+///     .unwrap_err();
+/// assert!(matches!(err, Error::LimitExceeded(ParseLimit::Depth, _)));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// The maximum depth to which elements may be nested. The document
+    /// element is at depth 1.
+    pub max_depth: Option<usize>,
+    /// The maximum number of attributes on an element. Namespace
+    /// declarations count as attributes.
+    pub max_attributes: Option<usize>,
+    /// The maximum length in bytes of a text node or an attribute value,
+    /// with entities expanded. Parsing stops as soon as an expansion goes
+    /// over it.
+    pub max_text_length: Option<usize>,
+    /// The maximum number of names the document may add to the [`Xot`].
+    pub max_names: Option<usize>,
+    /// The maximum number of namespaces the document may add to the
+    /// [`Xot`].
+    pub max_namespaces: Option<usize>,
+    /// The maximum number of prefixes the document may add to the [`Xot`].
+    pub max_prefixes: Option<usize>,
+    /// The maximum number of nodes in the document, not counting the
+    /// document node. Attribute and namespace nodes count.
+    pub max_nodes: Option<usize>,
+}
+
+/// A limit in [`ParseLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseLimit {
+    /// [`ParseLimits::max_depth`]
+    Depth,
+    /// [`ParseLimits::max_attributes`]
+    Attributes,
+    /// [`ParseLimits::max_text_length`]
+    TextLength,
+    /// [`ParseLimits::max_names`]
+    Names,
+    /// [`ParseLimits::max_namespaces`]
+    Namespaces,
+    /// [`ParseLimits::max_prefixes`]
+    Prefixes,
+    /// [`ParseLimits::max_nodes`]
+    Nodes,
+    /// The total length of the replacement text of the entities declared
+    /// in the DTD that a document may expand, which is 10 MB. This limit
+    /// always applies, even if you don't set any [`ParseLimits`].
+    EntityExpansion,
+}
+
+impl std::fmt::Display for ParseLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ParseLimit::Depth => "depth",
+            ParseLimit::Attributes => "attributes",
+            ParseLimit::TextLength => "text length",
+            ParseLimit::Names => "names",
+            ParseLimit::Namespaces => "namespaces",
+            ParseLimit::Prefixes => "prefixes",
+            ParseLimit::Nodes => "nodes",
+            ParseLimit::EntityExpansion => "entity expansion",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// Returns an error if the value exceeds the limit.
+fn check_limit(
+    limit: Option<usize>,
+    value: usize,
+    parse_limit: ParseLimit,
+    span: Span,
+) -> Result<(), Error> {
+    match limit {
+        Some(limit) if value > limit => Err(Error::LimitExceeded(parse_limit, span)),
+        _ => Ok(()),
+    }
+}
+
+// Builds a document from tokens. The tokens may come from a single
+// tokenizer over the whole document, or from a sequence of tokenizers over
+// parts of it; `offset` is the position of the text that is currently being
+// tokenized in the document, so that spans are always relative to the
+// document.
+pub(crate) struct DocumentBuilder {
     tree: NodeId,
     current_node_id: NodeId,
     name_id_builder: NameIdBuilder,
     element_builder: Option<ElementBuilder>,
+    entities: Entities,
+    document_type: Option<DocumentType>,
+    metadata: Option<DocumentMetadata>,
+    options: ParseOptions,
+    // whether xml:space="preserve" is in scope, per open element
+    preserve_space_stack: Vec<bool>,
+    // the start spans of the open elements
+    element_span_stack: Vec<Span>,
+    internal_subset_start: usize,
+    pub(crate) offset: usize,
+    span_info: Option<SpanInfo>,
+    // if we recover from errors, the diagnostics for the errors we recovered
+    // from
+    diagnostics: Option<Vec<Diagnostic>>,
+    // the number of nodes we created, and the number of names, namespaces
+    // and prefixes the Xot had when we started, so we can check the limits
+    node_count: usize,
+    initial_name_count: usize,
+    initial_namespace_count: usize,
+    initial_prefix_count: usize,
+    // whether the document is XML 1.1
+    xml11: bool,
+    // if we hand off subtrees while we parse, the elements that were closed
+    // since we last looked
+    closed_elements: Option<Vec<NodeId>>,
 }
 
 impl DocumentBuilder {
-    fn new(xot: &mut Xot) -> Self {
+    pub(crate) fn new(
+        xot: &mut Xot,
+        options: ParseOptions,
+        span_info: bool,
+    ) -> Result<Self, Error> {
+        let entities = Entities::from_options(&options)?;
         let document = xot.arena.new_node(Value::Document);
         let mut name_id_builder = NameIdBuilder::new(xot.base_prefixes().into_iter().collect());
         let base_prefixes = vec![(xot.empty_prefix_id, xot.no_namespace_id)];
         name_id_builder.push(base_prefixes);
-        DocumentBuilder {
+        Ok(DocumentBuilder {
             tree: document,
             current_node_id: document,
             name_id_builder,
             element_builder: None,
+            entities,
+            document_type: None,
+            metadata: None,
+            options,
+            preserve_space_stack: vec![false],
+            element_span_stack: Vec::new(),
+            internal_subset_start: 0,
+            offset: 0,
+            span_info: span_info.then(SpanInfo::new),
+            diagnostics: None,
+            node_count: 0,
+            initial_name_count: xot.name_lookup.len(),
+            initial_namespace_count: xot.namespace_lookup.len(),
+            initial_prefix_count: xot.prefix_lookup.len(),
+            xml11: false,
+            closed_elements: None,
+        })
+    }
+
+    // If we recover from errors, record the error so we can go on,
+    // otherwise return it.
+    fn recover(&mut self, error: Error) -> Result<(), Error> {
+        if self.diagnostics.is_none() {
+            return Err(error);
         }
+        self.add_error_diagnostic(error);
+        Ok(())
     }
 
-    fn element(&mut self, prefix: StrSpan<'_>, name: StrSpan<'_>) {
-        self.element_builder = Some(ElementBuilder::new(prefix, name));
+    // a diagnostic for an error of our own, which we also use for the
+    // errors we can't recover from, such as an exceeded limit
+    fn add_error_diagnostic(&mut self, error: Error) {
+        let span = error
+            .span()
+            .unwrap_or_else(|| Span::new(self.offset, self.offset));
+        if let Some(diagnostics) = &mut self.diagnostics {
+            diagnostics.push(Diagnostic { error, span });
+        }
     }
 
-    fn prefix(&mut self, prefix: &str, namespace_uri: &str, xot: &mut Xot) {
-        let prefix_id = xot.prefix_lookup.get_id_mut(prefix);
-        let namespace_id = xot.namespace_lookup.get_id_mut(namespace_uri);
-        self.element_builder
+    // a diagnostic for a tokenizer error, which has no span of its own
+    fn add_diagnostic(&mut self, error: xmlparser::Error, span: Span) {
+        if let Some(diagnostics) = &mut self.diagnostics {
+            diagnostics.push(Diagnostic {
+                error: error.into(),
+                span,
+            });
+        }
+    }
+
+    // Start recording the elements that are closed.
+    pub(crate) fn record_closed_elements(&mut self) {
+        self.closed_elements = Some(Vec::new());
+    }
+
+    // The elements that were closed since we last looked, in the order they
+    // were closed.
+    pub(crate) fn take_closed_elements(&mut self) -> Vec<NodeId> {
+        self.closed_elements
             .as_mut()
-            .unwrap()
-            .namespaces
-            .push((prefix_id, namespace_id));
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn element_closed(&mut self, node_id: NodeId) {
+        if let Some(closed_elements) = &mut self.closed_elements {
+            closed_elements.push(node_id);
+        }
+    }
+
+    fn diagnostic_count(&self) -> usize {
+        self.diagnostics
+            .as_ref()
+            .map_or(0, |diagnostics| diagnostics.len())
     }
 
-    fn attribute(
+    fn is_recovering(&self) -> bool {
+        self.diagnostics.is_some()
+    }
+
+    // the name in no namespace, which is what we use when we recover from an
+    // unknown prefix
+    fn no_namespace_name_id(name: &str, xot: &mut Xot) -> NameId {
+        let name = Name::new(name.to_string(), xot.no_namespace_id);
+        xot.name_lookup.get_id_mut(&name)
+    }
+
+    fn span(&self, span: StrSpan<'_>) -> Span {
+        Span::new(self.offset + span.start(), self.offset + span.end())
+    }
+
+    fn add_span(&mut self, key: SpanInfoKey, span: Span) {
+        if let Some(span_info) = &mut self.span_info {
+            span_info.add(key, span);
+        }
+    }
+
+    fn extend_text_span(&mut self, node_id: NodeId, span: Span) {
+        if let Some(span_info) = &mut self.span_info {
+            span_info.extend_text_span(node_id.into(), span);
+        }
+    }
+
+    pub(crate) fn element(&mut self, prefix: StrSpan<'_>, name: StrSpan<'_>) {
+        self.element_builder = Some(ElementBuilder::new(prefix, name, self.offset));
+    }
+
+    // whether we're in a start tag
+    pub(crate) fn is_element_pending(&self) -> bool {
+        self.element_builder.is_some()
+    }
+
+    // Declare a prefix. The name span is that of the declaring attribute,
+    // the value span that of the namespace URI.
+    pub(crate) fn prefix(
+        &mut self,
+        prefix: &str,
+        namespace_uri: &str,
+        name_span: Option<Span>,
+        value_span: Option<Span>,
+        xot: &mut Xot,
+    ) -> Result<(), Error> {
+        let result = self.element_builder.as_mut().unwrap().declare_prefix(
+            prefix,
+            namespace_uri,
+            name_span,
+            value_span,
+            self.xml11,
+            xot,
+        );
+        match result {
+            Ok(()) => Ok(()),
+            Err(error) => self.recover(error),
+        }
+    }
+
+    // Add an attribute. If the value is literal, entities in it aren't
+    // expanded.
+    pub(crate) fn attribute(
         &mut self,
         prefix: StrSpan<'_>,
         name: StrSpan<'_>,
         value: StrSpan<'_>,
+        literal: bool,
     ) -> Result<(), Error> {
-        let attributes = &mut self.element_builder.as_mut().unwrap().attributes;
-        let is_duplicate = attributes.iter().any(|attribute_builder| {
-            attribute_builder.prefix == prefix.as_str() && attribute_builder.name == name.as_str()
-        });
-        if is_duplicate {
-            let attr_name = if prefix.is_empty() {
-                name.to_string()
-            } else {
-                format!("{}:{}", prefix, name)
-            };
-            return Err(Error::DuplicateAttribute(attr_name));
+        let name_span = Span::from_prefix_name(prefix, name, self.offset);
+        if let Err(error) = self
+            .element_builder
+            .as_ref()
+            .unwrap()
+            .check_duplicate_attribute(prefix.as_str(), name.as_str(), name_span)
+        {
+            // when we recover, the first attribute wins
+            return self.recover(error);
         }
+        let value_span = Span::new(self.offset + value.start(), self.offset + value.end());
+        let value_offset = value_span.start;
+        // the limit applies to the value after entities are expanded
+        let max_length = self.options.limits.max_text_length;
+        let value = if literal {
+            check_limit(
+                max_length,
+                value.as_str().len(),
+                ParseLimit::TextLength,
+                value_span,
+            )?;
+            value.to_string()
+        } else if self.is_recovering() {
+            let mut errors = Vec::new();
+            let value = parse_attribute_recovering(
+                value.as_str().into(),
+                &self.entities,
+                value_offset,
+                max_length,
+                &mut errors,
+            )?
+            .to_string();
+            for error in errors {
+                self.recover(error)?;
+            }
+            value
+        } else {
+            parse_attribute(
+                value.as_str().into(),
+                &self.entities,
+                value_offset,
+                max_length,
+            )?
+            .to_string()
+        };
+        let attributes = &mut self.element_builder.as_mut().unwrap().attributes;
         attributes.push(AttributeBuilder {
             prefix: prefix.to_string(),
             name: name.to_string(),
-            value: parse_attribute(value.as_str().into())?.to_string(),
-            name_span: Span::from_prefix_name(prefix, name),
-            value_span: value.into(),
+            value,
+            name_span,
+            value_span,
         });
         Ok(())
     }
@@ -106,27 +574,48 @@ impl DocumentBuilder {
     fn add(&mut self, value: Value, xot: &mut Xot) -> NodeId {
         let node_id = xot.arena.new_node(value);
         self.current_node_id.append(node_id, &mut xot.arena);
+        self.node_count += 1;
         node_id
     }
 
     fn open_element(&mut self, xot: &mut Xot) -> Result<(NodeId, Span, AttributeSpans), Error> {
         let element_builder = self.element_builder.take().unwrap();
         let span = element_builder.span;
+        self.element_span_stack.push(span);
+        check_limit(
+            self.options.limits.max_depth,
+            self.element_span_stack.len(),
+            ParseLimit::Depth,
+            span,
+        )?;
 
         self.name_id_builder
             .push(element_builder.namespaces.clone());
 
-        let name_id = self.name_id_builder.element_name_id(
+        let name_id = match self.name_id_builder.element_name_id(
             &element_builder.prefix,
             &element_builder.name,
+            span,
             xot,
-        )?;
+        ) {
+            Ok(name_id) => name_id,
+            Err(error) => {
+                self.recover(error)?;
+                Self::no_namespace_name_id(&element_builder.name, xot)
+            }
+        };
         let element_value = Value::Element(Element { name_id });
         let node_id = self.add(element_value, xot);
         self.current_node_id = node_id;
 
         // add namespace nodes
         for (prefix_id, namespace_id) in &element_builder.namespaces {
+            // an undeclaration only affects how names are resolved while we
+            // parse
+            if is_undeclaration(*prefix_id, *namespace_id, xot) {
+                continue;
+            }
+            self.node_count += 1;
             let namespace_node = xot.arena.new_node(Value::Namespace(Namespace {
                 prefix_id: *prefix_id,
                 namespace_id: *namespace_id,
@@ -135,12 +624,35 @@ impl DocumentBuilder {
         }
         // add attribute nodes
         let mut attribute_spans = Vec::new();
+        let mut preserve_space = self.is_preserve_space();
         for attribute_builder in element_builder.attributes {
-            let name_id = self.name_id_builder.attribute_name_id(
+            let name_id = match self.name_id_builder.attribute_name_id(
                 &attribute_builder.prefix,
                 &attribute_builder.name,
+                attribute_builder.name_span,
                 xot,
-            )?;
+            ) {
+                Ok(name_id) => name_id,
+                Err(error) => {
+                    self.recover(error)?;
+                    Self::no_namespace_name_id(&attribute_builder.name, xot)
+                }
+            };
+            if let Err(error) = attribute_builder
+                .check_expanded_name(name_id, attribute_spans.iter().map(|(id, _, _)| *id))
+            {
+                self.recover(error)?;
+                // when we recover, the first attribute wins
+                continue;
+            }
+            if name_id == xot.xml_space_id {
+                match attribute_builder.value.as_str() {
+                    "preserve" => preserve_space = true,
+                    "default" => preserve_space = false,
+                    _ => {}
+                }
+            }
+            self.node_count += 1;
             let attribute_node = xot.arena.new_node(Value::Attribute(Attribute {
                 name_id,
                 value: attribute_builder.value,
@@ -152,78 +664,205 @@ impl DocumentBuilder {
             ));
             self.current_node_id.append(attribute_node, &mut xot.arena);
         }
+        self.preserve_space_stack.push(preserve_space);
 
         Ok((node_id, span, attribute_spans))
     }
 
-    // consolidates a text node with previous node if possible. If consolidation
-    // took place returns the node id , otherwise none.
-    fn consolidate_text(&mut self, content: &str, xot: &mut Xot) -> Option<NodeId> {
+    // the text node that text would be consolidated with, if any
+    fn consolidation_target(&self, cdata: bool, xot: &Xot) -> Option<NodeId> {
+        if !self.options.text_consolidation {
+            return None;
+        }
         // let's look at the last node we added
-        let last = xot.arena[self.current_node_id].last_child();
-        if let Some(last) = last {
-            let value = xot.arena.get_mut(last).unwrap().get_mut();
-            if let Value::Text(last_text) = value {
-                last_text.get_mut().push_str(content);
-                return Some(last);
+        let last = xot.arena[self.current_node_id].last_child()?;
+        match xot.arena[last].get() {
+            Value::Text(last_text)
+                if !(self.options.cdata_boundaries && (cdata || last_text.is_cdata())) =>
+            {
+                Some(last)
             }
+            _ => None,
         }
-        None
     }
 
-    fn text(&mut self, content: &str, xot: &mut Xot) -> Result<NodeId, Error> {
-        let content = parse_text(content.into())?;
-        if let Some(last) = self.consolidate_text(&content, xot) {
+    // consolidates a text node with previous node if possible. If consolidation
+    // took place returns the node id , otherwise none.
+    fn consolidate_text(&mut self, content: &str, cdata: bool, xot: &mut Xot) -> Option<NodeId> {
+        let last = self.consolidation_target(cdata, xot)?;
+        if let Value::Text(last_text) = xot.arena[last].get_mut() {
+            last_text.get_mut().push_str(content);
+        }
+        Some(last)
+    }
+
+    fn text(&mut self, content: &str, offset: usize, xot: &mut Xot) -> Result<NodeId, Error> {
+        // the limit applies to the text after entities are expanded, and
+        // consolidated text counts as a whole
+        let max_length = self.options.limits.max_text_length.map(|max_length| {
+            let consolidated = match self.consolidation_target(false, xot) {
+                Some(node_id) => match xot.arena[node_id].get() {
+                    Value::Text(text) => text.get().len(),
+                    _ => 0,
+                },
+                None => 0,
+            };
+            max_length.saturating_sub(consolidated)
+        });
+        let content = if self.is_recovering() {
+            let mut errors = Vec::new();
+            let content = parse_text_recovering(
+                content.into(),
+                &self.entities,
+                offset,
+                max_length,
+                &mut errors,
+            )?;
+            for error in errors {
+                self.recover(error)?;
+            }
+            content
+        } else {
+            parse_text(content.into(), &self.entities, offset, max_length)?
+        };
+        if let Some(last) = self.consolidate_text(&content, false, xot) {
             return Ok(last);
         }
         Ok(self.add(Value::Text(Text::new(content.to_string())), xot))
     }
 
     fn cdata_text(&mut self, content: &str, xot: &mut Xot) -> Result<NodeId, Error> {
-        if let Some(last) = self.consolidate_text(content, xot) {
-            return Ok(last);
+        Ok(self.literal_text(content, true, xot))
+    }
+
+    // Add text that has no entities to expand.
+    pub(crate) fn literal_text(&mut self, content: &str, cdata: bool, xot: &mut Xot) -> NodeId {
+        if let Some(last) = self.consolidate_text(content, cdata, xot) {
+            return last;
         }
-        Ok(self.add(Value::Text(Text::new(content.to_string())), xot))
+        let text = if cdata && self.options.cdata_boundaries {
+            Text::new_cdata(content.to_string())
+        } else {
+            Text::new(content.to_string())
+        };
+        self.add(Value::Text(text), xot)
+    }
+
+    fn is_preserve_space(&self) -> bool {
+        // there's always a bottom entry
+        *self.preserve_space_stack.last().unwrap()
     }
 
-    fn close_element_immediate(&mut self, xot: &mut Xot) -> NodeId {
+    // Strips whitespace-only text from the current element before we close
+    // it, in the same way as `remove_insignificant_whitespace`: text is only
+    // insignificant if the element has no other text that's significant.
+    // Returns the text nodes that were removed.
+    fn strip_whitespace(&mut self, xot: &mut Xot) -> Vec<NodeId> {
+        if !self.options.strip_whitespace || self.is_preserve_space() {
+            return Vec::new();
+        }
+        let mut text_node_ids = Vec::new();
+        for node_id in self.current_node_id.children(&xot.arena) {
+            if let Value::Text(text) = xot.arena[node_id].get() {
+                if !is_whitespace(text.get()) {
+                    return Vec::new();
+                }
+                text_node_ids.push(node_id);
+            }
+        }
+        for node_id in &text_node_ids {
+            node_id.remove(&mut xot.arena);
+        }
+        text_node_ids
+    }
+
+    pub(crate) fn close_element_immediate(&mut self, xot: &mut Xot) -> NodeId {
         let current_node = xot.arena.get(self.current_node_id).unwrap();
         if matches!(current_node.get(), Value::Element(_)) {
             self.name_id_builder.pop();
+            self.preserve_space_stack.pop();
+            self.element_span_stack.pop();
         }
         let closed_node_id = self.current_node_id;
         self.current_node_id = current_node.parent().expect("Cannot close document node");
         closed_node_id
     }
 
-    fn close_element(&mut self, prefix: &str, name: &str, xot: &mut Xot) -> Result<NodeId, Error> {
-        let name_id = self.name_id_builder.element_name_id(prefix, name, xot)?;
-        let current_node = xot.arena.get(self.current_node_id).unwrap();
-        if let Value::Element(element) = current_node.get() {
-            if element.name_id != name_id {
-                return Err(Error::InvalidCloseTag(prefix.to_string(), name.to_string()));
+    fn close_element(
+        &mut self,
+        prefix: &str,
+        name: &str,
+        span: Span,
+        xot: &mut Xot,
+    ) -> Result<Option<NodeId>, Error> {
+        let name_id = match self
+            .name_id_builder
+            .element_name_id(prefix, name, span, xot)
+        {
+            Ok(name_id) => name_id,
+            Err(error) => {
+                self.recover(error)?;
+                Self::no_namespace_name_id(name, xot)
+            }
+        };
+        // when we parse a fragment, a close tag may appear without a start
+        // tag
+        if !self.is_open_element(self.current_node_id, name_id, xot) {
+            let error = Error::InvalidCloseTag(prefix.to_string(), name.to_string(), span);
+            // when we recover, we close any elements that are still open
+            // inside a matching open element, and otherwise ignore the close
+            // tag
+            let is_ancestor_open =
+                xot.arena[self.current_node_id]
+                    .parent()
+                    .is_some_and(|parent_id| {
+                        parent_id
+                            .ancestors(&xot.arena)
+                            .any(|node_id| self.is_open_element(node_id, name_id, xot))
+                    });
+            if !self.is_recovering() || !is_ancestor_open {
+                self.recover(error)?;
+                return Ok(None);
+            }
+            while !self.is_open_element(self.current_node_id, name_id, xot) {
+                let span = *self.element_span_stack.last().unwrap();
+                self.recover(Error::UnclosedTag(span))?;
+                self.close_element_immediate(xot);
             }
-            self.name_id_builder.pop();
         }
-        let closed_node_id = self.current_node_id;
-        self.current_node_id = current_node.parent().expect("Cannot close document node");
-        Ok(closed_node_id)
+        Ok(Some(self.close_element_immediate(xot)))
+    }
+
+    fn is_open_element(&self, node_id: NodeId, name_id: NameId, xot: &Xot) -> bool {
+        match xot.arena[node_id].get() {
+            Value::Element(element) => element.name_id == name_id,
+            _ => false,
+        }
+    }
+
+    // When we recover from an error in the middle of a start tag, we still
+    // open the element with what we have.
+    pub(crate) fn open_pending_element(&mut self, xot: &mut Xot) -> Result<(), Error> {
+        if self.element_builder.is_some() {
+            let (node_id, span, attribute_spans) = self.open_element(xot)?;
+            self.add_span(SpanInfoKey::ElementStart(node_id.into()), span);
+            if let Some(span_info) = &mut self.span_info {
+                span_info.add_attribute_spans(node_id, attribute_spans);
+            }
+        }
+        Ok(())
     }
 
-    fn comment(&mut self, content: &str, xot: &mut Xot) -> Result<NodeId, Error> {
-        // XXX are there illegal comments, like those with -- inside? or
-        // won't they pass the parser?
+    pub(crate) fn comment(&mut self, content: &str, xot: &mut Xot) -> Result<NodeId, Error> {
         Ok(self.add(Value::Comment(Comment::new(content.to_string())), xot))
     }
 
-    fn processing_instruction(
+    pub(crate) fn processing_instruction(
         &mut self,
         target: &str,
         content: Option<&str>,
         xot: &mut Xot,
     ) -> Result<NodeId, Error> {
-        // XXX are there illegal processing instructions, like those with
-        // ?> inside? or won't they pass the parser? What about those with xml?
         let target = xot.add_name(target);
         Ok(self.add(
             Value::ProcessingInstruction(ProcessingInstruction::new(
@@ -234,48 +873,493 @@ impl DocumentBuilder {
         ))
     }
 
-    fn is_current_node_document(&self, xot: &Xot) -> bool {
+    fn declaration(&mut self, version: &str, encoding: Option<&str>, standalone: Option<bool>) {
+        self.metadata = Some(DocumentMetadata {
+            version: Some(version.to_string()),
+            encoding: encoding.map(|encoding| encoding.to_string()),
+            standalone,
+            detected_encoding: None,
+        });
+    }
+
+    fn document_type(&mut self, name: &str, external_id: Option<ExternalId<'_>>) {
+        let external_id = external_id.map(|external_id| match external_id {
+            ExternalId::System(system) => DocType::System {
+                system: system.to_string(),
+            },
+            ExternalId::Public(public, system) => DocType::Public {
+                public: public.to_string(),
+                system: system.to_string(),
+            },
+        });
+        self.document_type = Some(DocumentType {
+            name: name.to_string(),
+            external_id,
+            internal_subset: None,
+        });
+    }
+
+    fn internal_subset(&mut self, internal_subset: &str) {
+        if let Some(document_type) = self.document_type.as_mut() {
+            document_type.internal_subset = Some(internal_subset.to_string());
+        }
+    }
+
+    fn entity_declaration(
+        &mut self,
+        name: &str,
+        definition: EntityDefinition<'_>,
+        span: StrSpan<'_>,
+    ) -> Result<(), Error> {
+        if let Err(error) = declare_entity(&mut self.entities, name, definition, span, self.offset)
+        {
+            self.recover(error)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn is_current_node_document(&self, xot: &Xot) -> bool {
         matches!(xot.arena[self.current_node_id].get(), Value::Document)
     }
+
+    // whether the document element has been closed
+    pub(crate) fn is_document_element_closed(&self, xot: &Xot) -> bool {
+        self.current_node_id == self.tree
+            && self
+                .tree
+                .children(&xot.arena)
+                .any(|node_id| matches!(xot.arena[node_id].get(), Value::Element(_)))
+    }
+
+    // Process a token. `source` is the text that is being tokenized.
+    pub(crate) fn token(
+        &mut self,
+        token: Token<'_>,
+        source: &str,
+        xot: &mut Xot,
+    ) -> Result<(), Error> {
+        // names are only added when the start tag is complete, so we
+        // report the element for that
+        let span = match (&token, &self.element_builder) {
+            (Token::ElementEnd { .. }, Some(element_builder)) => element_builder.span,
+            _ => self.span(token_span(&token)),
+        };
+        self.process_token(token, source, xot)?;
+        self.check_totals(span, xot)
+    }
+
+    // Check the limits on what the document as a whole adds.
+    fn check_totals(&self, span: Span, xot: &Xot) -> Result<(), Error> {
+        let limits = &self.options.limits;
+        check_limit(limits.max_nodes, self.node_count, ParseLimit::Nodes, span)?;
+        check_limit(
+            limits.max_names,
+            xot.name_lookup.len() - self.initial_name_count,
+            ParseLimit::Names,
+            span,
+        )?;
+        check_limit(
+            limits.max_namespaces,
+            xot.namespace_lookup.len() - self.initial_namespace_count,
+            ParseLimit::Namespaces,
+            span,
+        )?;
+        check_limit(
+            limits.max_prefixes,
+            xot.prefix_lookup.len() - self.initial_prefix_count,
+            ParseLimit::Prefixes,
+            span,
+        )
+    }
+
+    fn check_text_length(&self, node_id: NodeId, span: Span, xot: &Xot) -> Result<(), Error> {
+        if let Value::Text(text) = xot.arena[node_id].get() {
+            check_limit(
+                self.options.limits.max_text_length,
+                text.get().len(),
+                ParseLimit::TextLength,
+                span,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn process_token(
+        &mut self,
+        token: Token<'_>,
+        source: &str,
+        xot: &mut Xot,
+    ) -> Result<(), Error> {
+        use Token::*;
+
+        if self.xml11 {
+            if let Err(error) = check_restricted_chars(&token, self.offset) {
+                self.recover(error)?;
+            }
+        }
+        match token {
+            Attribute {
+                prefix,
+                local,
+                value,
+                span,
+            } => {
+                let name_span = Span::from_prefix_name(prefix, local, self.offset);
+                match declared_prefix(prefix.as_str(), local.as_str()) {
+                    Some(declared) => {
+                        self.prefix(
+                            declared,
+                            value.as_str(),
+                            Some(name_span),
+                            Some(self.span(value)),
+                            xot,
+                        )?;
+                    }
+                    None => self.attribute(prefix, local, value, false)?,
+                }
+                let element_builder = self.element_builder.as_ref().unwrap();
+                check_limit(
+                    self.options.limits.max_attributes,
+                    element_builder.namespaces.len() + element_builder.attributes.len(),
+                    ParseLimit::Attributes,
+                    self.span(span),
+                )?;
+            }
+            Text { text } => {
+                let node_id = self.text(text.as_str(), self.offset + text.start(), xot)?;
+                self.extend_text_span(node_id, self.span(text));
+                self.check_text_length(node_id, self.span(text), xot)?;
+            }
+            Cdata { text, span: _ } => {
+                let node_id = self.cdata_text(text.as_str(), xot)?;
+                self.extend_text_span(node_id, self.span(text));
+                self.check_text_length(node_id, self.span(text), xot)?;
+            }
+            ElementStart {
+                prefix,
+                local,
+                span: _,
+            } => {
+                self.element(prefix, local);
+            }
+            ElementEnd {
+                end,
+                span: end_span,
+            } => {
+                use xmlparser::ElementEnd::*;
+
+                let end_span = self.span(end_span);
+                match end {
+                    Open => {
+                        let (node_id, span, attribute_spans) = self.open_element(xot)?;
+                        self.add_span(SpanInfoKey::ElementStart(node_id.into()), span);
+                        if let Some(span_info) = &mut self.span_info {
+                            span_info.add_attribute_spans(node_id, attribute_spans);
+                        }
+                    }
+                    Close(prefix, local) => {
+                        let removed = self.strip_whitespace(xot);
+                        if let Some(span_info) = &mut self.span_info {
+                            span_info.remove_text_spans(removed);
+                        }
+                        if let Some(node_id) =
+                            self.close_element(prefix.as_str(), local.as_str(), end_span, xot)?
+                        {
+                            self.add_span(SpanInfoKey::ElementEnd(node_id.into()), end_span);
+                            self.element_closed(node_id);
+                        }
+                    }
+                    Empty => {
+                        let (node_id, span, attribute_spans) = self.open_element(xot)?;
+                        self.add_span(SpanInfoKey::ElementStart(node_id.into()), span);
+                        if let Some(span_info) = &mut self.span_info {
+                            span_info.add_attribute_spans(node_id, attribute_spans);
+                        }
+                        let node_id = self.close_element_immediate(xot);
+                        self.add_span(SpanInfoKey::ElementEnd(node_id.into()), end_span);
+                        self.element_closed(node_id);
+                    }
+                }
+            }
+            Comment { text, span: _ } => {
+                if self.options.strip_comments {
+                    return Ok(());
+                }
+                let node_id = self.comment(text.as_str(), xot)?;
+                self.add_span(SpanInfoKey::Comment(node_id.into()), self.span(text));
+            }
+            ProcessingInstruction {
+                target,
+                content,
+                span: _,
+            } => {
+                if self.options.strip_processing_instructions {
+                    return Ok(());
+                }
+                let node_id =
+                    self.processing_instruction(target.as_str(), content.map(|s| s.as_str()), xot)?;
+                self.add_span(SpanInfoKey::PiTarget(node_id.into()), self.span(target));
+                if let Some(content) = content {
+                    self.add_span(SpanInfoKey::PiContent(node_id.into()), self.span(content));
+                }
+            }
+            Declaration {
+                version,
+                encoding,
+                standalone,
+                span: _,
+            } => {
+                match is_xml11(version, self.offset) {
+                    Ok(xml11) => {
+                        self.xml11 = xml11;
+                        self.entities.set_xml11(xml11);
+                    }
+                    Err(error) => self.recover(error)?,
+                }
+                self.declaration(
+                    version.as_str(),
+                    encoding.map(|encoding| encoding.as_str()),
+                    standalone,
+                );
+            }
+            DtdStart {
+                name,
+                external_id,
+                span,
+            } => {
+                self.document_type(name.as_str(), external_id);
+                self.internal_subset_start = span.end();
+            }
+            DtdEnd { span } => {
+                self.internal_subset(&source[self.internal_subset_start..span.start()]);
+            }
+            EmptyDtd {
+                name,
+                external_id,
+                span: _,
+            } => {
+                self.document_type(name.as_str(), external_id);
+            }
+            EntityDeclaration {
+                name,
+                definition,
+                span,
+            } => {
+                self.entity_declaration(name.as_str(), definition, span)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Finish the document when we recover from errors: any elements that
+    // are still open are closed. Returns the diagnostics.
+    fn finish_recovering(mut self, xot: &mut Xot) -> (Node, Vec<Diagnostic>) {
+        if let Err(error) = self.open_pending_element(xot) {
+            self.add_error_diagnostic(error);
+        }
+        while !self.is_current_node_document(xot) {
+            let span = *self.element_span_stack.last().unwrap();
+            self.add_error_diagnostic(Error::UnclosedTag(span));
+            self.close_element_immediate(xot);
+        }
+        let diagnostics = self.diagnostics.take().unwrap_or_default();
+        let (document, _) = self.finish_document(xot);
+        (document, diagnostics)
+    }
+
+    // Finish a fragment, returning the top-level nodes. They're detached
+    // from the document node we used to hold them, which is removed.
+    fn finish_fragment(self, end: usize, xot: &mut Xot) -> Result<Vec<Node>, Error> {
+        if !self.is_current_node_document(xot) {
+            let span = self
+                .element_span_stack
+                .last()
+                .copied()
+                .unwrap_or_else(|| Span::new(end, end));
+            return Err(Error::UnclosedTag(span));
+        }
+        let node_ids = self.tree.children(&xot.arena).collect::<Vec<_>>();
+        for node_id in &node_ids {
+            node_id.detach(&mut xot.arena);
+        }
+        self.tree.remove(&mut xot.arena);
+        Ok(node_ids.into_iter().map(Node::new).collect())
+    }
+
+    // Finish the document. `end` is the position of the end of the
+    // document.
+    pub(crate) fn finish(self, end: usize, xot: &mut Xot) -> Result<(Node, SpanInfo), Error> {
+        if self.is_current_node_document(xot) {
+            Ok(self.finish_document(xot))
+        } else {
+            // the span of the innermost unclosed element
+            let span = self
+                .element_span_stack
+                .last()
+                .copied()
+                .unwrap_or_else(|| Span::new(end, end));
+            Err(Error::UnclosedTag(span))
+        }
+    }
+
+    // Finish a document of which all elements are closed.
+    fn finish_document(self, xot: &mut Xot) -> (Node, SpanInfo) {
+        let document = Node::new(self.tree);
+        if let Some(document_type) = self.document_type {
+            xot.document_types.insert(document, document_type);
+        }
+        if let Some(metadata) = self.metadata {
+            xot.document_metadata.insert(document, metadata);
+        }
+        (document, self.span_info.unwrap_or_else(SpanInfo::new))
+    }
 }
 
-struct NameIdBuilder {
+// Whether the declared version is XML 1.1. Versions other than 1.0 and 1.1
+// are not supported.
+pub(crate) fn is_xml11(version: StrSpan<'_>, offset: usize) -> Result<bool, Error> {
+    match version.as_str() {
+        "1.0" => Ok(false),
+        "1.1" => Ok(true),
+        _ => Err(Error::UnsupportedVersion(
+            version.to_string(),
+            Span::new(offset + version.start(), offset + version.end()),
+        )),
+    }
+}
+
+// Check the namespace constraints on a prefix declaration. The name span is
+// that of the declaring attribute, the value span that of the namespace URI;
+// there are none if the declaration doesn't come from a source text.
+fn check_prefix_declaration(
+    prefix: &str,
+    namespace_uri: &str,
+    name_span: Option<Span>,
+    value_span: Option<Span>,
+    xml11: bool,
+) -> Result<(), Error> {
+    // https://www.w3.org/TR/xml-names/#xmlReserved
+    if prefix == "xmlns" || (prefix == "xml" && namespace_uri != XML_NAMESPACE) {
+        return Err(Error::ReservedPrefix(prefix.to_string(), name_span));
+    }
+    if (namespace_uri == XML_NAMESPACE && prefix != "xml") || namespace_uri == XMLNS_NAMESPACE {
+        return Err(Error::ReservedNamespace(
+            namespace_uri.to_string(),
+            value_span,
+        ));
+    }
+    // https://www.w3.org/TR/xml-names/#nsc-NoPrefixUndecl
+    // XML 1.1 allows a prefix to be undeclared
+    // https://www.w3.org/TR/xml-names11/#scoping
+    if namespace_uri.is_empty() && !prefix.is_empty() && !xml11 {
+        return Err(Error::EmptyPrefixedNamespace(
+            prefix.to_string(),
+            value_span,
+        ));
+    }
+    Ok(())
+}
+
+// XML 1.1 allows some control characters only as character references.
+// https://www.w3.org/TR/xml11/#NT-RestrictedChar
+pub(crate) fn check_restricted_chars(token: &Token<'_>, offset: usize) -> Result<(), Error> {
+    let content = match *token {
+        Token::Attribute { value, .. } => Some(value),
+        Token::Text { text } | Token::Cdata { text, .. } | Token::Comment { text, .. } => {
+            Some(text)
+        }
+        Token::ProcessingInstruction { content, .. } => content,
+        _ => None,
+    };
+    let Some(content) = content else {
+        return Ok(());
+    };
+    match content
+        .as_str()
+        .char_indices()
+        .find(|(_, c)| is_restricted_char(*c))
+    {
+        Some((i, c)) => {
+            let start = offset + content.start() + i;
+            Err(Error::RestrictedCharacter(
+                c,
+                Span::new(start, start + c.len_utf8()),
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+// Declare an entity from the internal DTD subset.
+pub(crate) fn declare_entity(
+    entities: &mut Entities,
+    name: &str,
+    definition: EntityDefinition<'_>,
+    span: StrSpan<'_>,
+    offset: usize,
+) -> Result<(), Error> {
+    // parameter entities are only meaningful inside the DTD, and
+    // xmlparser doesn't distinguish them from general entities, so we
+    // look for the `%` ourselves
+    let is_parameter_entity = span.as_str()["<!ENTITY".len()..]
+        .trim_start()
+        .starts_with('%');
+    if is_parameter_entity {
+        return Ok(());
+    }
+    // external entities are never loaded, so a reference to them
+    // results in an unknown entity
+    if let EntityDefinition::EntityValue(value) = definition {
+        entities.declare(name, value.as_str(), offset + value.start())?;
+    }
+    Ok(())
+}
+
+// In XML 1.1, a prefix can be undeclared by binding it to the empty
+// namespace URI.
+pub(crate) fn is_undeclaration(prefix_id: PrefixId, namespace_id: NamespaceId, xot: &Xot) -> bool {
+    prefix_id != xot.empty_prefix_id && namespace_id == xot.no_namespace_id
+}
+
+pub(crate) struct NameIdBuilder {
     namespace_stack: Vec<Namespaces>,
 }
 
 impl NameIdBuilder {
-    fn new(prefixes: Namespaces) -> Self {
+    pub(crate) fn new(prefixes: Namespaces) -> Self {
         let namespace_stack = vec![prefixes];
         Self { namespace_stack }
     }
 
-    fn push(&mut self, namespaces: Namespaces) {
+    pub(crate) fn push(&mut self, namespaces: Namespaces) {
         self.namespace_stack.push(namespaces);
     }
 
-    fn pop(&mut self) {
+    pub(crate) fn pop(&mut self) {
         // should always be able to pop as there's a bottom entry
         self.namespace_stack.pop();
     }
 
-    fn element_name_id(
+    pub(crate) fn element_name_id(
         &mut self,
         prefix: &str,
         name: &str,
+        span: Span,
         xot: &mut Xot,
     ) -> Result<NameId, Error> {
         let prefix_id = xot.prefix_lookup.get_id_mut(prefix);
         if let Ok(name_id) = self.name_id_with_prefix_id(prefix_id, name, xot) {
             Ok(name_id)
         } else {
-            Err(Error::UnknownPrefix(prefix.to_string()))
+            Err(Error::UnknownPrefix(prefix.to_string(), Some(span)))
         }
     }
 
-    fn attribute_name_id(
+    pub(crate) fn attribute_name_id(
         &mut self,
         prefix: &str,
         name: &str,
+        span: Span,
         xot: &mut Xot,
     ) -> Result<NameId, Error> {
         // an unprefixed attribute is in no namespace, not
@@ -289,7 +1373,7 @@ impl NameIdBuilder {
         if let Ok(name_id) = self.name_id_with_prefix_id(prefix_id, name, xot) {
             Ok(name_id)
         } else {
-            Err(Error::UnknownPrefix(prefix.to_string()))
+            Err(Error::UnknownPrefix(prefix.to_string(), Some(span)))
         }
     }
 
@@ -307,11 +1391,68 @@ impl NameIdBuilder {
                 .find_map(|(p, ns)| if *p == prefix_id { Some(*ns) } else { None })
         });
         let namespace_id = namespace_id.ok_or(())?;
+        if is_undeclaration(prefix_id, namespace_id, xot) {
+            return Err(());
+        }
         let name = Name::new(name.to_string(), namespace_id);
         Ok(xot.name_lookup.get_id_mut(&name))
     }
 }
 
+// the span of the whole token
+fn token_span<'a>(token: &Token<'a>) -> StrSpan<'a> {
+    match *token {
+        Token::Text { text } => text,
+        Token::Attribute { span, .. }
+        | Token::Cdata { span, .. }
+        | Token::ElementStart { span, .. }
+        | Token::ElementEnd { span, .. }
+        | Token::Comment { span, .. }
+        | Token::ProcessingInstruction { span, .. }
+        | Token::Declaration { span, .. }
+        | Token::DtdStart { span, .. }
+        | Token::DtdEnd { span }
+        | Token::EmptyDtd { span, .. }
+        | Token::EntityDeclaration { span, .. } => span,
+    }
+}
+
+/// A diagnostic for an error that was recovered from
+///
+/// You get these from [`Xot::parse_with_recovery`](crate::Xot::parse_with_recovery).
+#[derive(Debug)]
+pub struct Diagnostic {
+    /// The error
+    pub error: Error,
+    /// Where the error occurred in the XML source. For a tokenizer error this
+    /// is the text that was skipped.
+    pub span: Span,
+}
+
+// the position in the text for a byte offset, as xmlparser reports it
+fn text_pos(text: &str, offset: usize) -> TextPos {
+    let before = &text[..offset];
+    let row = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let col = before[line_start..].chars().count() + 1;
+    TextPos::new(row as u32, col as u32)
+}
+
+// the byte offset for a position in the text, as xmlparser reports it
+fn text_pos_offset(text: &str, pos: TextPos) -> usize {
+    let line_start = text
+        .match_indices('\n')
+        .nth((pos.row as usize).saturating_sub(2))
+        .filter(|_| pos.row > 1)
+        .map(|(i, _)| i + 1)
+        .unwrap_or(0);
+    text[line_start..]
+        .char_indices()
+        .nth((pos.col as usize).saturating_sub(1))
+        .map(|(i, _)| line_start + i)
+        .unwrap_or(text.len())
+}
+
 /// A span with a start and end position
 ///
 /// Spans describe ranges in the source text, with the end point not inclusive,
@@ -335,11 +1476,11 @@ impl Span {
         Span { start, end }
     }
 
-    fn from_prefix_name(prefix: StrSpan<'_>, name: StrSpan<'_>) -> Self {
+    pub(crate) fn from_prefix_name(prefix: StrSpan<'_>, name: StrSpan<'_>, offset: usize) -> Self {
         if prefix.is_empty() {
-            Self::new(name.start(), name.end())
+            Self::new(offset + name.start(), offset + name.end())
         } else {
-            Self::new(prefix.start(), name.end())
+            Self::new(offset + prefix.start(), offset + name.end())
         }
     }
 
@@ -347,6 +1488,46 @@ impl Span {
     pub fn range(&self) -> std::ops::Range<usize> {
         self.start..self.end
     }
+
+    /// The 1-based line and column of the start of the span in the source.
+    ///
+    /// The column is counted in characters. A line ends with `\n`, `\r\n`
+    /// or `\r`.
+    ///
+    /// ```rust
+    /// use xot::Span;
+    ///
+    /// let xml = "<a>\n<b/></a>";
+    /// assert_eq!(Span::new(5, 6).line_column(xml), (2, 2));
+    /// ```
+    pub fn line_column(&self, source: &str) -> (usize, usize) {
+        let mut line = 1;
+        let mut column = 1;
+        let mut chars = source[..self.start.min(source.len())].chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' => {
+                    if chars.peek() == Some(&'\n') {
+                        continue;
+                    }
+                    line += 1;
+                    column = 1;
+                }
+                '\n' => {
+                    line += 1;
+                    column = 1;
+                }
+                _ => column += 1,
+            }
+        }
+        (line, column)
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
 }
 
 impl<'a> From<xmlparser::StrSpan<'a>> for Span {
@@ -431,6 +1612,9 @@ pub enum SpanInfoKey {
 ///
 /// In every case all text content in the adjacent CDATA and text is included
 /// in the span.
+///
+/// If you parse with [`ParseOptions::cdata_boundaries`] or without
+/// [`ParseOptions::text_consolidation`], no such consolidation takes place.
 pub struct SpanInfo {
     map: HashMap<SpanInfoKey, Span>,
 }
@@ -451,6 +1635,12 @@ impl SpanInfo {
         self.map.insert(key, span);
     }
 
+    fn remove_text_spans(&mut self, node_ids: Vec<NodeId>) {
+        for node_id in node_ids {
+            self.map.remove(&SpanInfoKey::Text(node_id.into()));
+        }
+    }
+
     fn extend_text_span(&mut self, node: Node, span: Span) {
         // if we already have span for this (text) node it, we need to store the span with that
         // start and the given ending
@@ -485,123 +1675,205 @@ impl Xot {
     /// This parses the XML source into a Xot tree, and also returns
     /// [`SpanInfo`](`crate::SpanInfo`) which describes where nodes in the
     /// tree are located in the source text.
+    ///
+    /// The XML declaration is retained, see [`Xot::document_metadata`]. A
+    /// document type declaration is retained, see [`Xot::document_type`].
+    /// General entities declared in its internal subset are expanded in text
+    /// and attribute values; other declarations are ignored, and no external
+    /// DTD is loaded.
     pub fn parse_with_span_info(&mut self, xml: &str) -> Result<(Node, SpanInfo), Error> {
-        use Token::*;
+        self.parse_with_span_info_and_options(xml, ParseOptions::default())
+    }
 
-        let mut builder = DocumentBuilder::new(self);
-        let mut span_info = SpanInfo::new();
+    fn parse_with_span_info_and_options(
+        &mut self,
+        xml: &str,
+        options: ParseOptions,
+    ) -> Result<(Node, SpanInfo), Error> {
+        let mut builder = DocumentBuilder::new(self, options, true)?;
         for token in Tokenizer::from(xml) {
-            match token? {
-                Attribute {
-                    prefix,
-                    local,
-                    value,
-                    span: _,
-                } => {
-                    if prefix.as_str() == "xmlns" {
-                        builder.prefix(local.as_str(), value.as_str(), self);
-                    } else if local.as_str() == "xmlns" {
-                        builder.prefix("", value.as_str(), self);
-                    } else {
-                        builder.attribute(prefix, local, value)?;
-                    }
-                }
-                Text { text } => {
-                    let node_id = builder.text(text.as_str(), self)?;
-                    span_info.extend_text_span(node_id.into(), text.into());
-                }
-                Cdata { text, span: _ } => {
-                    let node_id = builder.cdata_text(text.as_str(), self)?;
-                    span_info.extend_text_span(node_id.into(), text.into());
-                }
-                ElementStart {
-                    prefix,
-                    local,
-                    span: _,
-                } => {
-                    builder.element(prefix, local);
-                }
-
-                ElementEnd {
-                    end,
-                    span: end_span,
-                } => {
-                    use self::ElementEnd::*;
+            builder.token(token?, xml, self)?;
+        }
+        builder.finish(xml.len(), self)
+    }
 
-                    match end {
-                        Open => {
-                            let (node_id, span, attribute_spans) = builder.open_element(self)?;
-                            span_info.add(SpanInfoKey::ElementStart(node_id.into()), span);
-                            span_info.add_attribute_spans(node_id, attribute_spans);
-                        }
-                        Close(prefix, local) => {
-                            let node_id =
-                                builder.close_element(prefix.as_str(), local.as_str(), self)?;
-                            span_info.add(SpanInfoKey::ElementEnd(node_id.into()), end_span.into());
+    /// Parse a string containing XML into a node, recovering from errors.
+    ///
+    /// Unlike [`Xot::parse`], this doesn't stop at the first error. Instead it
+    /// does its best to build a tree anyway, and returns the errors it
+    /// recovered from as [`Diagnostic`] values, in the order they were
+    /// encountered:
+    ///
+    /// - Elements that aren't closed are closed automatically, at the end of
+    ///   their parent or the end of the document. A close tag without a
+    ///   matching open element is ignored.
+    ///
+    /// - A name with an undeclared prefix is taken to be in no namespace.
+    ///
+    /// - An entity or character reference that cannot be expanded is kept in
+    ///   the text as it is.
+    ///
+    /// - If a duplicate attribute is found, the first one is kept.
+    ///
+    /// - Markup that cannot be tokenized is skipped up to the next `<`. Nothing
+    ///   after the document element is parsed once this happens.
+    ///
+    /// - If a limit is exceeded, such as the limit on the expansion of
+    ///   entities, parsing stops there.
+    ///
+    /// If there are no diagnostics, the tree is the same as the one
+    /// [`Xot::parse`] returns.
+    ///
+    /// ```rust
+    /// use xot::{Error, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let (root, diagnostics) = xot.parse_with_recovery("<a><b>&foo;</a>");
+    ///
+    /// assert_eq!(xot.to_string(root)?, "<a><b>&amp;foo;</b></a>");
+    /// assert_eq!(diagnostics.len(), 2);
+    /// assert!(matches!(diagnostics[0].error, Error::InvalidEntity(..)));
+    /// assert!(matches!(diagnostics[1].error, Error::UnclosedTag(..)));
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn parse_with_recovery(&mut self, xml: &str) -> (Node, Vec<Diagnostic>) {
+        let mut builder = DocumentBuilder::new(self, ParseOptions::default(), false)
+            .expect("default options are valid");
+        builder.diagnostics = Some(Vec::new());
+        let mut tokenizer = Tokenizer::from(xml);
+        'parse: loop {
+            let mut failed_at = None;
+            let mut resync_at = None;
+            for token in &mut tokenizer {
+                let token = match token {
+                    Ok(token) => token,
+                    Err(error) => {
+                        failed_at = Some((error, text_pos_offset(xml, error.pos())));
+                        break;
+                    }
+                };
+                // after we resume, we're tokenizing content, so we have to
+                // check what's allowed outside the document element ourselves
+                if builder.is_current_node_document(self) && builder.element_builder.is_none() {
+                    let unexpected = match &token {
+                        Token::Text { text } => {
+                            let content = text.as_str();
+                            (!is_whitespace(content)).then(|| {
+                                let start =
+                                    text.start() + (content.len() - content.trim_start().len());
+                                (start, text.end())
+                            })
                         }
-                        Empty => {
-                            let (node_id, span, attribute_spans) = builder.open_element(self)?;
-                            span_info.add(SpanInfoKey::ElementStart(node_id.into()), span);
-                            span_info.add_attribute_spans(node_id, attribute_spans);
-                            let node_id = builder.close_element_immediate(self);
-                            span_info.add(SpanInfoKey::ElementEnd(node_id.into()), end_span.into());
+                        Token::Cdata { span, .. } => Some((span.start(), span.end())),
+                        Token::ElementStart { span, .. }
+                            if builder.is_document_element_closed(self) =>
+                        {
+                            builder.add_diagnostic(
+                                xmlparser::Error::UnknownToken(text_pos(xml, span.start())),
+                                Span::new(span.start(), xml.len()),
+                            );
+                            break 'parse;
                         }
+                        _ => None,
+                    };
+                    if let Some((start, end)) = unexpected {
+                        builder.add_diagnostic(
+                            xmlparser::Error::UnknownToken(text_pos(xml, start)),
+                            Span::new(start, end),
+                        );
+                        continue;
                     }
                 }
-                Comment { text, span: _ } => {
-                    let node_id = builder.comment(text.as_str(), self)?;
-                    span_info.add(SpanInfoKey::Comment(node_id.into()), text.into());
+                let close_end = match &token {
+                    Token::ElementEnd {
+                        end: xmlparser::ElementEnd::Close(..),
+                        span,
+                    } => Some(span.end()),
+                    _ => None,
+                };
+                let diagnostic_count = builder.diagnostic_count();
+                // what's left are errors we can't recover from, such as an
+                // exceeded limit; we stop there
+                if let Err(error) = builder.token(token, xml, self) {
+                    builder.add_error_diagnostic(error);
+                    break 'parse;
                 }
-                ProcessingInstruction {
-                    target,
-                    content,
-                    span: _,
-                } => {
-                    let node_id = builder.processing_instruction(
-                        target.as_str(),
-                        content.map(|s| s.as_str()),
-                        self,
-                    )?;
-                    span_info.add(SpanInfoKey::PiTarget(node_id.into()), target.into());
-                    if let Some(content) = content {
-                        span_info.add(SpanInfoKey::PiContent(node_id.into()), content.into());
+                // the tokenizer keeps track of the depth of elements itself,
+                // which no longer matches ours if we recovered at a close tag
+                if let Some(close_end) = close_end {
+                    if builder.diagnostic_count() > diagnostic_count {
+                        resync_at = Some(close_end);
+                        break;
                     }
                 }
-                Declaration {
-                    version,
-                    encoding: _,
-                    standalone,
-                    span: _,
-                } => {
-                    if version.as_str() != "1.0" {
-                        return Err(Error::UnsupportedVersion(version.to_string()));
-                    }
-                    if let Some(standalone) = standalone {
-                        if !standalone {
-                            return Err(Error::UnsupportedNotStandalone);
-                        }
+            }
+            if let Some(resync_at) = resync_at {
+                tokenizer = Tokenizer::from_fragment(xml, resync_at..xml.len());
+                continue;
+            }
+            let Some((error, start)) = failed_at else {
+                break;
+            };
+            // we resume at the next markup, if there is any; we don't try to
+            // make sense of what comes after the document element
+            let resume_at = xml[start..]
+                .char_indices()
+                .skip(1)
+                .find(|(_, c)| *c == '<')
+                .map(|(i, _)| start + i)
+                .filter(|_| !builder.is_document_element_closed(self));
+            builder.add_diagnostic(error, Span::new(start, resume_at.unwrap_or(xml.len())));
+            match resume_at {
+                Some(resume_at) => {
+                    if let Err(error) = builder.open_pending_element(self) {
+                        builder.add_error_diagnostic(error);
+                        break;
                     }
+                    tokenizer = Tokenizer::from_fragment(xml, resume_at..xml.len());
                 }
-                DtdStart { .. } => {
-                    return Err(Error::DtdUnsupported);
-                }
-                DtdEnd { .. } => {
-                    return Err(Error::DtdUnsupported);
-                }
-                EmptyDtd { .. } => {
-                    return Err(Error::DtdUnsupported);
-                }
-                EntityDeclaration { .. } => {
-                    return Err(Error::DtdUnsupported);
-                }
+                None => break,
             }
         }
+        builder.finish_recovering(self)
+    }
 
-        if builder.is_current_node_document(self) {
-            Ok((Node::new(builder.tree), span_info))
-        } else {
-            Err(Error::UnclosedTag)
+    /// Parse a string containing an XML fragment into nodes.
+    ///
+    /// The fragment is parsed as if it were content of the `context` node:
+    /// the namespace prefixes that are in scope of `context` can be used in
+    /// the fragment. The fragment may contain any number of elements, text,
+    /// comments and processing instructions at the top level.
+    ///
+    /// This returns the top-level nodes, which are not attached to any
+    /// tree. You can then use [`Xot::append`] to add them to `context`, or
+    /// anywhere else.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse(r#"<x:doc xmlns:x="http://example.com"/>"#)?;
+    /// let doc_el = xot.document_element(root)?;
+    ///
+    /// let nodes = xot.parse_fragment(doc_el, "<x:item>one</x:item>text<x:item>two</x:item>")?;
+    /// assert_eq!(nodes.len(), 3);
+    /// for node in nodes {
+    ///     xot.append(doc_el, node)?;
+    /// }
+    /// assert_eq!(
+    ///     xot.to_string(root)?,
+    ///     r#"<x:doc xmlns:x="http://example.com"><x:item>one</x:item>text<x:item>two</x:item></x:doc>"#
+    /// );
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn parse_fragment(&mut self, context: Node, xml: &str) -> Result<Vec<Node>, Error> {
+        let namespaces = self.namespaces_in_scope(context).collect();
+        let mut builder = DocumentBuilder::new(self, ParseOptions::default(), false)?;
+        builder.name_id_builder.push(namespaces);
+        for token in Tokenizer::from_fragment(xml, 0..xml.len()) {
+            builder.token(token?, xml, self)?;
         }
+        builder.finish_fragment(xml.len(), self)
     }
 
     /// Parse a string containing XML into a node.
@@ -625,6 +1897,34 @@ impl Xot {
         self.parse_with_span_info(xml).map(|(node, _)| node)
     }
 
+    /// Parse a string containing XML into a node, with [`ParseOptions`].
+    ///
+    /// This lets you leave out whitespace-only text, comments and processing
+    /// instructions while parsing, so you don't need a separate pass over the
+    /// tree afterward.
+    ///
+    /// ```rust
+    /// use xot::{Xot, ParseOptions};
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse_with_options(
+    ///     r#"<doc> <p> </p> <pre xml:space="preserve"> </pre> </doc>"#,
+    ///     ParseOptions {
+    ///         strip_whitespace: true,
+    ///         ..Default::default()
+    ///     },
+    /// )?;
+    /// assert_eq!(
+    ///     xot.to_string(root)?,
+    ///     r#"<doc><p/><pre xml:space="preserve"> </pre></doc>"#
+    /// );
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn parse_with_options(&mut self, xml: &str, options: ParseOptions) -> Result<Node, Error> {
+        self.parse_with_span_info_and_options(xml, options)
+            .map(|(node, _)| node)
+    }
+
     /// Parse bytes containing XML into a node.
     ///
     /// This attempts to decode the data in the bytes into a Rust string
@@ -632,6 +1932,9 @@ impl Xot {
     ///
     /// If you already have a Rust string, use [`Xot::parse`].
     ///
+    /// The encoding that was used to decode the bytes is recorded in the
+    /// [`Xot::document_metadata`] of the document.
+    ///
     /// The returned node is the document node of the parsed XML document.
     ///
     /// ```rust
@@ -656,7 +1959,280 @@ impl Xot {
     /// # Ok::<(), xot::Error>(())
     /// ```
     pub fn parse_bytes(&mut self, bytes: &[u8]) -> Result<Node, Error> {
-        let xml = decode(bytes, None);
-        self.parse(&xml)
+        let (xml, encoding) = decode(bytes, None)?;
+        let document = self.parse(&xml)?;
+        self.document_metadata
+            .entry(document)
+            .or_default()
+            .detected_encoding = Some(encoding.name().to_string());
+        Ok(document)
+    }
+
+    /// Parse bytes containing XML into a node, decoding them strictly.
+    ///
+    /// [`Xot::parse_bytes`] replaces bytes that aren't valid in the detected
+    /// encoding with the replacement character. This instead fails with
+    /// [`Error::MalformedEncoding`], which gives the byte offset of the
+    /// first malformed byte sequence.
+    ///
+    /// The encoding is determined in this order:
+    ///
+    /// - A byte order mark, for UTF-8 and UTF-16 (little or big endian).
+    ///
+    /// - The `encoding` you pass in, if any. This is the place for an
+    ///   encoding you know from elsewhere, such as the `charset` of an HTTP
+    ///   `Content-Type` header.
+    ///
+    /// - UTF-16 without a byte order mark, if the document starts with `<?`
+    ///   in UTF-16.
+    ///
+    /// - The encoding declaration in the document, and UTF-8 otherwise.
+    ///
+    /// If the encoding is not known, you get [`Error::UnsupportedEncoding`].
+    /// The encoding that was used is recorded in the
+    /// [`Xot::document_metadata`] of the document.
+    ///
+    /// ```rust
+    /// use xot::{Error, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    ///
+    /// // this claims to be UTF-8, but it's Latin-1
+    /// let bytes = b"<?xml version=\"1.0\" encoding=\"UTF-8\"?><p>\xe9</p>";
+    /// let err = xot.parse_bytes_strict(bytes, None).unwrap_err();
+    /// assert!(matches!(err, Error::MalformedEncoding(_, 41)));
+    ///
+    /// // we know better
+    /// let document = xot.parse_bytes_strict(bytes, Some("ISO-8859-1"))?;
+    /// let doc_el = xot.document_element(document)?;
+    /// assert_eq!(xot.text_content_str(doc_el), Some("é"));
+    /// let metadata = xot.document_metadata(document).unwrap();
+    /// assert_eq!(metadata.detected_encoding.as_deref(), Some("windows-1252"));
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn parse_bytes_strict(
+        &mut self,
+        bytes: &[u8],
+        encoding: Option<&str>,
+    ) -> Result<Node, Error> {
+        let (xml, encoding) = decode_strict(bytes, encoding)?;
+        let document = self.parse(&xml)?;
+        self.document_metadata
+            .entry(document)
+            .or_default()
+            .detected_encoding = Some(encoding.name().to_string());
+        Ok(document)
+    }
+
+    /// Parse XML from a reader into a node.
+    ///
+    /// This reads and decodes the data incrementally, so the full source text
+    /// is never held in memory, only the tree that is built from it. The
+    /// encoding is detected in the same way as [`Xot::parse_bytes`] does, and
+    /// is recorded in the [`Xot::document_metadata`] of the document.
+    ///
+    /// Positions in errors are relative to the whole document, as with
+    /// [`Xot::parse`]. If reading fails, you get [`Error::Io`].
+    ///
+    /// The returned node is the document node of the parsed XML document.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let reader = std::io::Cursor::new(b"<p>Hello</p>");
+    /// let document = xot.parse_reader(reader)?;
+    ///
+    /// assert_eq!(xot.to_string(document)?, "<p>Hello</p>");
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn parse_reader(&mut self, mut reader: impl Read) -> Result<Node, Error> {
+        let mut parser = IncrementalParser::new(self, ParseOptions::default())?;
+        let mut buffer = vec![0; READ_BUFFER_SIZE];
+        loop {
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            parser.feed_bytes(&buffer[..read], self)?;
+        }
+        parser.finish(self)
+    }
+
+    /// Parse XML from a reader, handing off subtrees as they're completed.
+    ///
+    /// This is meant for documents that consist of a long list of records,
+    /// such as `<feed><record/>...<record/></feed>`. Like
+    /// [`Xot::parse_reader`], the data is read and parsed incrementally.
+    /// Each time an element is completed, `predicate` is called with it.
+    /// If it returns `true`, the element is handed to `f`, after which it's
+    /// removed from the tree, along with whitespace-only text that separates
+    /// it from other markup. This way you can use the full tree API on each
+    /// record, while the memory used is bounded for the document as a whole.
+    ///
+    /// Elements are handed off in the order in which they are closed, a
+    /// while after they're parsed: the data is processed in chunks, and
+    /// matching elements are handed off after each chunk. While `f` runs the
+    /// element is still in the tree, so you can look at its ancestors and
+    /// the namespace prefixes in scope. Don't change the tree outside of
+    /// the element. If you want to keep the element, detach it or move it
+    /// elsewhere; it's then not removed. The document element is never
+    /// handed off.
+    ///
+    /// Any error that `f` returns stops parsing and is returned. Otherwise
+    /// you get the document node, holding what remains of the document.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let record = xot.add_name("record");
+    ///
+    /// let xml = "<feed><record>A</record><record>B</record></feed>";
+    /// let mut texts = Vec::new();
+    /// let document = xot.parse_reader_subtrees(
+    ///     xml.as_bytes(),
+    ///     |xot, node| xot.element(node).map(|e| e.name()) == Some(record),
+    ///     |xot, node| {
+    ///         texts.push(xot.text_content_str(node).unwrap().to_string());
+    ///         Ok(())
+    ///     },
+    /// )?;
+    ///
+    /// assert_eq!(texts, vec!["A", "B"]);
+    /// assert_eq!(xot.to_string(document)?, "<feed/>");
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn parse_reader_subtrees(
+        &mut self,
+        mut reader: impl Read,
+        mut predicate: impl FnMut(&Xot, Node) -> bool,
+        mut f: impl FnMut(&mut Xot, Node) -> Result<(), Error>,
+    ) -> Result<Node, Error> {
+        let mut parser = IncrementalParser::new(self, ParseOptions::default())?;
+        parser.builder_mut().record_closed_elements();
+        let mut buffer = vec![0; READ_BUFFER_SIZE];
+        loop {
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            parser.feed_bytes(&buffer[..read], self)?;
+            self.hand_off_subtrees(&mut parser, &mut predicate, &mut f)?;
+        }
+        parser.finish_input(self)?;
+        self.hand_off_subtrees(&mut parser, &mut predicate, &mut f)?;
+        parser.finish_document(self)
+    }
+
+    fn hand_off_subtrees(
+        &mut self,
+        parser: &mut IncrementalParser,
+        predicate: &mut impl FnMut(&Xot, Node) -> bool,
+        f: &mut impl FnMut(&mut Xot, Node) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        for node_id in parser.builder_mut().take_closed_elements() {
+            let node = Node::new(node_id);
+            // a previous handler may have removed it along with an ancestor
+            if self.is_removed(node) || self.has_document_parent(node) || !predicate(self, node) {
+                continue;
+            }
+            let parent = self.parent(node);
+            f(self, node)?;
+            // if the handler moved the element, it's kept
+            if !self.is_removed(node) && self.parent(node) == parent {
+                // drop the indentation around the element too; otherwise
+                // it's consolidated into an ever growing text node. Text
+                // is only complete once markup follows it: until then more
+                // text, such as that of a CDATA section, may be added to it
+                for sibling in [self.previous_sibling(node), self.next_sibling(node)]
+                    .into_iter()
+                    .flatten()
+                {
+                    let is_complete = self
+                        .next_sibling(sibling)
+                        .is_some_and(|next| self.text_str(next).is_none());
+                    if is_complete && self.text_str(sibling).is_some_and(is_whitespace) {
+                        sibling.get().remove(self.arena_mut());
+                    }
+                }
+                self.remove(node)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a [`PushParser`], which you can give XML in chunks.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let mut parser = xot.push_parser();
+    /// parser.feed_str("<p>Hello")?;
+    /// parser.feed_str("</p>")?;
+    /// let document = parser.finish()?;
+    ///
+    /// assert_eq!(xot.to_string(document)?, "<p>Hello</p>");
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn push_parser(&mut self) -> PushParser<'_> {
+        PushParser::new(self, ParseOptions::default()).expect("default options are valid")
+    }
+
+    /// Create a [`PushParser`] with [`ParseOptions`].
+    ///
+    /// If the replacement text of one of [`ParseOptions::entities`] contains
+    /// a character that's not allowed in XML, you get
+    /// [`Error::InvalidCharacter`].
+    pub fn push_parser_with_options(
+        &mut self,
+        options: ParseOptions,
+    ) -> Result<PushParser<'_>, Error> {
+        PushParser::new(self, options)
+    }
+
+    /// Create a [`TreeBuilder`], which you can use to build a tree from
+    /// the events of another parser.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let mut builder = xot.tree_builder();
+    /// builder.start_element("", "p")?;
+    /// builder.text("Hello")?;
+    /// builder.end_element()?;
+    /// let document = builder.finish()?;
+    ///
+    /// assert_eq!(xot.to_string(document)?, "<p>Hello</p>");
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn tree_builder(&mut self) -> TreeBuilder<'_> {
+        TreeBuilder::new(self)
+    }
+
+    /// Create a [`Reader`], which yields the XML as a sequence of [`Event`]
+    /// values without building a tree.
+    ///
+    /// ```rust
+    /// use xot::{Event, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let events = xot
+    ///     .reader("<p>Hello</p>")
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(events.len(), 3);
+    /// assert_eq!(events[1], Event::Text("Hello".to_string()));
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    ///
+    /// [`Event`]: crate::Event
+    pub fn reader<'a>(&'a mut self, xml: &'a str) -> Reader<'a> {
+        Reader::new(self, xml)
     }
 }
@@ -0,0 +1,319 @@
+// A pull reader: the XML is tokenized and checked in the same way as when
+// we build a tree, but instead of creating nodes we hand out events.
+use xmlparser::{Token, Tokenizer};
+
+use crate::entity::{parse_attribute, parse_text, Entities};
+use crate::error::Error;
+use crate::id::{NameId, NamespaceId, PrefixId};
+use crate::parse::{
+    check_restricted_chars, declare_entity, declared_prefix, is_undeclaration, is_xml11,
+    AttributeBuilder, ElementBuilder, NameIdBuilder, Span,
+};
+use crate::xotdata::Xot;
+
+/// An event produced by a [`Reader`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The start of an element.
+    ElementStart {
+        /// The name of the element, with its namespace resolved.
+        name: NameId,
+        /// The namespace declarations on the element, in order. Prefix
+        /// undeclarations in XML 1.1 are not included.
+        namespaces: Vec<(PrefixId, NamespaceId)>,
+        /// The attributes of the element, in order, with their namespace
+        /// resolved and entities in their value expanded.
+        attributes: Vec<(NameId, String)>,
+    },
+    /// The end of an element. An empty element such as `<a/>` gets this
+    /// right after its start.
+    ElementEnd {
+        /// The name of the element.
+        name: NameId,
+    },
+    /// Text, with entities expanded. CDATA sections are reported as text
+    /// as well. Adjacent text isn't consolidated, so text interrupted by a
+    /// CDATA section results in multiple events.
+    Text(String),
+    /// A comment.
+    Comment(String),
+    /// A processing instruction.
+    ProcessingInstruction {
+        /// The target of the processing instruction.
+        target: NameId,
+        /// The data of the processing instruction, if any.
+        data: Option<String>,
+    },
+}
+
+/// A pull reader over XML that yields [`Event`] values without building a
+/// tree.
+///
+/// You get one with [`Xot::reader`]. This is useful if you want to scan
+/// a large document for a few values: no nodes are created, but names are
+/// interned in the [`Xot`] just as when you parse, so you can compare them
+/// with [`NameId`] values you obtained in advance. The XML is checked for
+/// well-formedness and namespace constraints in the same way as by
+/// [`Xot::parse`], and entities declared in the internal DTD subset are
+/// expanded.
+///
+/// Once an error is produced, the reader yields no further events.
+///
+/// ```rust
+/// use xot::{Event, Xot};
+///
+/// let mut xot = Xot::new();
+/// let item = xot.add_name("item");
+/// let id = xot.add_name("id");
+///
+/// let mut ids = Vec::new();
+/// for event in xot.reader(r#"<list><item id="a"/><other/><item id="b"/></list>"#) {
+///     if let Event::ElementStart { name, attributes, .. } = event? {
+///         if name == item {
+///             ids.extend(
+///                 attributes
+///                     .into_iter()
+///                     .filter(|(name, _)| *name == id)
+///                     .map(|(_, value)| value),
+///             );
+///         }
+///     }
+/// }
+/// assert_eq!(ids, vec!["a", "b"]);
+/// # Ok::<(), xot::Error>(())
+/// ```
+pub struct Reader<'a> {
+    xot: &'a mut Xot,
+    tokenizer: Tokenizer<'a>,
+    name_id_builder: NameIdBuilder,
+    entities: Entities,
+    xml11: bool,
+    // the start tag we're reading
+    element_builder: Option<ElementBuilder>,
+    // the names and spans of the open elements
+    open_elements: Vec<(NameId, Span)>,
+    // the end of an empty element, which we report after its start
+    pending_end: Option<NameId>,
+    done: bool,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(xot: &'a mut Xot, xml: &'a str) -> Self {
+        let mut name_id_builder = NameIdBuilder::new(xot.base_prefixes().into_iter().collect());
+        name_id_builder.push(vec![(xot.empty_prefix_id, xot.no_namespace_id)]);
+        Self {
+            xot,
+            tokenizer: Tokenizer::from(xml),
+            name_id_builder,
+            entities: Entities::new(),
+            xml11: false,
+            element_builder: None,
+            open_elements: Vec::new(),
+            pending_end: None,
+            done: false,
+        }
+    }
+
+    /// Access the [`Xot`] while reading, for instance to look up the names
+    /// in the events.
+    ///
+    /// ```rust
+    /// use xot::{Event, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let mut reader = xot.reader("<doc/>");
+    /// let event = reader.next().unwrap()?;
+    /// if let Event::ElementStart { name, .. } = event {
+    ///     assert_eq!(reader.xot().local_name_str(name), "doc");
+    /// }
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn xot(&self) -> &Xot {
+        self.xot
+    }
+
+    fn token(&mut self, token: Token<'_>) -> Result<Option<Event>, Error> {
+        if self.xml11 {
+            check_restricted_chars(&token, 0)?;
+        }
+        match token {
+            Token::Declaration { version, .. } => {
+                self.xml11 = is_xml11(version, 0)?;
+                self.entities.set_xml11(self.xml11);
+            }
+            Token::EntityDeclaration {
+                name,
+                definition,
+                span,
+            } => {
+                declare_entity(&mut self.entities, name.as_str(), definition, span, 0)?;
+            }
+            Token::DtdStart { .. } | Token::DtdEnd { .. } | Token::EmptyDtd { .. } => {}
+            Token::ElementStart { prefix, local, .. } => {
+                self.element_builder = Some(ElementBuilder::new(prefix, local, 0));
+            }
+            Token::Attribute {
+                prefix,
+                local,
+                value,
+                ..
+            } => {
+                let name_span = Span::from_prefix_name(prefix, local, 0);
+                let value_span = Span::from(value);
+                let element_builder = self.element_builder.as_mut().unwrap();
+                if let Some(declared) = declared_prefix(prefix.as_str(), local.as_str()) {
+                    element_builder.declare_prefix(
+                        declared,
+                        value.as_str(),
+                        Some(name_span),
+                        Some(value_span),
+                        self.xml11,
+                        self.xot,
+                    )?;
+                } else {
+                    element_builder.check_duplicate_attribute(
+                        prefix.as_str(),
+                        local.as_str(),
+                        name_span,
+                    )?;
+                    let value = parse_attribute(
+                        value.as_str().into(),
+                        &self.entities,
+                        value.start(),
+                        None,
+                    )?;
+                    element_builder.attributes.push(AttributeBuilder {
+                        prefix: prefix.to_string(),
+                        name: local.to_string(),
+                        value: value.into_owned(),
+                        name_span,
+                        value_span,
+                    });
+                }
+            }
+            Token::ElementEnd { end, span } => match end {
+                xmlparser::ElementEnd::Open => return self.element_start().map(Some),
+                xmlparser::ElementEnd::Empty => {
+                    let event = self.element_start()?;
+                    self.pending_end = Some(self.element_end());
+                    return Ok(Some(event));
+                }
+                xmlparser::ElementEnd::Close(prefix, local) => {
+                    let span = Span::from(span);
+                    let name = self.name_id_builder.element_name_id(
+                        prefix.as_str(),
+                        local.as_str(),
+                        span,
+                        self.xot,
+                    )?;
+                    if self.open_elements.last().map(|(open, _)| *open) != Some(name) {
+                        return Err(Error::InvalidCloseTag(
+                            prefix.to_string(),
+                            local.to_string(),
+                            span,
+                        ));
+                    }
+                    return Ok(Some(Event::ElementEnd {
+                        name: self.element_end(),
+                    }));
+                }
+            },
+            Token::Text { text } => {
+                let text = parse_text(text.as_str().into(), &self.entities, text.start(), None)?;
+                return Ok(Some(Event::Text(text.into_owned())));
+            }
+            Token::Cdata { text, .. } => {
+                return Ok(Some(Event::Text(text.to_string())));
+            }
+            Token::Comment { text, .. } => {
+                return Ok(Some(Event::Comment(text.to_string())));
+            }
+            Token::ProcessingInstruction {
+                target, content, ..
+            } => {
+                return Ok(Some(Event::ProcessingInstruction {
+                    target: self.xot.add_name(target.as_str()),
+                    data: content.map(|content| content.to_string()),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    fn element_start(&mut self) -> Result<Event, Error> {
+        let element_builder = self.element_builder.take().unwrap();
+        self.name_id_builder
+            .push(element_builder.namespaces.clone());
+        let name = self.name_id_builder.element_name_id(
+            &element_builder.prefix,
+            &element_builder.name,
+            element_builder.span,
+            self.xot,
+        )?;
+        self.open_elements.push((name, element_builder.span));
+        let mut attributes: Vec<(NameId, String)> = Vec::new();
+        for attribute_builder in element_builder.attributes {
+            let name = self.name_id_builder.attribute_name_id(
+                &attribute_builder.prefix,
+                &attribute_builder.name,
+                attribute_builder.name_span,
+                self.xot,
+            )?;
+            attribute_builder.check_expanded_name(name, attributes.iter().map(|(id, _)| *id))?;
+            attributes.push((name, attribute_builder.value));
+        }
+        let namespaces = element_builder
+            .namespaces
+            .into_iter()
+            .filter(|(prefix_id, namespace_id)| {
+                !is_undeclaration(*prefix_id, *namespace_id, self.xot)
+            })
+            .collect();
+        Ok(Event::ElementStart {
+            name,
+            namespaces,
+            attributes,
+        })
+    }
+
+    fn element_end(&mut self) -> NameId {
+        self.name_id_builder.pop();
+        let (name, _) = self.open_elements.pop().unwrap();
+        name
+    }
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = Result<Event, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if let Some(name) = self.pending_end.take() {
+            return Some(Ok(Event::ElementEnd { name }));
+        }
+        loop {
+            let result = match self.tokenizer.next() {
+                Some(Ok(token)) => self.token(token),
+                Some(Err(error)) => Err(error.into()),
+                None => {
+                    self.done = true;
+                    // the span of the innermost unclosed element
+                    return self
+                        .open_elements
+                        .last()
+                        .map(|(_, span)| Err(Error::UnclosedTag(*span)));
+                }
+            };
+            match result {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => {}
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+}
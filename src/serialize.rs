@@ -1,14 +1,16 @@
 use std::io::Write;
 
+use crate::encoding::OutputEncoding;
 use crate::error::Error;
+use crate::output::xml::Version;
 use crate::output::{
-    gen_outputs, Html5Elements, Html5Serializer, Output, OutputToken, TokenSerializeParameters,
-    XmlSerializer,
+    gen_outputs, C14nSerializer, Html5Elements, Html5Serializer, Output, OutputToken,
+    TokenSerializeParameters, XmlSerializer,
 };
+use crate::output::{AttributeWrapper, Pretty, PrettyOutputToken};
 use crate::output::{NoopNormalizer, Normalizer};
-use crate::output::{Pretty, PrettyOutputToken};
 use crate::xmlname::NameStrInfo;
-use crate::{output, NameId, Value};
+use crate::{output, Value};
 
 use crate::xotdata::{Node, Xot};
 
@@ -82,11 +84,11 @@ impl<'a> Html5<'a> {
             self.xot,
             &self.html5_elements,
             node,
-            &parameters.cdata_section_elements,
+            &parameters,
             normalizer,
         );
-        if let Some(indentation) = parameters.indentation {
-            serializer.serialize_pretty(w, outputs, &indentation.suppress)?;
+        if let Some(indentation) = &parameters.indentation {
+            serializer.serialize_pretty(w, outputs, indentation)?;
         } else {
             serializer.serialize(w, outputs)?;
         }
@@ -258,9 +260,13 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
         node: Node,
         normalizer: N,
     ) -> Result<String, Error> {
+        let encoding = output_encoding(&parameters)?;
         let mut buf = Vec::new();
-        self.serialize_xml_write_with_normalizer(parameters, node, &mut buf, normalizer)?;
-        Ok(String::from_utf8(buf).unwrap())
+        self.serialize_xml_encoded(parameters, node, &mut buf, normalizer, encoding)?;
+        Ok(match encoding {
+            Some(encoding) => encoding.decode(&buf),
+            None => String::from_utf8(buf).unwrap(),
+        })
     }
 
     /// Serialize to XML via a [`Write`], with options.
@@ -287,8 +293,26 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
         w: &mut impl Write,
         normalizer: N,
     ) -> Result<(), Error> {
-        if let Some(declaration) = parameters.declaration {
-            declaration.serialize(w)?;
+        let encoding = output_encoding(&parameters)?;
+        self.serialize_xml_encoded(parameters, node, w, normalizer, encoding)
+    }
+
+    fn serialize_xml_encoded<N: Normalizer>(
+        &self,
+        parameters: output::xml::Parameters,
+        node: Node,
+        w: &mut impl Write,
+        normalizer: N,
+        encoding: Option<OutputEncoding>,
+    ) -> Result<(), Error> {
+        // the declaration and doctype go through the encoding too
+        let mut prolog = Vec::new();
+        let declaration = parameters.declaration.or_else(|| {
+            self.document_metadata(node)
+                .and_then(|metadata| metadata.declaration())
+        });
+        if let Some(declaration) = &declaration {
+            declaration.serialize(&mut prolog)?;
         }
         if let Some(doctype) = parameters.doctype {
             // if we are in a document node, we look for the document_element,
@@ -302,7 +326,14 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
             // know it's an element now
             let name = self.node_name_ref(node)?.unwrap();
             let name = name.full_name();
-            doctype.serialize(name.as_ref(), w)?;
+            doctype.serialize(name.as_ref(), &mut prolog)?;
+        } else if let Some(document_type) = self.document_type(node) {
+            document_type.serialize(&mut prolog)?;
+        }
+        if let Some(encoding) = encoding {
+            encoding.write(w, &String::from_utf8(prolog).unwrap())?;
+        } else {
+            w.write_all(&prolog)?;
         }
         let outputs = gen_outputs(self, node);
         let mut serializer = XmlSerializer::new(
@@ -311,17 +342,84 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
             TokenSerializeParameters {
                 cdata_section_elements: parameters.cdata_section_elements,
                 unescaped_gt: parameters.unescaped_gt,
+                character_map: parameters.character_map,
+                attribute_order: parameters.attribute_order,
+                namespace_order: parameters.namespace_order,
             },
+            declaration.map_or(Version::Xml10, |declaration| declaration.version),
+            encoding,
             normalizer,
         );
         if let Some(indentation) = parameters.indentation {
-            serializer.serialize_pretty(w, outputs, &indentation.suppress)?;
+            serializer.serialize_pretty(w, outputs, &indentation)?;
         } else {
             serializer.serialize(w, outputs)?;
         }
         Ok(())
     }
 
+    /// Serialize a node as Canonical XML.
+    ///
+    /// See [`output::c14n`] for the canonicalization methods. Canonical XML
+    /// is always UTF-8 and has neither XML declaration nor doctype; the
+    /// serialization parameters of [`Xot::serialize_xml_string`] don't
+    /// apply to it.
+    ///
+    /// ```rust
+    /// use xot::{Xot, output};
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse(r#"<a xmlns:x="http://example.com/x" xmlns:y="http://example.com/y"><x:b b="2" a="1"/></a>"#)?;
+    /// let a = xot.document_element(root)?;
+    /// let b = xot.first_child(a).unwrap();
+    ///
+    /// let c14n = xot.serialize_c14n_string(output::c14n::Parameters::default(), b)?;
+    /// assert_eq!(
+    ///     c14n,
+    ///     r#"<x:b xmlns:x="http://example.com/x" xmlns:y="http://example.com/y" a="1" b="2"></x:b>"#
+    /// );
+    ///
+    /// let c14n = xot.serialize_c14n_string(output::c14n::Parameters {
+    ///     method: output::c14n::Method::Exclusive { inclusive_prefixes: vec![] },
+    ///     ..Default::default()
+    /// }, b)?;
+    /// assert_eq!(
+    ///     c14n,
+    ///     r#"<x:b xmlns:x="http://example.com/x" a="1" b="2"></x:b>"#
+    /// );
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn serialize_c14n_string(
+        &self,
+        parameters: output::c14n::Parameters,
+        node: Node,
+    ) -> Result<String, Error> {
+        let mut buf = Vec::new();
+        self.serialize_c14n_write(parameters, node, &mut buf)?;
+        Ok(String::from_utf8(buf).unwrap())
+    }
+
+    /// Write a node as Canonical XML via a [`Write`].
+    ///
+    /// This is like [`Xot::serialize_c14n_string`] but writes to a [`Write`].
+    pub fn serialize_c14n_write(
+        &self,
+        parameters: output::c14n::Parameters,
+        node: Node,
+        w: &mut impl Write,
+    ) -> Result<(), Error> {
+        let outputs = gen_outputs(self, node);
+        let mut serializer = C14nSerializer::new(self, node, &parameters);
+        serializer.serialize(w, outputs)
+    }
+
+    // The XML version declared by a document node, if it has metadata.
+    fn document_version(&self, node: Node) -> Version {
+        self.document_metadata(node)
+            .and_then(|metadata| metadata.declaration())
+            .map_or(Version::Xml10, |declaration| declaration.version)
+    }
+
     /// Get HTML 5 serialization API.
     ///
     /// This is a mutable calls as it needs to create a lot of new HTML names
@@ -371,10 +469,23 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
         normalizer: N,
     ) -> impl Iterator<Item = (Node, Output, OutputToken)> + 'a {
         let outputs = gen_outputs(self, node);
-        let mut serializer = XmlSerializer::new(self, node, parameters, normalizer);
-        outputs.map(move |(node, output)| {
-            let rendered = serializer.render_output(node, &output).unwrap();
-            (node, output, rendered)
+        let mut serializer = XmlSerializer::new(
+            self,
+            node,
+            parameters,
+            self.document_version(node),
+            None,
+            normalizer,
+        );
+        outputs.flat_map(move |(node, output)| {
+            let ordered = serializer.order(node, output).unwrap();
+            ordered
+                .into_iter()
+                .map(|(node, output)| {
+                    let rendered = serializer.render_output(node, &output).unwrap();
+                    (node, output, rendered)
+                })
+                .collect::<Vec<_>>()
         })
     }
 
@@ -388,8 +499,11 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
     /// instance by specifying elements that should be serialized as a CDATA
     /// section.
     ///
-    /// You can also include a list of elements names that are excluded from
-    /// indentation.
+    /// In `indentation` you control the pretty printing: elements that are
+    /// excluded from indentation, and when attributes are wrapped. The
+    /// `indentation` of a token is a level; you get the indentation to write
+    /// by repeating [`Indentation::indent`](output::Indentation::indent) that
+    /// many times.
     ///
     /// You can also pass in a normalizer; if you don't care about
     /// normalization, use
@@ -398,29 +512,46 @@ assert_eq!(s, "<doc>\u{1E0D}\u{0307}</doc>");
         &'a self,
         node: Node,
         parameters: TokenSerializeParameters,
-        suppress_elements: &'a [NameId],
+        indentation: &'a output::Indentation,
         normalizer: N,
     ) -> impl Iterator<Item = (Node, Output, PrettyOutputToken)> + 'a {
         let outputs = gen_outputs(self, node);
-        let mut serializer = XmlSerializer::new(self, node, parameters, normalizer);
+        let mut serializer = XmlSerializer::new(
+            self,
+            node,
+            parameters,
+            self.document_version(node),
+            None,
+            normalizer,
+        );
         let mut pretty = Pretty::new(
             self,
-            |name| suppress_elements.contains(&name),
+            |name| indentation.suppress.contains(&name),
             |_name| false,
         );
-        outputs.map(move |(node, output)| {
-            let (indentation, newline) = pretty.prettify(node, &output);
-            let rendered = serializer.render_output(node, &output).unwrap();
-            (
-                node,
-                output,
-                PrettyOutputToken {
-                    text: rendered.text,
-                    space: rendered.space,
-                    indentation,
-                    newline,
-                },
-            )
+        let mut wrapper = AttributeWrapper::new(indentation);
+        outputs.flat_map(move |(node, output)| {
+            let ordered = serializer.order(node, output).unwrap();
+            let mut tokens = Vec::new();
+            for (node, output) in ordered {
+                let token = serializer
+                    .render_pretty_output(&mut pretty, node, &output)
+                    .unwrap();
+                tokens.extend(wrapper.push(node, output, token));
+            }
+            tokens
         })
     }
 }
+
+// The encoding given in the XML declaration we write, if it's not UTF-8.
+fn output_encoding(parameters: &output::xml::Parameters) -> Result<Option<OutputEncoding>, Error> {
+    match parameters
+        .declaration
+        .as_ref()
+        .and_then(|declaration| declaration.encoding.as_deref())
+    {
+        Some(label) => OutputEncoding::for_label(label),
+        None => Ok(None),
+    }
+}
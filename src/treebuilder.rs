@@ -0,0 +1,193 @@
+// A tree builder for other parsers: the same document builder we use when
+// we parse, driven by method calls instead of tokens.
+use crate::error::Error;
+use crate::parse::{DocumentBuilder, ParseOptions};
+use crate::unpretty::is_whitespace;
+use crate::validate::{
+    validate_chars, validate_comment, validate_name, validate_prefix,
+    validate_processing_instruction_data, validate_processing_instruction_target,
+};
+use crate::xotdata::{Node, Xot};
+
+/// Builds a Xot tree from events, so you can use another parser or
+/// tokenizer to create a tree.
+///
+/// You get one with [`Xot::tree_builder`]. You give it the start of an
+/// element with its prefix and local name as they appear in the source,
+/// followed by its namespace declarations and attributes, then its content,
+/// and then the end of the element. Namespace prefixes are resolved with the
+/// declarations in scope, and adjacent text is consolidated, the same way as
+/// when Xot parses XML. When you're done, [`TreeBuilder::finish`] returns
+/// the document node.
+///
+/// Text and attribute values are taken literally: entities in them are not
+/// expanded, as the source is expected to have done that already. Whitespace
+/// outside of the document element is ignored.
+///
+/// Names and content are checked the way the parser would: you get
+/// [`Error::InvalidName`] or [`Error::InvalidPrefix`] for a malformed name,
+/// [`Error::InvalidCharacter`] for a character that's not allowed in XML,
+/// and [`Error::InvalidComment`], [`Error::InvalidTarget`] or
+/// [`Error::InvalidProcessingInstructionData`] for a comment or processing
+/// instruction that can't be serialized. Errors about names are the same as
+/// those of the parser, such as [`Error::UnknownPrefix`], except that they
+/// have no span, as the builder doesn't have a source text. An event that's
+/// out of place, such as an attribute outside of a start tag, results in
+/// [`Error::InvalidOperation`].
+///
+/// ```rust
+/// use xot::Xot;
+///
+/// let mut xot = Xot::new();
+/// let mut builder = xot.tree_builder();
+/// builder.start_element("x", "doc")?;
+/// builder.namespace("x", "http://example.com")?;
+/// builder.attribute("", "a", "1 & 2")?;
+/// builder.text("Hello")?;
+/// builder.start_element("x", "p")?;
+/// builder.end_element()?;
+/// builder.text(" world")?;
+/// builder.end_element()?;
+/// let document = builder.finish()?;
+///
+/// assert_eq!(
+///     xot.to_string(document)?,
+///     r#"<x:doc xmlns:x="http://example.com" a="1 &amp; 2">Hello<x:p/> world</x:doc>"#
+/// );
+/// # Ok::<(), xot::Error>(())
+/// ```
+pub struct TreeBuilder<'a> {
+    xot: &'a mut Xot,
+    builder: DocumentBuilder,
+}
+
+impl<'a> TreeBuilder<'a> {
+    pub(crate) fn new(xot: &'a mut Xot) -> Self {
+        let builder = DocumentBuilder::new(xot, ParseOptions::default(), false)
+            .expect("default options are valid");
+        Self { xot, builder }
+    }
+
+    /// Start an element. The prefix is empty if the element name has none.
+    ///
+    /// This is followed by the namespace declarations and attributes of the
+    /// element, if any.
+    pub fn start_element(&mut self, prefix: &str, local_name: &str) -> Result<(), Error> {
+        self.open_pending_element()?;
+        if self.builder.is_document_element_closed(self.xot) {
+            return Err(Error::InvalidOperation(
+                "Cannot add a second document element".to_string(),
+            ));
+        }
+        validate_prefix(prefix)?;
+        validate_name(local_name)?;
+        self.builder.element(prefix.into(), local_name.into());
+        Ok(())
+    }
+
+    /// Declare a namespace prefix on the element that was just started, as
+    /// with `xmlns:prefix="uri"`. Use the empty prefix to declare the
+    /// default namespace, as with `xmlns="uri"`.
+    pub fn namespace(&mut self, prefix: &str, namespace_uri: &str) -> Result<(), Error> {
+        self.check_start_tag()?;
+        validate_prefix(prefix)?;
+        validate_chars(namespace_uri)?;
+        self.builder
+            .prefix(prefix, namespace_uri, None, None, self.xot)
+    }
+
+    /// Add an attribute to the element that was just started. The prefix
+    /// is empty if the attribute name has none, in which case the attribute
+    /// is in no namespace.
+    pub fn attribute(&mut self, prefix: &str, local_name: &str, value: &str) -> Result<(), Error> {
+        self.check_start_tag()?;
+        validate_prefix(prefix)?;
+        validate_name(local_name)?;
+        validate_chars(value)?;
+        self.builder
+            .attribute(prefix.into(), local_name.into(), value.into(), true)
+            .map_err(Error::without_span)
+    }
+
+    /// Add text.
+    pub fn text(&mut self, text: &str) -> Result<(), Error> {
+        validate_chars(text)?;
+        self.open_pending_element()?;
+        if self.builder.is_current_node_document(self.xot) {
+            if is_whitespace(text) {
+                return Ok(());
+            }
+            return Err(Error::InvalidOperation(
+                "Cannot add text outside of the document element".to_string(),
+            ));
+        }
+        self.builder.literal_text(text, false, self.xot);
+        Ok(())
+    }
+
+    /// Add a comment.
+    pub fn comment(&mut self, text: &str) -> Result<(), Error> {
+        validate_comment(text)?;
+        self.open_pending_element()?;
+        self.builder.comment(text, self.xot)?;
+        Ok(())
+    }
+
+    /// Add a processing instruction.
+    pub fn processing_instruction(
+        &mut self,
+        target: &str,
+        data: Option<&str>,
+    ) -> Result<(), Error> {
+        validate_processing_instruction_target(target)?;
+        if let Some(data) = data {
+            validate_processing_instruction_data(data)?;
+        }
+        self.open_pending_element()?;
+        self.builder
+            .processing_instruction(target, data, self.xot)?;
+        Ok(())
+    }
+
+    /// End the current element.
+    pub fn end_element(&mut self) -> Result<(), Error> {
+        self.open_pending_element()?;
+        if self.builder.is_current_node_document(self.xot) {
+            return Err(Error::InvalidOperation("No element to end".to_string()));
+        }
+        self.builder.close_element_immediate(self.xot);
+        Ok(())
+    }
+
+    /// Finish building, and get the document node.
+    ///
+    /// All elements must have been ended.
+    pub fn finish(mut self) -> Result<Node, Error> {
+        self.open_pending_element()?;
+        if !self.builder.is_current_node_document(self.xot) {
+            return Err(Error::InvalidOperation(
+                "Cannot finish with elements that aren't ended".to_string(),
+            ));
+        }
+        let (document, _) = self.builder.finish(0, self.xot)?;
+        Ok(document)
+    }
+
+    // Names are resolved once the start tag is complete, so that's where
+    // errors about them show up.
+    fn open_pending_element(&mut self) -> Result<(), Error> {
+        self.builder
+            .open_pending_element(self.xot)
+            .map_err(Error::without_span)
+    }
+
+    fn check_start_tag(&self) -> Result<(), Error> {
+        if self.builder.is_element_pending() {
+            Ok(())
+        } else {
+            Err(Error::InvalidOperation(
+                "Namespaces and attributes can only follow the start of an element".to_string(),
+            ))
+        }
+    }
+}
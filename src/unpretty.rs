@@ -12,7 +12,7 @@ pub(crate) fn remove_insignificant_whitespace(xot: &mut Xot, node: Node) {
     }
 }
 
-fn is_whitespace(text: &str) -> bool {
+pub(crate) fn is_whitespace(text: &str) -> bool {
     text.chars().all(|c| c.is_whitespace())
 }
 
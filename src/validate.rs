@@ -0,0 +1,130 @@
+// Validation of names and content against the productions in the XML and
+// Namespaces in XML specifications.
+use xmlparser::XmlCharExt;
+
+use crate::error::Error;
+
+// https://www.w3.org/TR/xml-names/#NT-NCName
+pub(crate) fn is_ncname(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c != ':' && c.is_xml_name_start() => {}
+        _ => return false,
+    }
+    chars.all(|c| c != ':' && c.is_xml_name())
+}
+
+// https://www.w3.org/TR/xml-names/#NT-QName
+pub(crate) fn is_qname(s: &str) -> bool {
+    match s.split_once(':') {
+        Some((prefix, local_name)) => is_ncname(prefix) && is_ncname(local_name),
+        None => is_ncname(s),
+    }
+}
+
+pub(crate) fn validate_name(name: &str) -> Result<(), Error> {
+    if is_ncname(name) {
+        Ok(())
+    } else {
+        Err(Error::InvalidName(name.to_string()))
+    }
+}
+
+pub(crate) fn validate_qname(name: &str) -> Result<(), Error> {
+    if is_qname(name) {
+        Ok(())
+    } else {
+        Err(Error::InvalidName(name.to_string()))
+    }
+}
+
+// the empty prefix is allowed, as it's the prefix of the default namespace
+pub(crate) fn validate_prefix(prefix: &str) -> Result<(), Error> {
+    if prefix.is_empty() || is_ncname(prefix) {
+        Ok(())
+    } else {
+        Err(Error::InvalidPrefix(prefix.to_string()))
+    }
+}
+
+// https://www.w3.org/TR/xml/#NT-Char
+pub(crate) fn is_xml_char(c: char) -> bool {
+    c.is_xml_char()
+}
+
+// Characters that XML 1.1 allows only as character references.
+// https://www.w3.org/TR/xml11/#NT-RestrictedChar
+pub(crate) fn is_restricted_char(c: char) -> bool {
+    matches!(c, '\u{1}'..='\u{8}' | '\u{B}'..='\u{C}' | '\u{E}'..='\u{1F}' | '\u{7F}'..='\u{84}' | '\u{86}'..='\u{9F}')
+}
+
+pub(crate) fn validate_chars(s: &str) -> Result<(), Error> {
+    match s.chars().find(|c| !c.is_xml_char()) {
+        Some(c) => Err(Error::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+// https://www.w3.org/TR/xml/#NT-PI
+pub(crate) fn validate_processing_instruction_data(data: &str) -> Result<(), Error> {
+    validate_chars(data)?;
+    if data.contains("?>") {
+        return Err(Error::InvalidProcessingInstructionData(data.to_string()));
+    }
+    Ok(())
+}
+
+// https://www.w3.org/TR/xml/#NT-Comment
+pub(crate) fn validate_comment(text: &str) -> Result<(), Error> {
+    validate_chars(text)?;
+    if text.contains("--") || text.ends_with('-') {
+        return Err(Error::InvalidComment(text.to_string()));
+    }
+    Ok(())
+}
+
+// https://www.w3.org/TR/xml/#NT-PITarget; with namespaces it can't contain
+// a colon either
+pub(crate) fn validate_processing_instruction_target(target: &str) -> Result<(), Error> {
+    validate_name(target)?;
+    if target.eq_ignore_ascii_case("xml") {
+        return Err(Error::InvalidTarget(target.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ncname() {
+        assert!(is_ncname("a"));
+        assert!(is_ncname("_a-b.c1"));
+        assert!(is_ncname("é"));
+        assert!(!is_ncname(""));
+        assert!(!is_ncname("1a"));
+        assert!(!is_ncname("-a"));
+        assert!(!is_ncname("a b"));
+        assert!(!is_ncname("a:b"));
+    }
+
+    #[test]
+    fn test_qname() {
+        assert!(is_qname("a"));
+        assert!(is_qname("a:b"));
+        assert!(!is_qname(":b"));
+        assert!(!is_qname("a:"));
+        assert!(!is_qname("a:b:c"));
+    }
+
+    #[test]
+    fn test_chars() {
+        assert!(validate_chars("a\tb\nc\r").is_ok());
+        assert!(matches!(
+            validate_chars("a\u{1}b"),
+            Err(Error::InvalidCharacter('\u{1}'))
+        ));
+        assert!(validate_chars("\u{FFFE}").is_err());
+    }
+}
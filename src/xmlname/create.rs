@@ -1,6 +1,7 @@
 use crate::{id::NameId, Error, NamespaceId, PrefixId, Xot};
 
 use super::owned::parse_full_name;
+use crate::validate::validate_qname;
 
 /// This is a convenient and efficient way to create a new name for use in Xot.
 ///
@@ -45,8 +46,8 @@ impl CreateName {
         local_name: &str,
         lookup_namespace: impl Fn(&str) -> Option<NamespaceId>,
     ) -> Result<Self, Error> {
-        let namespace =
-            lookup_namespace(prefix).ok_or_else(|| Error::UnknownPrefix(prefix.to_string()))?;
+        let namespace = lookup_namespace(prefix)
+            .ok_or_else(|| Error::UnknownPrefix(prefix.to_string(), None))?;
         let name_id = xot.add_name_ns(local_name, namespace);
         Ok(Self { name_id })
     }
@@ -63,6 +64,20 @@ impl CreateName {
         Self::prefixed(xot, prefix, local_name, lookup_namespace)
     }
 
+    /// Parse a fullname (with potentially a prefix) and construct a name,
+    /// checking that it's valid.
+    ///
+    /// Like [`CreateName::parse_full_name`], but if the full name is not a
+    /// `QName` you get [`Error::InvalidName`].
+    pub fn parse_full_name_checked(
+        xot: &mut Xot,
+        full_name: &str,
+        lookup_namespace: impl Fn(&str) -> Option<NamespaceId>,
+    ) -> Result<Self, Error> {
+        validate_qname(full_name)?;
+        Self::parse_full_name(xot, full_name, lookup_namespace)
+    }
+
     /// The created name id.
     ///
     /// Note that you can also use `create.into()` to convert to a `NameId`.
@@ -96,8 +96,8 @@ impl OwnedName {
         local_name: &str,
         lookup_namespace: impl Fn(&str) -> Option<String>,
     ) -> Result<Self, Error> {
-        let namespace =
-            lookup_namespace(prefix).ok_or_else(|| Error::UnknownPrefix(prefix.to_string()))?;
+        let namespace = lookup_namespace(prefix)
+            .ok_or_else(|| Error::UnknownPrefix(prefix.to_string(), None))?;
         Ok(Self {
             local_name_str: local_name.to_string(),
             namespace_str: namespace,
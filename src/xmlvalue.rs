@@ -4,6 +4,7 @@ use ahash::AHashMap;
 
 use crate::error::Error;
 use crate::id::{NameId, NamespaceId, PrefixId};
+use crate::validate::{validate_chars, validate_processing_instruction_data};
 
 /// The type of the XML node.
 ///
@@ -155,11 +156,16 @@ impl Element {
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Text {
     pub(crate) text: String,
+    pub(crate) cdata: bool,
 }
 
 impl Text {
     pub(crate) fn new(text: String) -> Self {
-        Text { text }
+        Text { text, cdata: false }
+    }
+
+    pub(crate) fn new_cdata(text: String) -> Self {
+        Text { text, cdata: true }
     }
 
     /// Get the text value.
@@ -194,6 +200,84 @@ impl Text {
     pub fn set<S: Into<String>>(&mut self, text: S) {
         self.text = text.into();
     }
+
+    /// Set the text value, checking that it's valid.
+    ///
+    /// Like [`Text::set`], but if the text contains a character that's not
+    /// allowed in XML you get [`Error::InvalidCharacter`], and the text is
+    /// left unchanged.
+    ///
+    /// ```rust
+    /// use xot::{Error, Xot};
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse(r#"<doc>Example</doc>"#)?;
+    /// let doc_el = xot.document_element(root).unwrap();
+    /// let text_node = xot.first_child(doc_el).unwrap();
+    ///
+    /// let text = xot.text_mut(text_node).unwrap();
+    /// assert!(text.set_checked("a\u{0}b").is_err());
+    ///
+    /// assert_eq!(xot.to_string(root).unwrap(), r#"<doc>Example</doc>"#);
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn set_checked<S: Into<String>>(&mut self, text: S) -> Result<(), Error> {
+        let text = text.into();
+        validate_chars(&text)?;
+        self.text = text;
+        Ok(())
+    }
+
+    /// Whether this text is a CDATA section.
+    ///
+    /// Text parsed from a CDATA section is only marked as such if you parse
+    /// with [`ParseOptions::cdata_boundaries`](crate::ParseOptions::cdata_boundaries).
+    /// Otherwise CDATA sections become ordinary text.
+    ///
+    /// ```rust
+    /// use xot::{Xot, ParseOptions};
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse_with_options(
+    ///     "<doc>a &lt; b<![CDATA[c < d]]></doc>",
+    ///     ParseOptions {
+    ///         cdata_boundaries: true,
+    ///         ..Default::default()
+    ///     },
+    /// )?;
+    /// let doc_el = xot.document_element(root)?;
+    /// let text = xot.text(xot.first_child(doc_el).unwrap()).unwrap();
+    /// assert!(!text.is_cdata());
+    /// let cdata = xot.text(xot.last_child(doc_el).unwrap()).unwrap();
+    /// assert!(cdata.is_cdata());
+    ///
+    /// assert_eq!(xot.to_string(root)?, "<doc>a &lt; b<![CDATA[c < d]]></doc>");
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn is_cdata(&self) -> bool {
+        self.cdata
+    }
+
+    /// Set whether this text is a CDATA section.
+    ///
+    /// CDATA text is serialized as a CDATA section by the XML serializer.
+    ///
+    /// ```rust
+    /// use xot::Xot;
+    ///
+    /// let mut xot = Xot::new();
+    /// let root = xot.parse(r#"<doc>a &amp; b</doc>"#)?;
+    /// let doc_el = xot.document_element(root).unwrap();
+    /// let text_node = xot.first_child(doc_el).unwrap();
+    ///
+    /// xot.text_mut(text_node).unwrap().set_cdata(true);
+    ///
+    /// assert_eq!(xot.to_string(root)?, r#"<doc><![CDATA[a & b]]></doc>"#);
+    /// # Ok::<(), xot::Error>(())
+    /// ```
+    pub fn set_cdata(&mut self, cdata: bool) {
+        self.cdata = cdata;
+    }
 }
 
 /// XML comment.
@@ -259,9 +343,10 @@ impl ProcessingInstruction {
     }
 
     /// Set data.
+    ///
+    /// This doesn't check the data; see
+    /// [`ProcessingInstruction::set_data_checked`].
     pub fn set_data<S: Into<String>>(&mut self, data: Option<S>) {
-        // XXX Ideally check that data follows XML spec, i.e. not contain
-        // "?>".
         if let Some(data) = data {
             let data = data.into();
             if !data.is_empty() {
@@ -271,6 +356,21 @@ impl ProcessingInstruction {
         }
         self.data = None;
     }
+
+    /// Set data, checking that it's valid.
+    ///
+    /// If the data contains `?>` you get
+    /// [`Error::InvalidProcessingInstructionData`], and if it contains a
+    /// character that's not allowed in XML you get
+    /// [`Error::InvalidCharacter`]. The data is left unchanged then.
+    pub fn set_data_checked<S: Into<String>>(&mut self, data: Option<S>) -> Result<(), Error> {
+        let data = data.map(|data| data.into());
+        if let Some(data) = &data {
+            validate_processing_instruction_data(data)?;
+        }
+        self.set_data(data);
+        Ok(())
+    }
 }
 
 /// Represents a namespace node.
@@ -319,6 +419,18 @@ impl Attribute {
     pub fn set_value<S: Into<String>>(&mut self, value: S) {
         self.value = value.into();
     }
+
+    /// Set value, checking that it's valid.
+    ///
+    /// Like [`Attribute::set_value`], but if the value contains a character
+    /// that's not allowed in XML you get [`Error::InvalidCharacter`], and
+    /// the value is left unchanged.
+    pub fn set_value_checked<S: Into<String>>(&mut self, value: S) -> Result<(), Error> {
+        let value = value.into();
+        validate_chars(&value)?;
+        self.value = value;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -1,5 +1,7 @@
+use ahash::{HashMap, HashMapExt};
 use indextree::{Arena, NodeId};
 
+use crate::document::{DocumentMetadata, DocumentType};
 use crate::id::{Name, NameId, NameLookup, NamespaceId, NamespaceLookup, PrefixId, PrefixLookup};
 use crate::xmlvalue::Value;
 
@@ -42,6 +44,7 @@ impl From<NodeId> for Node {
 /// * [Creation](#creation)
 /// * [Manipulation](#manipulation)
 /// * [Names, namespaces and prefixes](#names-namespaces-and-prefixes)
+/// * [Document metadata](#document-metadata)
 /// * [Parsing](#parsing)
 /// * [Serialization](#serialization)
 /// * [Value and type access](#value-and-type-access)
@@ -57,6 +60,8 @@ pub struct Xot {
     pub(crate) xml_prefix_id: PrefixId,
     pub(crate) xml_space_id: NameId,
     pub(crate) text_consolidation: bool,
+    pub(crate) document_types: HashMap<Node, DocumentType>,
+    pub(crate) document_metadata: HashMap<Node, DocumentMetadata>,
 }
 
 impl Xot {
@@ -81,6 +86,8 @@ impl Xot {
             xml_prefix_id,
             xml_space_id,
             text_consolidation: true,
+            document_types: HashMap::new(),
+            document_metadata: HashMap::new(),
         }
     }
 
@@ -67,6 +67,22 @@ fn roundtrip(#[values(
   (
     "prefix stability",
     r#"<root xmlns:foo="http://example.com" xmlns:bar="http://example.com/bar"/>"#,
+  ),
+  (
+    "doctype system",
+    "<!DOCTYPE root SYSTEM \"root.dtd\">\n<root/>",
+  ),
+  (
+    "doctype public",
+    "<!DOCTYPE root PUBLIC \"-//Example//DTD Root//EN\" \"root.dtd\">\n<root/>",
+  ),
+  (
+    "xml declaration",
+    "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<root/>",
+  ),
+  (
+    "doctype internal subset",
+    "<!DOCTYPE root [\n<!ENTITY e \"E\">\n]>\n<root/>",
   )
 )] value: RoundTripEntry) {
     let (name, xml) = value;
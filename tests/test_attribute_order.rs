@@ -1,3 +1,6 @@
+use std::sync::Arc;
+
+use xot::output::{self, AttributeOrder, NamespaceOrder};
 use xot::{Error, Xot};
 
 #[test]
@@ -60,3 +63,119 @@ fn test_atribute_reorder() {
     let root = xot.parse(original_xml).unwrap();
     assert_eq!(original_xml, xot.to_string(root).unwrap());
 }
+
+fn ordered(
+    attribute_order: AttributeOrder,
+    namespace_order: NamespaceOrder,
+) -> output::xml::Parameters {
+    output::xml::Parameters {
+        attribute_order,
+        namespace_order,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_attribute_order_expanded_name() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(
+        r#"<doc xmlns:z="http://a.example.com" xmlns:a="http://b.example.com" c="C" a:b="B" z:d="D" b="B"/>"#,
+    )?;
+    assert_eq!(
+        xot.serialize_xml_string(
+            ordered(AttributeOrder::ExpandedName, NamespaceOrder::Insertion),
+            root
+        )?,
+        r#"<doc xmlns:z="http://a.example.com" xmlns:a="http://b.example.com" b="B" c="C" z:d="D" a:b="B"/>"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_attribute_order_qualified_name() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(
+        r#"<doc xmlns:z="http://a.example.com" xmlns:a="http://b.example.com" c="C" z:d="D" a:b="B" b="B"/>"#,
+    )?;
+    assert_eq!(
+        xot.serialize_xml_string(
+            ordered(AttributeOrder::QualifiedName, NamespaceOrder::Prefix),
+            root
+        )?,
+        r#"<doc xmlns:a="http://b.example.com" xmlns:z="http://a.example.com" a:b="B" b="B" c="C" z:d="D"/>"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_attribute_order_custom() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(r#"<doc a="A" bb="B" ccc="C"/>"#)?;
+    // longest name first
+    let compare: output::AttributeCompare = Arc::new(|xot, a, b| {
+        xot.local_name_str(b)
+            .len()
+            .cmp(&xot.local_name_str(a).len())
+    });
+    assert_eq!(
+        xot.serialize_xml_string(
+            ordered(AttributeOrder::Custom(compare), NamespaceOrder::Insertion),
+            root
+        )?,
+        r#"<doc ccc="C" bb="B" a="A"/>"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_namespace_order_prefix_default_first() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(r#"<doc xmlns:b="B" xmlns="D" xmlns:a="A"><p b="1" a="2"/></doc>"#)?;
+    assert_eq!(
+        xot.serialize_xml_string(
+            ordered(AttributeOrder::QualifiedName, NamespaceOrder::Prefix),
+            root
+        )?,
+        r#"<doc xmlns="D" xmlns:a="A" xmlns:b="B"><p a="2" b="1"/></doc>"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_attribute_order_html5() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(r#"<html><body><p title="T" class="C" id="I">x</p></body></html>"#)?;
+    let html = xot.html5().serialize_string(
+        output::html5::Parameters {
+            attribute_order: AttributeOrder::QualifiedName,
+            ..Default::default()
+        },
+        root,
+    )?;
+    assert_eq!(
+        html,
+        r#"<!DOCTYPE html><html><body><p class="C" id="I" title="T">x</p></body></html>"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_attribute_order_pretty_tokens() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(r#"<doc b="B" a="A"/>"#)?;
+    let indentation = output::Indentation::default();
+    let text = xot
+        .pretty_tokens(
+            root,
+            output::TokenSerializeParameters {
+                attribute_order: AttributeOrder::ExpandedName,
+                ..Default::default()
+            },
+            &indentation,
+            output::NoopNormalizer,
+        )
+        .map(|(_, _, token)| token.text)
+        .collect::<Vec<_>>();
+    assert_eq!(text, vec!["<doc", "a=\"A\"", "b=\"B\"", "/>", ""]);
+    Ok(())
+}
@@ -0,0 +1,202 @@
+use xot::output::c14n::{Method, Parameters};
+use xot::{Error, Node, Xot};
+
+fn c14n(xot: &Xot, node: Node) -> Result<String, Error> {
+    xot.serialize_c14n_string(Parameters::default(), node)
+}
+
+fn c14n_with_comments(xot: &Xot, node: Node) -> Result<String, Error> {
+    xot.serialize_c14n_string(
+        Parameters {
+            with_comments: true,
+            ..Default::default()
+        },
+        node,
+    )
+}
+
+fn exc_c14n(xot: &Xot, node: Node, inclusive_prefixes: &[&str]) -> Result<String, Error> {
+    xot.serialize_c14n_string(
+        Parameters {
+            method: Method::Exclusive {
+                inclusive_prefixes: inclusive_prefixes.iter().map(|p| p.to_string()).collect(),
+            },
+            ..Default::default()
+        },
+        node,
+    )
+}
+
+// https://www.w3.org/TR/xml-c14n#Example-OutsideDoc
+#[test]
+fn test_c14n_pis_and_comments_outside_document_element() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(
+        r#"<?xml version="1.0"?>
+
+<?xml-stylesheet   href="doc.xsl"
+   type="text/xsl"   ?>
+
+<!DOCTYPE doc SYSTEM "doc.dtd">
+
+<doc>Hello, world!<!-- Comment 1 --></doc>
+
+<?pi-without-data     ?>
+
+<!-- Comment 2 -->
+
+<!-- Comment 3 -->"#,
+    )?;
+    assert_eq!(
+        c14n(&xot, root)?,
+        "<?xml-stylesheet href=\"doc.xsl\"\n   type=\"text/xsl\"   ?>\n<doc>Hello, world!</doc>\n<?pi-without-data?>"
+    );
+    assert_eq!(
+        c14n_with_comments(&xot, root)?,
+        "<?xml-stylesheet href=\"doc.xsl\"\n   type=\"text/xsl\"   ?>\n<doc>Hello, world!<!-- Comment 1 --></doc>\n<?pi-without-data?>\n<!-- Comment 2 -->\n<!-- Comment 3 -->"
+    );
+    Ok(())
+}
+
+// https://www.w3.org/TR/xml-c14n#Example-SETags
+#[test]
+fn test_c14n_start_and_end_tags() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(
+        r#"<doc>
+   <e1   />
+   <e2   ></e2>
+   <e3   name = "elem3"   id="elem3"   />
+   <e4   name="elem4"   id="elem4"   ></e4>
+   <e5 a:attr="out" b:attr="sorted" attr2="all" attr="I'm"
+      xmlns:b="http://www.ietf.org"
+      xmlns:a="http://www.w3.org"
+      xmlns="http://example.org"/>
+   <e6 xmlns="" xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="" xmlns:a="http://www.w3.org">
+            <e9 xmlns="" xmlns:a="http://www.ietf.org"/>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#,
+    )?;
+    assert_eq!(
+        c14n(&xot, root)?,
+        r#"<doc>
+   <e1></e1>
+   <e2></e2>
+   <e3 id="elem3" name="elem3"></e3>
+   <e4 id="elem4" name="elem4"></e4>
+   <e5 xmlns="http://example.org" xmlns:a="http://www.w3.org" xmlns:b="http://www.ietf.org" attr="I'm" attr2="all" b:attr="sorted" a:attr="out"></e5>
+   <e6 xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="">
+            <e9 xmlns:a="http://www.ietf.org"></e9>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#
+    );
+    Ok(())
+}
+
+// https://www.w3.org/TR/xml-c14n#Example-Chars
+#[test]
+fn test_c14n_character_modifications() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(
+        "<doc>\n   <text>First line&#x0d;&#10;Second line</text>\n   <value>&#x32;</value>\n   <compute><![CDATA[value>\"0\" && value<\"10\" ?\"valid\":\"error\"]]></compute>\n   <compute expr='value>\"0\" &amp;&amp; value&lt;\"10\" ?\"valid\":\"error\"'>valid</compute>\n   <norm attr=' &apos;   &#x20;&#13;&#xa;&#9;   &apos; '/>\n</doc>",
+    )?;
+    assert_eq!(
+        c14n(&xot, root)?,
+        "<doc>\n   <text>First line&#xD;\nSecond line</text>\n   <value>2</value>\n   <compute>value&gt;\"0\" &amp;&amp; value&lt;\"10\" ?\"valid\":\"error\"</compute>\n   <compute expr=\"value>&quot;0&quot; &amp;&amp; value&lt;&quot;10&quot; ?&quot;valid&quot;:&quot;error&quot;\">valid</compute>\n   <norm attr=\" '    &#xD;&#xA;&#x9;   ' \"></norm>\n</doc>"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_c14n_no_namespace_element_undeclares_default() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(r#"<a xmlns="http://example.com"><b/></a>"#)?;
+    let a = xot.document_element(root)?;
+    let b = xot.first_child(a).unwrap();
+    let name = xot.add_name("c");
+    let c = xot.new_element(name);
+    xot.append(b, c)?;
+    assert_eq!(
+        c14n(&xot, root)?,
+        r#"<a xmlns="http://example.com"><b><c xmlns=""></c></b></a>"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_c14n_subset_inherits_namespaces_and_xml_attributes() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(
+        r#"<a xmlns="http://example.com" xmlns:x="http://example.com/x" xml:lang="en" xml:space="preserve"><b xml:lang="fr"><c/></b></a>"#,
+    )?;
+    let a = xot.document_element(root)?;
+    let b = xot.first_child(a).unwrap();
+    assert_eq!(
+        c14n(&xot, b)?,
+        r#"<b xmlns="http://example.com" xmlns:x="http://example.com/x" xml:lang="fr" xml:space="preserve"><c></c></b>"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_c14n_text_node() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse("<a>1 &lt; 2 &amp;&amp; 3 &gt; 2</a>")?;
+    let a = xot.document_element(root)?;
+    let text = xot.first_child(a).unwrap();
+    assert_eq!(c14n(&xot, text)?, "1 &lt; 2 &amp;&amp; 3 &gt; 2");
+    Ok(())
+}
+
+#[test]
+fn test_exc_c14n_omits_unused_namespaces() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(
+        r#"<a xmlns="http://example.com" xmlns:x="http://example.com/x" xmlns:y="http://example.com/y" xml:lang="en"><x:b y:c="1"><d/></x:b></a>"#,
+    )?;
+    let a = xot.document_element(root)?;
+    let b = xot.first_child(a).unwrap();
+    assert_eq!(
+        exc_c14n(&xot, b, &[])?,
+        r#"<x:b xmlns:x="http://example.com/x" xmlns:y="http://example.com/y" y:c="1"><d xmlns="http://example.com"></d></x:b>"#
+    );
+    assert_eq!(
+        exc_c14n(&xot, root, &[])?,
+        r#"<a xmlns="http://example.com" xml:lang="en"><x:b xmlns:x="http://example.com/x" xmlns:y="http://example.com/y" y:c="1"><d></d></x:b></a>"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_exc_c14n_inclusive_prefixes() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(
+        r#"<a xmlns="http://example.com" xmlns:x="http://example.com/x" xmlns:y="http://example.com/y"><x:b><d/></x:b></a>"#,
+    )?;
+    let a = xot.document_element(root)?;
+    let b = xot.first_child(a).unwrap();
+    assert_eq!(
+        exc_c14n(&xot, b, &["y", "#default", "unknown"])?,
+        r#"<x:b xmlns="http://example.com" xmlns:x="http://example.com/x" xmlns:y="http://example.com/y"><d></d></x:b>"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_exc_c14n_rerenders_prefix_on_sibling() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(r#"<a xmlns:x="http://example.com/x"><x:b/><x:c/></a>"#)?;
+    assert_eq!(
+        exc_c14n(&xot, root, &[])?,
+        r#"<a><x:b xmlns:x="http://example.com/x"></x:b><x:c xmlns:x="http://example.com/x"></x:c></a>"#
+    );
+    Ok(())
+}
@@ -942,3 +942,109 @@ fn test_prepend_text_with_attributes() -> Result<(), Error> {
     assert_eq!(xot.children(document_element).count(), 1);
     Ok(())
 }
+
+#[test]
+fn test_add_name_checked() {
+    let mut xot = Xot::new();
+    assert_eq!(xot.add_name_checked("a").unwrap(), xot.add_name("a"));
+    assert!(matches!(
+        xot.add_name_checked("a b"),
+        Err(Error::InvalidName(_))
+    ));
+    assert!(matches!(
+        xot.add_name_checked(""),
+        Err(Error::InvalidName(_))
+    ));
+    assert!(matches!(
+        xot.add_name_checked("x:a"),
+        Err(Error::InvalidName(_))
+    ));
+    let ns = xot.add_namespace("http://example.com");
+    assert!(matches!(
+        xot.add_name_ns_checked("1a", ns),
+        Err(Error::InvalidName(_))
+    ));
+    // nothing was added
+    assert_eq!(xot.name("a b"), None);
+}
+
+#[test]
+fn test_add_prefix_checked() {
+    let mut xot = Xot::new();
+    assert!(xot.add_prefix_checked("").is_ok());
+    assert!(xot.add_prefix_checked("foo").is_ok());
+    assert!(matches!(
+        xot.add_prefix_checked("f:oo"),
+        Err(Error::InvalidPrefix(_))
+    ));
+}
+
+#[test]
+fn test_text_checked() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc>a</doc>").unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    assert!(matches!(
+        xot.new_text_checked("\u{0}"),
+        Err(Error::InvalidCharacter('\u{0}'))
+    ));
+    let text_node = xot.first_child(doc_el).unwrap();
+    let text = xot.text_mut(text_node).unwrap();
+    assert!(text.set_checked("b\u{1b}").is_err());
+    assert!(text.set_checked("b\t").is_ok());
+    assert_eq!(xot.to_string(root).unwrap(), "<doc>b\t</doc>");
+}
+
+#[test]
+fn test_attribute_checked() {
+    let mut xot = Xot::new();
+    let root = xot.parse(r#"<doc a="1"/>"#).unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    let a = xot.name("a").unwrap();
+    assert!(xot
+        .new_attribute_node_checked(a, "\u{FFFF}".to_string())
+        .is_err());
+    let attribute_node = xot.attributes(doc_el).get_node(a).unwrap();
+    let Value::Attribute(attribute) = xot.value_mut(attribute_node) else {
+        unreachable!()
+    };
+    assert!(matches!(
+        attribute.set_value_checked("\u{8}"),
+        Err(Error::InvalidCharacter(_))
+    ));
+    attribute.set_value_checked("2").unwrap();
+    assert_eq!(xot.to_string(root).unwrap(), r#"<doc a="2"/>"#);
+}
+
+#[test]
+fn test_processing_instruction_checked() {
+    let mut xot = Xot::new();
+    let target = xot.add_name("target");
+    assert!(matches!(
+        xot.new_processing_instruction_checked(target, Some("a ?> b")),
+        Err(Error::InvalidProcessingInstructionData(_))
+    ));
+    let pi = xot
+        .new_processing_instruction_checked(target, Some("data"))
+        .unwrap();
+    let Value::ProcessingInstruction(pi_value) = xot.value_mut(pi) else {
+        unreachable!()
+    };
+    assert!(pi_value.set_data_checked(Some("?>")).is_err());
+    assert_eq!(pi_value.data(), Some("data"));
+    pi_value.set_data_checked(None::<String>).unwrap();
+    assert_eq!(pi_value.data(), None);
+}
+
+#[test]
+fn test_parse_full_name_checked() {
+    let mut xot = Xot::new();
+    let ns = xot.add_namespace("http://example.com");
+    let lookup = |prefix: &str| (prefix == "x").then_some(ns);
+    let name = xot::xmlname::CreateName::parse_full_name_checked(&mut xot, "x:a", lookup).unwrap();
+    assert_eq!(xot.name_ns_str(name.name_id()), ("a", "http://example.com"));
+    assert!(matches!(
+        xot::xmlname::CreateName::parse_full_name_checked(&mut xot, "x:a:b", lookup),
+        Err(Error::InvalidName(_))
+    ));
+}
@@ -358,15 +358,17 @@ fn test_namespaces_overrides() {
 
 #[test]
 fn test_namespaces_overrides_xml_prefix() {
+    // the parser doesn't allow rebinding the xml prefix, so we construct
+    // this by hand
     let mut xot = Xot::new();
-    let doc = xot
-        .parse(r#"<doc><p xmlns:xml="http://example.com/a"/></doc>"#)
-        .unwrap();
+    let doc = xot.parse(r#"<doc><p/></doc>"#).unwrap();
     let root_id = xot.document_element(doc).unwrap();
     let a_ns = xot.add_namespace("http://example.com/a");
     let xml_prefix = xot.add_prefix("xml");
     let xml_ns = xot.add_namespace("http://www.w3.org/XML/1998/namespace");
     let p0 = xot.first_child(root_id).unwrap();
+    let namespace_node = xot.new_namespace_node(xml_prefix, a_ns);
+    xot.append_namespace_node(p0, namespace_node).unwrap();
 
     assert_eq!(
         xot.namespaces_in_scope(p0).collect::<Vec<_>>(),
@@ -377,3 +379,31 @@ fn test_namespaces_overrides_xml_prefix() {
         [(xml_prefix, xml_ns)]
     );
 }
+
+#[test]
+fn test_many_names() {
+    // more names than fit in 16 bits
+    let mut xot = Xot::new();
+    let names = (0..70_000)
+        .map(|i| xot.add_name(&format!("n{}", i)))
+        .collect::<Vec<_>>();
+    assert_ne!(names[0], names[65_536]);
+    assert_eq!(xot.local_name_str(names[0]), "n0");
+    assert_eq!(xot.local_name_str(names[65_536]), "n65536");
+    assert_eq!(xot.add_name("n65536"), names[65_536]);
+}
+
+#[test]
+fn test_many_namespaces_and_prefixes() {
+    let mut xot = Xot::new();
+    let namespaces = (0..70_000)
+        .map(|i| xot.add_namespace(&format!("urn:{}", i)))
+        .collect::<Vec<_>>();
+    let prefixes = (0..70_000)
+        .map(|i| xot.add_prefix(&format!("p{}", i)))
+        .collect::<Vec<_>>();
+    assert_eq!(xot.namespace_str(namespaces[65_536]), "urn:65536");
+    assert_eq!(xot.prefix_str(prefixes[65_536]), "p65536");
+    assert_ne!(namespaces[0], namespaces[65_536]);
+    assert_ne!(prefixes[0], prefixes[65_536]);
+}
@@ -1,4 +1,5 @@
-use xot::{Error, Span, SpanInfoKey, Xot};
+use xot::output::xml::DocType;
+use xot::{Error, ParseLimit, ParseLimits, ParseOptions, Span, SpanInfoKey, Xot};
 
 const US_ASCII: &str = include_str!("fixtures/us-ascii.xml");
 
@@ -6,22 +7,31 @@ const US_ASCII: &str = include_str!("fixtures/us-ascii.xml");
 fn test_unclosed_tag() {
     let mut xot = Xot::new();
     let doc = xot.parse(r#"<a><b></a>"#);
-    assert!(matches!(doc, Err(Error::InvalidCloseTag(_, _))));
+    if let Err(Error::InvalidCloseTag(_, _, span)) = doc {
+        assert_eq!(span, Span::new(6, 10));
+    } else {
+        unreachable!();
+    }
 }
 
 #[test]
 fn test_unclosed_tag_at_end() {
     let mut xot = Xot::new();
     let doc = xot.parse(r#"<a>"#);
-    assert!(matches!(doc, Err(Error::UnclosedTag)));
+    if let Err(Error::UnclosedTag(span)) = doc {
+        assert_eq!(span, Span::new(1, 2));
+    } else {
+        unreachable!();
+    }
 }
 
 #[test]
 fn test_duplicate_attributes() {
     let mut xot = Xot::new();
     let doc = xot.parse(r#"<a x="x" x="y"/>"#);
-    if let Err(Error::DuplicateAttribute(s)) = doc {
+    if let Err(Error::DuplicateAttribute(s, span)) = doc {
         assert_eq!(s, "x");
+        assert_eq!(span, Some(Span::new(9, 10)));
     } else {
         unreachable!();
     }
@@ -31,7 +41,7 @@ fn test_duplicate_attributes() {
 fn test_duplicate_attributes_ns() {
     let mut xot = Xot::new();
     let doc = xot.parse(r#"<a xmlns:foo="http://example.com" foo:x="x" foo:x="y"/>"#);
-    if let Err(Error::DuplicateAttribute(s)) = doc {
+    if let Err(Error::DuplicateAttribute(s, _)) = doc {
         assert_eq!(s, "foo:x");
     } else {
         unreachable!();
@@ -63,8 +73,9 @@ fn test_encoding_us_ascii() {
 fn test_unknown_prefix() {
     let mut xot = Xot::new();
     let doc = xot.parse(r#"<a><foo:b></a>"#);
-    if let Err(Error::UnknownPrefix(s)) = doc {
+    if let Err(Error::UnknownPrefix(s, span)) = doc {
         assert_eq!(s, "foo");
+        assert_eq!(span, Some(Span::new(4, 9)));
     } else {
         unreachable!();
     }
@@ -94,7 +105,7 @@ fn test_ampersand() -> Result<(), Error> {
     let mut xot = Xot::new();
     let doc = xot.parse(r#"<a>&</a>"#);
     if let Err(err) = doc {
-        assert!(matches!(err, Error::UnclosedEntity(_)));
+        assert!(matches!(err, Error::UnclosedEntity(_, _)));
     } else {
         unreachable!();
     }
@@ -373,3 +384,1543 @@ fn test_span_for_cdata_cdata() {
         &Span::new(12, 30)
     );
 }
+
+#[test]
+fn test_parse_doctype() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<!DOCTYPE a SYSTEM "a.dtd"><a/>"#)?;
+    let document_type = xot.document_type(doc).unwrap();
+    assert_eq!(document_type.name, "a");
+    assert_eq!(
+        document_type.external_id,
+        Some(DocType::System {
+            system: "a.dtd".to_string()
+        })
+    );
+    assert_eq!(document_type.internal_subset, None);
+    Ok(())
+}
+
+#[test]
+fn test_parse_doctype_internal_subset() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<!DOCTYPE a [<!ELEMENT a EMPTY>]><a/>"#)?;
+    let document_type = xot.document_type(doc).unwrap();
+    assert_eq!(document_type.external_id, None);
+    assert_eq!(
+        document_type.internal_subset.as_deref(),
+        Some("<!ELEMENT a EMPTY>")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_parse_without_doctype() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<a/>"#)?;
+    assert!(xot.document_type(doc).is_none());
+    Ok(())
+}
+
+#[test]
+fn test_parse_after_remove_doctype() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<!DOCTYPE a SYSTEM "a.dtd"><a/>"#)?;
+    xot.remove(doc)?;
+    assert!(xot.document_type(doc).is_none());
+    let new_doc = xot.parse(r#"<a/>"#)?;
+    assert!(xot.document_type(new_doc).is_none());
+    Ok(())
+}
+
+#[test]
+fn test_parse_internal_entity_in_text() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse(
+        r#"<!DOCTYPE a [
+<!ELEMENT a (#PCDATA)>
+<!ENTITY custom "Custom text">
+]><a>Hello &custom;!</a>"#,
+    )?;
+    let doc_el = xot.document_element(doc)?;
+    assert_eq!(xot.text_content_str(doc_el), Some("Hello Custom text!"));
+    Ok(())
+}
+
+#[test]
+fn test_parse_internal_entity_in_attribute() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<!DOCTYPE a [<!ENTITY custom "Custom">]><a b="&custom;"/>"#)?;
+    let doc_el = xot.document_element(doc)?;
+    let b = xot.add_name("b");
+    assert_eq!(xot.get_attribute(doc_el, b), Some("Custom"));
+    Ok(())
+}
+
+#[test]
+fn test_parse_parameter_entity_ignored() {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<!DOCTYPE a [<!ENTITY % custom "Custom">]><a>&custom;</a>"#);
+    assert!(matches!(doc, Err(Error::InvalidEntity(_, _))));
+}
+
+#[test]
+fn test_parse_external_entity_unsupported() {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<!DOCTYPE a [<!ENTITY custom SYSTEM "custom.xml">]><a>&custom;</a>"#);
+    assert!(matches!(doc, Err(Error::InvalidEntity(_, _))));
+}
+
+#[test]
+fn test_parse_billion_laughs() {
+    let mut xot = Xot::new();
+    let mut dtd = r#"<!ENTITY lol0 "lol">"#.to_string();
+    for i in 1..10 {
+        let references = format!("&lol{};", i - 1).repeat(10);
+        dtd.push_str(&format!(r#"<!ENTITY lol{} "{}">"#, i, references));
+    }
+    let xml = format!("<!DOCTYPE a [{}]><a>&lol9;</a>", dtd);
+    let err = xot.parse(&xml).unwrap_err();
+    assert!(matches!(
+        err,
+        Error::LimitExceeded(ParseLimit::EntityExpansion, _)
+    ));
+    assert_eq!(&xml[err.span().unwrap().range()], "&lol9;");
+}
+
+#[test]
+fn test_parse_billion_laughs_with_recovery() {
+    let mut xot = Xot::new();
+    let mut dtd = r#"<!ENTITY lol0 "lol">"#.to_string();
+    for i in 1..10 {
+        let references = format!("&lol{};", i - 1).repeat(10);
+        dtd.push_str(&format!(r#"<!ENTITY lol{} "{}">"#, i, references));
+    }
+    // the limit isn't recovered from in attribute values either
+    let xml = format!(r#"<!DOCTYPE a [{}]><a b="&lol9;"><c/></a>"#, dtd);
+    let (root, diagnostics) = xot.parse_with_recovery(&xml);
+    assert!(matches!(
+        diagnostics[0].error,
+        Error::LimitExceeded(ParseLimit::EntityExpansion, _)
+    ));
+    assert_eq!(&xml[diagnostics[0].span.range()], "&lol9;");
+    let a = xot.document_element(root).unwrap();
+    assert_eq!(xot.to_string(a).unwrap(), "<a/>");
+}
+
+#[test]
+fn test_parse_recursive_entity() {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<!DOCTYPE a [<!ENTITY x "&y;"><!ENTITY y "&x;">]><a>&x;</a>"#);
+    assert!(matches!(doc, Err(Error::RecursiveEntity(_, _))));
+}
+
+#[test]
+fn test_parse_declaration_metadata() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?><a/>"#)?;
+    let metadata = xot.document_metadata(doc).unwrap();
+    assert_eq!(metadata.version.as_deref(), Some("1.0"));
+    assert_eq!(metadata.encoding.as_deref(), Some("utf-8"));
+    assert_eq!(metadata.standalone, Some(true));
+    assert_eq!(metadata.detected_encoding, None);
+    Ok(())
+}
+
+#[test]
+fn test_parse_without_declaration_metadata() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<a/>"#)?;
+    assert!(xot.document_metadata(doc).is_none());
+    Ok(())
+}
+
+#[test]
+fn test_parse_after_remove_metadata() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<?xml version="1.0" encoding="utf-8"?><a/>"#)?;
+    xot.remove(doc)?;
+    assert!(xot.document_metadata(doc).is_none());
+    let new_doc = xot.parse(r#"<a/>"#)?;
+    assert!(xot.document_metadata(new_doc).is_none());
+    Ok(())
+}
+
+#[test]
+fn test_parse_bytes_detected_encoding() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse_bytes(b"<a/>")?;
+    let metadata = xot.document_metadata(doc).unwrap();
+    assert_eq!(metadata.version, None);
+    assert_eq!(metadata.detected_encoding.as_deref(), Some("UTF-8"));
+    Ok(())
+}
+
+#[test]
+fn test_invalid_entity_span() {
+    let mut xot = Xot::new();
+    let xml = "<a>\n  text &unknown; text\n</a>";
+    let err = xot.parse(xml).unwrap_err();
+    let span = err.span().unwrap();
+    assert_eq!(&xml[span.range()], "&unknown;");
+    assert_eq!(span.line_column(xml), (2, 8));
+}
+
+#[test]
+fn test_invalid_entity_in_attribute_span() {
+    let mut xot = Xot::new();
+    let xml = r#"<a b="&unknown;"/>"#;
+    let err = xot.parse(xml).unwrap_err();
+    assert_eq!(&xml[err.span().unwrap().range()], "&unknown;");
+}
+
+#[test]
+fn test_invalid_entity_in_replacement_text_span() {
+    // an error in the replacement text of an entity is reported at the
+    // reference to the entity
+    let mut xot = Xot::new();
+    let xml = r#"<!DOCTYPE a [<!ENTITY x "&unknown;">]><a>&x;</a>"#;
+    let err = xot.parse(xml).unwrap_err();
+    assert_eq!(&xml[err.span().unwrap().range()], "&x;");
+}
+
+#[test]
+fn test_unknown_prefix_in_attribute_span() {
+    let mut xot = Xot::new();
+    let xml = "<a\n foo:b=\"B\"/>";
+    let err = xot.parse(xml).unwrap_err();
+    let span = err.span().unwrap();
+    assert_eq!(&xml[span.range()], "foo:b");
+    assert_eq!(span.line_column(xml), (2, 2));
+}
+
+#[test]
+fn test_parse_error_display_includes_span() {
+    let mut xot = Xot::new();
+    let err = xot.parse("<a><foo:b/></a>").unwrap_err();
+    assert_eq!(err.to_string(), "Unknown prefix: foo at 4..9");
+}
+
+#[test]
+fn test_unsupported_version_span() {
+    let mut xot = Xot::new();
+    let xml = r#"<?xml version="1.2"?><a/>"#;
+    let err = xot.parse(xml).unwrap_err();
+    assert_eq!(&xml[err.span().unwrap().range()], "1.2");
+}
+
+#[test]
+fn test_parse_standalone_no() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<?xml version="1.0" standalone="no"?><a/>"#)?;
+    let metadata = xot.document_metadata(doc).unwrap();
+    assert_eq!(metadata.standalone, Some(false));
+    assert_eq!(
+        xot.to_string(doc)?,
+        "<?xml version=\"1.0\" standalone=\"no\"?>\n<a/>"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_parse_xml11() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<?xml version="1.1"?><a/>"#)?;
+    let metadata = xot.document_metadata(doc).unwrap();
+    assert_eq!(metadata.version.as_deref(), Some("1.1"));
+    assert_eq!(xot.to_string(doc)?, "<?xml version=\"1.1\"?>\n<a/>");
+    Ok(())
+}
+
+#[test]
+fn test_parse_xml11_line_ends() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse(
+        "<?xml version=\"1.1\"?><a b=\"x\u{85}y\u{2028}z\">1\r\u{85}2\u{85}3\u{2028}4</a>",
+    )?;
+    let a = xot.document_element(doc)?;
+    let b = xot.add_name("b");
+    assert_eq!(xot.get_attribute(a, b), Some("x y z"));
+    assert_eq!(xot.text_content_str(a), Some("1\n2\n3\n4"));
+    Ok(())
+}
+
+#[test]
+fn test_parse_xml10_no_nel_line_ends() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse("<a>1\u{85}2\u{2028}3</a>")?;
+    let a = xot.document_element(doc)?;
+    assert_eq!(xot.text_content_str(a), Some("1\u{85}2\u{2028}3"));
+    Ok(())
+}
+
+#[test]
+fn test_parse_xml11_control_character_reference() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<?xml version="1.1"?><a>&#x1;</a>"#)?;
+    let a = xot.document_element(doc)?;
+    assert_eq!(xot.text_content_str(a), Some("\u{1}"));
+
+    assert!(xot.parse("<a>&#x1;</a>").is_err());
+    assert!(xot.parse(r#"<?xml version="1.1"?><a>&#x0;</a>"#).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_parse_xml11_restricted_character() {
+    let mut xot = Xot::new();
+    let xml = "<?xml version=\"1.1\"?><a>x\u{80}</a>";
+    let err = xot.parse(xml).unwrap_err();
+    assert!(matches!(err, Error::RestrictedCharacter('\u{80}', _)));
+    assert_eq!(&xml[err.span().unwrap().range()], "\u{80}");
+
+    // in XML 1.0 this is an ordinary character
+    assert!(xot.parse("<a>x\u{80}</a>").is_ok());
+}
+
+#[test]
+fn test_serialize_xml11_control_characters_roundtrip() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let xml = r#"<?xml version="1.1"?><a b="&#x1;&#x85;">&#x1;&#x7F;&#x85;&#x9F;&#x2028;<![CDATA[x]]>&#x2;</a>"#;
+    let doc = xot.parse(xml)?;
+    let serialized = xot.to_string(doc)?;
+    assert_eq!(
+        serialized,
+        "<?xml version=\"1.1\"?>\n<a b=\"&#x1;&#x85;\">&#x1;&#x7F;&#x85;&#x9F;&#x2028;x&#x2;</a>"
+    );
+    let reparsed = xot.parse(&serialized)?;
+    let a = xot.document_element(reparsed)?;
+    assert_eq!(
+        xot.text_content_str(a),
+        Some("\u{1}\u{7F}\u{85}\u{9F}\u{2028}x\u{2}")
+    );
+    let b = xot.add_name("b");
+    assert_eq!(xot.get_attribute(a, b), Some("\u{1}\u{85}"));
+
+    // in XML 1.0 these characters are written as they are
+    let doc = xot.parse("<a>\u{85}</a>")?;
+    assert_eq!(xot.to_string(doc)?, "<a>\u{85}</a>");
+    Ok(())
+}
+
+#[test]
+fn test_parse_xml11_prefix_undeclaration() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse(r#"<?xml version="1.1"?><p:a xmlns:p="http://example.com"><b xmlns:p=""/></p:a>"#)?;
+    let a = xot.document_element(doc)?;
+    let b = xot.first_child(a).unwrap();
+    assert_eq!(xot.namespaces(b).len(), 0);
+
+    let err = xot
+        .parse(
+            r#"<?xml version="1.1"?><a xmlns:p="http://example.com"><b xmlns:p=""><p:c/></b></a>"#,
+        )
+        .unwrap_err();
+    assert!(matches!(err, Error::UnknownPrefix(_, _)));
+
+    // prefix undeclaration is not allowed in XML 1.0
+    let err = xot.parse(r#"<a xmlns:p=""/>"#).unwrap_err();
+    assert!(matches!(err, Error::EmptyPrefixedNamespace(_, _)));
+    Ok(())
+}
+
+#[test]
+fn test_parse_options_default() {
+    let mut xot = Xot::new();
+    let xml = "<doc> <!--c--><?pi?>a<![CDATA[b]]>c </doc>";
+    let doc = xot
+        .parse_with_options(xml, ParseOptions::default())
+        .unwrap();
+    assert_eq!(
+        xot.to_string(doc).unwrap(),
+        "<doc> <!--c--><?pi?>abc </doc>"
+    );
+}
+
+#[test]
+fn test_parse_options_strip_whitespace() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse_with_options(
+            "<doc>\n  <p>Hello <b>world</b> !</p>\n  <p> </p>\n</doc>",
+            ParseOptions {
+                strip_whitespace: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        xot.to_string(doc).unwrap(),
+        "<doc><p>Hello <b>world</b> !</p><p/></doc>"
+    );
+}
+
+#[test]
+fn test_parse_options_strip_whitespace_same_as_remove_insignificant_whitespace() {
+    let xml = r#"<doc xml:space="preserve">  <p xml:space="default">  <i> </i> </p><q> </q></doc>"#;
+    let mut xot = Xot::new();
+    let stripped = xot
+        .parse_with_options(
+            xml,
+            ParseOptions {
+                strip_whitespace: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let removed = xot.parse(xml).unwrap();
+    xot.remove_insignificant_whitespace(removed);
+    assert_eq!(
+        xot.to_string(stripped).unwrap(),
+        xot.to_string(removed).unwrap()
+    );
+    assert_eq!(
+        xot.to_string(stripped).unwrap(),
+        r#"<doc xml:space="preserve">  <p xml:space="default"><i/></p><q> </q></doc>"#
+    );
+}
+
+#[test]
+fn test_parse_options_strip_whitespace_cdata() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse_with_options(
+            "<doc> <![CDATA[ ]]> <p> <![CDATA[x]]></p></doc>",
+            ParseOptions {
+                strip_whitespace: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(xot.to_string(doc).unwrap(), "<doc><p> x</p></doc>");
+}
+
+#[test]
+fn test_parse_options_strip_comments_and_processing_instructions() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse_with_options(
+            "<!--before--><?before?><doc>a<!--c-->b<?pi data?>c</doc><!--after-->",
+            ParseOptions {
+                strip_comments: true,
+                strip_processing_instructions: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(xot.to_string(doc).unwrap(), "<doc>abc</doc>");
+    let doc_el = xot.document_element(doc).unwrap();
+    assert_eq!(xot.children(doc_el).count(), 1);
+    assert_eq!(xot.children(doc).count(), 1);
+}
+
+#[test]
+fn test_parse_options_cdata_boundaries() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse_with_options(
+            "<doc>a<![CDATA[b]]><![CDATA[c]]>d</doc>",
+            ParseOptions {
+                cdata_boundaries: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    let texts = xot
+        .children(doc_el)
+        .map(|node| xot.text_str(node).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(texts, vec!["a", "b", "c", "d"]);
+}
+
+#[test]
+fn test_parse_options_no_text_consolidation() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse_with_options(
+            "<doc>a<!--c-->b<![CDATA[c]]></doc>",
+            ParseOptions {
+                strip_comments: true,
+                text_consolidation: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    let texts = xot
+        .children(doc_el)
+        .map(|node| xot.text_str(node).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(texts, vec!["a", "b", "c"]);
+}
+
+// a reader that returns a few bytes at a time
+struct ChunkReader<'a> {
+    data: &'a [u8],
+    size: usize,
+}
+
+impl std::io::Read for ChunkReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.size.min(buf.len()).min(self.data.len());
+        buf[..size].copy_from_slice(&self.data[..size]);
+        self.data = &self.data[size..];
+        Ok(size)
+    }
+}
+
+#[test]
+fn test_parse_reader() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse_reader(ChunkReader {
+            data: US_ASCII.as_bytes(),
+            size: 7,
+        })
+        .unwrap();
+    let expected = xot.parse(US_ASCII).unwrap();
+    assert_eq!(
+        xot.to_string(doc).unwrap(),
+        xot.to_string(expected).unwrap()
+    );
+}
+
+#[test]
+fn test_parse_reader_large() {
+    let mut xml = String::from("<doc>");
+    for i in 0..10000 {
+        xml.push_str(&format!("<p n=\"{}\">text &amp; more text {}</p>\n", i, i));
+    }
+    xml.push_str("</doc>");
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse_reader(std::io::Cursor::new(xml.as_bytes()))
+        .unwrap();
+    assert_eq!(xot.to_string(doc).unwrap(), xml);
+}
+
+#[test]
+fn test_parse_reader_encoding() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse_reader(ChunkReader {
+            data: b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>\xe9</p>",
+            size: 5,
+        })
+        .unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    assert_eq!(xot.text_content_str(doc_el), Some("\u{e9}"));
+    assert_eq!(
+        xot.document_metadata(doc)
+            .unwrap()
+            .detected_encoding
+            .as_deref(),
+        Some("windows-1252")
+    );
+}
+
+#[test]
+fn test_parse_reader_error_span() {
+    let xml = "<doc>\n  <p>text</p>\n  <p>&unknown;</p>\n</doc>";
+    let mut xot = Xot::new();
+    let err = xot
+        .parse_reader(ChunkReader {
+            data: xml.as_bytes(),
+            size: 3,
+        })
+        .unwrap_err();
+    let expected = xot.parse(xml).unwrap_err();
+    assert_eq!(err.span(), expected.span());
+    assert_eq!(err.span().unwrap().line_column(xml), (3, 6));
+}
+
+#[test]
+fn test_parse_reader_error_before_end() {
+    use std::io::Read;
+
+    // the malformed attribute is reported without reading the rest
+    let mut reader =
+        std::io::Cursor::new("<doc><p a=1/>").chain(std::io::repeat(b'x').take(100_000_000));
+    let mut xot = Xot::new();
+    let err = xot.parse_reader(&mut reader).unwrap_err();
+    assert!(matches!(err, Error::Parser(_)));
+    assert!(reader.get_ref().1.limit() > 0);
+}
+
+#[test]
+fn test_parse_reader_io_error() {
+    struct FailingReader;
+
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("failed"))
+        }
+    }
+
+    let mut xot = Xot::new();
+    let err = xot.parse_reader(FailingReader).unwrap_err();
+    assert!(matches!(err, Error::Io(_)));
+}
+
+#[test]
+fn test_parse_html5_entities() {
+    let mut xot = Xot::new();
+    let xml = r#"<p title="caf&eacute;">a&nbsp;b&mdash;&amp;&NotEqualTilde;</p>"#;
+    assert!(matches!(xot.parse(xml), Err(Error::InvalidEntity(..))));
+    let doc = xot
+        .parse_with_options(
+            xml,
+            ParseOptions {
+                html5_entities: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let p = xot.document_element(doc).unwrap();
+    let title = xot.add_name("title");
+    assert_eq!(xot.get_attribute(p, title), Some("caf\u{e9}"));
+    assert_eq!(
+        xot.text_content_str(p),
+        Some("a\u{a0}b\u{2014}&\u{2242}\u{338}")
+    );
+}
+
+#[test]
+fn test_parse_html5_entities_unknown() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse_with_options(
+            "<p>&nosuchentity;</p>",
+            ParseOptions {
+                html5_entities: true,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidEntity(..)));
+}
+
+#[test]
+fn test_parse_custom_entities() {
+    let mut xot = Xot::new();
+    let options = ParseOptions {
+        html5_entities: true,
+        entities: [
+            ("product".to_string(), "Xot & co".to_string()),
+            ("nbsp".to_string(), " ".to_string()),
+        ]
+        .into_iter()
+        .collect(),
+        ..Default::default()
+    };
+    let doc = xot
+        .parse_with_options(r#"<p a="&product;">&product;&nbsp;</p>"#, options.clone())
+        .unwrap();
+    let p = xot.document_element(doc).unwrap();
+    let a = xot.add_name("a");
+    // custom entities take precedence over the HTML 5 ones, and are taken
+    // literally
+    assert_eq!(xot.get_attribute(p, a), Some("Xot & co"));
+    assert_eq!(xot.text_content_str(p), Some("Xot & co "));
+
+    // entities declared in the DTD take precedence
+    let doc = xot
+        .parse_with_options(
+            r#"<!DOCTYPE p [<!ENTITY product "Other">]><p>&product;</p>"#,
+            options,
+        )
+        .unwrap();
+    let p = xot.document_element(doc).unwrap();
+    assert_eq!(xot.text_content_str(p), Some("Other"));
+}
+
+#[test]
+fn test_parse_custom_entities_invalid_character() {
+    let mut xot = Xot::new();
+    let options = ParseOptions {
+        entities: [("bad".to_string(), "a\0b".to_string())]
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    };
+    assert!(matches!(
+        xot.parse_with_options("<p>&bad;</p>", options.clone()),
+        Err(Error::InvalidCharacter('\0'))
+    ));
+    assert!(matches!(
+        xot.parse_with_options("<p/>", options.clone()),
+        Err(Error::InvalidCharacter('\0'))
+    ));
+    assert!(matches!(
+        xot.push_parser_with_options(options),
+        Err(Error::InvalidCharacter('\0'))
+    ));
+}
+
+#[test]
+fn test_parse_reader_subtrees() {
+    let xml = r#"<feed xmlns:x="http://example.com"><title>T</title><record n="1"><x:a>A</x:a></record><record n="2"><x:a>B</x:a></record></feed>"#;
+    let mut xot = Xot::new();
+    let record = xot.add_name("record");
+    let mut records = Vec::new();
+    let doc = xot
+        .parse_reader_subtrees(
+            ChunkReader {
+                data: xml.as_bytes(),
+                size: 7,
+            },
+            |xot, node| xot.element(node).map(|element| element.name()) == Some(record),
+            |xot, node| {
+                // the prefixes in scope of the ancestors are available
+                records.push(xot.to_string(node)?);
+                Ok(())
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        records,
+        vec![
+            r#"<record xmlns:x="http://example.com" n="1"><x:a>A</x:a></record>"#,
+            r#"<record xmlns:x="http://example.com" n="2"><x:a>B</x:a></record>"#,
+        ]
+    );
+    assert_eq!(
+        xot.to_string(doc).unwrap(),
+        r#"<feed xmlns:x="http://example.com"><title>T</title></feed>"#
+    );
+}
+
+#[test]
+fn test_parse_reader_subtrees_indented() {
+    let mut xml = String::from("<feed>\n  <title>T</title>");
+    for i in 0..100000 {
+        xml.push_str(&format!("\n  <record>\n    <n>{}</n>\n  </record>", i));
+    }
+    xml.push_str("\n</feed>\n");
+    let mut xot = Xot::new();
+    let record = xot.add_name("record");
+    let mut count = 0;
+    let doc = xot
+        .parse_reader_subtrees(
+            std::io::Cursor::new(xml.as_bytes()),
+            |xot, node| xot.element(node).map(|element| element.name()) == Some(record),
+            |_, _| {
+                count += 1;
+                Ok(())
+            },
+        )
+        .unwrap();
+    assert_eq!(count, 100000);
+    // the indentation around the records is gone as well
+    assert_eq!(
+        xot.to_string(doc).unwrap(),
+        "<feed>\n  <title>T</title>\n</feed>"
+    );
+}
+
+#[test]
+fn test_parse_reader_subtrees_whitespace_before_cdata() {
+    let xml = "<feed><r/>  <![CDATA[x]]><r/> </feed>";
+    let mut xot = Xot::new();
+    let r = xot.add_name("r");
+    // whether the whitespace is kept doesn't depend on where a chunk ends
+    for size in 1..=xml.len() {
+        let doc = xot
+            .parse_reader_subtrees(
+                ChunkReader {
+                    data: xml.as_bytes(),
+                    size,
+                },
+                |xot, node| xot.element(node).map(|element| element.name()) == Some(r),
+                |_, _| Ok(()),
+            )
+            .unwrap();
+        assert_eq!(xot.to_string(doc).unwrap(), "<feed>  x </feed>", "{}", size);
+    }
+}
+
+#[test]
+fn test_parse_reader_subtrees_large() {
+    let mut xml = String::from("<feed>");
+    for i in 0..10000 {
+        xml.push_str(&format!("<record><n>{}</n></record>", i));
+    }
+    xml.push_str("</feed>");
+    let mut xot = Xot::new();
+    let record = xot.add_name("record");
+    let mut count = 0;
+    let doc = xot
+        .parse_reader_subtrees(
+            std::io::Cursor::new(xml.as_bytes()),
+            |xot, node| xot.element(node).map(|element| element.name()) == Some(record),
+            |xot, node| {
+                assert_eq!(xot.text_content_str(node), None);
+                let n = xot.first_child(node).unwrap();
+                assert_eq!(xot.text_content_str(n), Some(count.to_string().as_str()));
+                count += 1;
+                Ok(())
+            },
+        )
+        .unwrap();
+    assert_eq!(count, 10000);
+    assert_eq!(xot.to_string(doc).unwrap(), "<feed/>");
+}
+
+#[test]
+fn test_parse_reader_subtrees_keep() {
+    let xml = "<feed><record>A</record><record>B</record></feed>";
+    let mut xot = Xot::new();
+    let record = xot.add_name("record");
+    let mut kept = Vec::new();
+    xot.parse_reader_subtrees(
+        xml.as_bytes(),
+        |xot, node| xot.element(node).map(|element| element.name()) == Some(record),
+        |xot, node| {
+            xot.detach(node)?;
+            kept.push(node);
+            Ok(())
+        },
+    )
+    .unwrap();
+    assert_eq!(kept.len(), 2);
+    assert_eq!(xot.to_string(kept[1]).unwrap(), "<record>B</record>");
+}
+
+#[test]
+fn test_parse_reader_subtrees_error() {
+    let xml = "<feed><record>A</record><record>B</record></feed>";
+    let mut xot = Xot::new();
+    let err = xot
+        .parse_reader_subtrees(
+            xml.as_bytes(),
+            |_, _| true,
+            |_, _| Err(Error::InvalidOperation("stop".to_string())),
+        )
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidOperation(_)));
+
+    let err = xot
+        .parse_reader_subtrees(
+            "<feed><record></feed>".as_bytes(),
+            |_, _| true,
+            |_, _| Ok(()),
+        )
+        .unwrap_err();
+    assert!(matches!(err, Error::InvalidCloseTag(..)));
+}
+
+#[test]
+fn test_push_parser_split_character() {
+    let xml = "<doc>caf\u{e9} \u{1f600}</doc>";
+    let mut xot = Xot::new();
+    let mut parser = xot.push_parser();
+    for chunk in xml.as_bytes().chunks(1) {
+        parser.feed(chunk).unwrap();
+    }
+    let doc = parser.finish().unwrap();
+    assert_eq!(xot.to_string(doc).unwrap(), xml);
+}
+
+#[test]
+fn test_push_parser_unclosed_tag() {
+    let xml = "<doc>\n  <p>";
+    let mut xot = Xot::new();
+    let mut parser = xot.push_parser();
+    parser.feed_str(&xml[..6]).unwrap();
+    parser.feed_str(&xml[6..]).unwrap();
+    let err = parser.finish().unwrap_err();
+    assert!(matches!(err, Error::UnclosedTag(_)));
+    assert_eq!(err.span(), Some(Span::new(9, 10)));
+}
+
+#[test]
+fn test_push_parser_error_in_feed() {
+    let mut xot = Xot::new();
+    let mut parser = xot.push_parser();
+    parser.feed_str("<doc><p>").unwrap();
+    let err = parser.feed_str("</q><p>more").unwrap_err();
+    assert!(matches!(err, Error::InvalidCloseTag(_, _, _)));
+    assert_eq!(err.span(), Some(Span::new(8, 12)));
+}
+
+#[test]
+fn test_push_parser_malformed_error_in_feed() {
+    let mut xot = Xot::new();
+    let mut parser = xot.push_parser();
+    parser.feed(b"<doc><p a=1/>").unwrap();
+    let chunk = "<p>text</p>".repeat(10);
+    let err = (0..10)
+        .find_map(|_| parser.feed(chunk.as_bytes()).err())
+        .expect("error not reported by feed");
+    let expected = Xot::new()
+        .parse(&format!("<doc><p a=1/>{}", chunk))
+        .unwrap_err();
+    assert_eq!(err.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_push_parser_with_options() {
+    let mut xot = Xot::new();
+    let mut parser = xot
+        .push_parser_with_options(ParseOptions {
+            strip_whitespace: true,
+            strip_comments: true,
+            ..Default::default()
+        })
+        .unwrap();
+    parser.feed_str("<doc>\n  <p>a</p><!-- c").unwrap();
+    parser.feed_str(" -->\n</doc>").unwrap();
+    let doc = parser.finish().unwrap();
+    assert_eq!(xot.to_string(doc).unwrap(), "<doc><p>a</p></doc>");
+}
+
+#[test]
+fn test_push_parser_cannot_mix_bytes_and_text() {
+    let mut xot = Xot::new();
+    let mut parser = xot.push_parser();
+    parser.feed_str("<doc>").unwrap();
+    assert!(matches!(
+        parser.feed(b"</doc>"),
+        Err(Error::InvalidOperation(_))
+    ));
+}
+
+#[test]
+fn test_parse_fragment_default_namespace() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<doc xmlns="http://example.com/a"><p/></doc>"#)
+        .unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    let p = xot.first_child(doc_el).unwrap();
+    let nodes = xot.parse_fragment(p, "<q/>").unwrap();
+    let ns = xot.namespace("http://example.com/a").unwrap();
+    assert_eq!(
+        xot.element(nodes[0]).unwrap().name(),
+        xot.name_ns("q", ns).unwrap()
+    );
+    assert_eq!(xot.parent(nodes[0]), None);
+}
+
+#[test]
+fn test_parse_fragment_top_level_nodes() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc/>").unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    let nodes = xot
+        .parse_fragment(doc_el, "text<!--comment--><?pi?><a>&amp;</a> ")
+        .unwrap();
+    assert_eq!(nodes.len(), 5);
+    assert_eq!(xot.text_str(nodes[0]), Some("text"));
+    assert!(xot.is_comment(nodes[1]));
+    assert!(xot.is_processing_instruction(nodes[2]));
+    assert!(xot.is_element(nodes[3]));
+    assert_eq!(xot.text_str(nodes[4]), Some(" "));
+    for node in nodes {
+        xot.append(doc_el, node).unwrap();
+    }
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        "<doc>text<!--comment--><?pi?><a>&amp;</a> </doc>"
+    );
+}
+
+#[test]
+fn test_parse_fragment_empty() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc/>").unwrap();
+    assert!(xot.parse_fragment(root, "").unwrap().is_empty());
+}
+
+#[test]
+fn test_parse_fragment_own_declarations() {
+    let mut xot = Xot::new();
+    let root = xot
+        .parse(r#"<doc xmlns:x="http://example.com/x"/>"#)
+        .unwrap();
+    let doc_el = xot.document_element(root).unwrap();
+    let nodes = xot
+        .parse_fragment(
+            doc_el,
+            r#"<x:a xmlns:x="http://example.com/y"><x:b/></x:a><x:c/>"#,
+        )
+        .unwrap();
+    let ns_x = xot.namespace("http://example.com/x").unwrap();
+    let ns_y = xot.namespace("http://example.com/y").unwrap();
+    let b = xot.first_child(nodes[0]).unwrap();
+    assert_eq!(
+        xot.element(b).unwrap().name(),
+        xot.name_ns("b", ns_y).unwrap()
+    );
+    assert_eq!(
+        xot.element(nodes[1]).unwrap().name(),
+        xot.name_ns("c", ns_x).unwrap()
+    );
+}
+
+#[test]
+fn test_parse_fragment_unknown_prefix() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc/>").unwrap();
+    let err = xot.parse_fragment(root, "<x:a/>").unwrap_err();
+    assert!(matches!(err, Error::UnknownPrefix(_, _)));
+    assert_eq!(err.span(), Some(Span::new(1, 4)));
+}
+
+#[test]
+fn test_parse_fragment_unmatched_close_tag() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc/>").unwrap();
+    let err = xot.parse_fragment(root, "<a/></a>").unwrap_err();
+    assert!(matches!(err, Error::InvalidCloseTag(_, _, _)));
+}
+
+#[test]
+fn test_parse_fragment_unclosed_tag() {
+    let mut xot = Xot::new();
+    let root = xot.parse("<doc/>").unwrap();
+    let err = xot.parse_fragment(root, "<a><b>").unwrap_err();
+    assert_eq!(err.span(), Some(Span::new(4, 5)));
+}
+
+#[test]
+fn test_parse_with_recovery_no_errors() {
+    let mut xot = Xot::new();
+    let xml = r#"<?xml version="1.0"?><a x="1"><b>text &amp; more</b><!-- c --></a>"#;
+    let (root, diagnostics) = xot.parse_with_recovery(xml);
+    assert!(diagnostics.is_empty());
+    let expected = xot.parse(xml).unwrap();
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        xot.to_string(expected).unwrap()
+    );
+}
+
+#[test]
+fn test_parse_with_recovery_unclosed_tags() {
+    let mut xot = Xot::new();
+    let (root, diagnostics) = xot.parse_with_recovery("<a><b><c>text");
+    assert_eq!(xot.to_string(root).unwrap(), "<a><b><c>text</c></b></a>");
+    let spans = diagnostics.iter().map(|d| d.span).collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        vec![Span::new(7, 8), Span::new(4, 5), Span::new(1, 2)]
+    );
+    assert!(diagnostics
+        .iter()
+        .all(|d| matches!(d.error, Error::UnclosedTag(_))));
+}
+
+#[test]
+fn test_parse_with_recovery_close_ancestor() {
+    let mut xot = Xot::new();
+    let (root, diagnostics) = xot.parse_with_recovery("<a><b><c>text</a>");
+    assert_eq!(xot.to_string(root).unwrap(), "<a><b><c>text</c></b></a>");
+    assert_eq!(diagnostics.len(), 2);
+    assert!(matches!(diagnostics[0].error, Error::UnclosedTag(_)));
+    assert_eq!(diagnostics[0].span, Span::new(7, 8));
+    assert_eq!(diagnostics[1].span, Span::new(4, 5));
+}
+
+#[test]
+fn test_parse_with_recovery_unmatched_close_tag() {
+    let mut xot = Xot::new();
+    let (root, diagnostics) = xot.parse_with_recovery("<a>one</b>two</a>");
+    assert_eq!(xot.to_string(root).unwrap(), "<a>onetwo</a>");
+    assert_eq!(diagnostics.len(), 1);
+    assert!(matches!(
+        diagnostics[0].error,
+        Error::InvalidCloseTag(_, _, _)
+    ));
+}
+
+#[test]
+fn test_parse_with_recovery_unknown_prefix() {
+    let mut xot = Xot::new();
+    let (root, diagnostics) = xot.parse_with_recovery(r#"<x:a y:b="1"></x:a>"#);
+    let doc_el = xot.document_element(root).unwrap();
+    assert_eq!(xot.element(doc_el).unwrap().name(), xot.name("a").unwrap());
+    assert_eq!(xot.get_attribute(doc_el, xot.name("b").unwrap()), Some("1"));
+    // the prefix is reported for the start tag, the attribute and the end
+    // tag
+    assert_eq!(diagnostics.len(), 3);
+    assert!(diagnostics
+        .iter()
+        .all(|d| matches!(d.error, Error::UnknownPrefix(_, _))));
+    assert_eq!(diagnostics[0].span, Span::new(1, 4));
+}
+
+#[test]
+fn test_parse_with_recovery_entity_expansion_limit() {
+    let mut xot = Xot::new();
+    let mut dtd = "<!ENTITY e0 'ha'>".to_string();
+    for i in 1..9 {
+        let references = format!("&e{};", i - 1).repeat(10);
+        dtd.push_str(&format!("<!ENTITY e{} '{}'>", i, references));
+    }
+    let xml = format!("<!DOCTYPE a [{}]><a><b>&e8;</b>after</a>", dtd);
+    assert!(matches!(
+        xot.parse(&xml),
+        Err(Error::LimitExceeded(ParseLimit::EntityExpansion, _))
+    ));
+    // we don't recover from a limit, but stop parsing there
+    let (root, diagnostics) = xot.parse_with_recovery(&xml);
+    let a = xot.document_element(root).unwrap();
+    assert_eq!(xot.to_string(a).unwrap(), "<a><b/></a>");
+    assert_eq!(diagnostics.len(), 3);
+    assert!(matches!(
+        diagnostics[0].error,
+        Error::LimitExceeded(ParseLimit::EntityExpansion, _)
+    ));
+    assert_eq!(&xml[diagnostics[0].span.range()], "&e8;");
+    assert!(matches!(diagnostics[1].error, Error::UnclosedTag(_)));
+    assert!(matches!(diagnostics[2].error, Error::UnclosedTag(_)));
+}
+
+#[test]
+fn test_parse_with_recovery_entities() {
+    let mut xot = Xot::new();
+    let (root, diagnostics) = xot.parse_with_recovery(r#"<a b="&x;">&y; &#xZZ; &amp; &z</a>"#);
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        r#"<a b="&amp;x;">&amp;y; &amp;#xZZ; &amp; &amp;z</a>"#
+    );
+    assert_eq!(diagnostics.len(), 4);
+    assert!(matches!(diagnostics[0].error, Error::InvalidEntity(_, _)));
+    assert_eq!(diagnostics[0].span, Span::new(6, 9));
+    assert!(matches!(diagnostics[1].error, Error::InvalidEntity(_, _)));
+    assert!(matches!(diagnostics[2].error, Error::InvalidEntity(_, _)));
+    assert!(matches!(diagnostics[3].error, Error::UnclosedEntity(_, _)));
+}
+
+#[test]
+fn test_parse_with_recovery_duplicate_attribute() {
+    let mut xot = Xot::new();
+    let (root, diagnostics) = xot.parse_with_recovery(r#"<a b="1" b="2"/>"#);
+    assert_eq!(xot.to_string(root).unwrap(), r#"<a b="1"/>"#);
+    assert_eq!(diagnostics.len(), 1);
+    assert!(matches!(
+        diagnostics[0].error,
+        Error::DuplicateAttribute(_, _)
+    ));
+}
+
+#[test]
+fn test_parse_with_recovery_duplicate_expanded_attribute() {
+    let mut xot = Xot::new();
+    // the undeclared prefix puts the first attribute in no namespace
+    let (root, diagnostics) = xot.parse_with_recovery(r#"<a x:b="1" b="2"/>"#);
+    assert_eq!(xot.to_string(root).unwrap(), r#"<a b="1"/>"#);
+    assert_eq!(diagnostics.len(), 2);
+    assert!(matches!(diagnostics[0].error, Error::UnknownPrefix(_, _)));
+    match &diagnostics[1].error {
+        Error::DuplicateExpandedAttribute(name, _) => assert_eq!(name, "b"),
+        error => panic!("unexpected error: {:?}", error),
+    }
+}
+
+#[test]
+fn test_parse_with_recovery_skip_markup() {
+    let mut xot = Xot::new();
+    let (root, diagnostics) = xot.parse_with_recovery("<a><b =></b><c/></a>");
+    assert_eq!(xot.to_string(root).unwrap(), "<a><b/><c/></a>");
+    assert_eq!(diagnostics.len(), 1);
+    assert!(matches!(diagnostics[0].error, Error::Parser(_)));
+    assert_eq!(diagnostics[0].span, Span::new(5, 8));
+}
+
+#[test]
+fn test_parse_with_recovery_after_document_element() {
+    let mut xot = Xot::new();
+    let (root, diagnostics) = xot.parse_with_recovery("<a/><b/>");
+    assert_eq!(xot.to_string(root).unwrap(), "<a/>");
+    assert_eq!(diagnostics.len(), 1);
+    assert!(matches!(diagnostics[0].error, Error::Parser(_)));
+    assert_eq!(diagnostics[0].span, Span::new(4, 8));
+}
+
+#[test]
+fn test_parse_with_recovery_skip_markup_multiline() {
+    let mut xot = Xot::new();
+    let (root, diagnostics) = xot.parse_with_recovery("<a>\n<b =></b></a>");
+    assert_eq!(xot.to_string(root).unwrap(), "<a>\n<b/></a>");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].span, Span::new(6, 9));
+}
+
+#[test]
+fn test_parse_with_recovery_text_after_document_element() {
+    let mut xot = Xot::new();
+    let (root, diagnostics) = xot.parse_with_recovery("<a></b></a> junk");
+    assert_eq!(xot.to_string(root).unwrap(), "<a/>");
+    assert_eq!(diagnostics.len(), 2);
+    assert!(matches!(
+        diagnostics[0].error,
+        Error::InvalidCloseTag(_, _, _)
+    ));
+    assert!(matches!(diagnostics[1].error, Error::Parser(_)));
+    assert_eq!(diagnostics[1].span, Span::new(12, 16));
+}
+
+fn parse_with_limits(xml: &str, limits: ParseLimits) -> Result<xot::Node, Error> {
+    let mut xot = Xot::new();
+    xot.parse_with_options(
+        xml,
+        ParseOptions {
+            limits,
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+fn test_limits_default_unlimited() {
+    assert!(parse_with_limits("<a><b><c x='1'>text</c></b></a>", ParseLimits::default()).is_ok());
+}
+
+#[test]
+fn test_limit_depth() {
+    let limits = ParseLimits {
+        max_depth: Some(2),
+        ..Default::default()
+    };
+    assert!(parse_with_limits("<a><b/><b/></a>", limits.clone()).is_ok());
+    let err = parse_with_limits("<a><b><c/></b></a>", limits).unwrap_err();
+    assert!(matches!(err, Error::LimitExceeded(ParseLimit::Depth, _)));
+    assert_eq!(err.span(), Some(Span::new(7, 8)));
+}
+
+#[test]
+fn test_limit_attributes() {
+    let limits = ParseLimits {
+        max_attributes: Some(2),
+        ..Default::default()
+    };
+    assert!(parse_with_limits(r#"<a x="1" y="2"><b z="3"/></a>"#, limits.clone()).is_ok());
+    let err = parse_with_limits(r#"<a xmlns:p="urn:p" x="1" y="2"/>"#, limits).unwrap_err();
+    assert!(matches!(
+        err,
+        Error::LimitExceeded(ParseLimit::Attributes, _)
+    ));
+    assert_eq!(err.span(), Some(Span::new(25, 30)));
+}
+
+#[test]
+fn test_limit_text_length() {
+    let limits = ParseLimits {
+        max_text_length: Some(4),
+        ..Default::default()
+    };
+    assert!(parse_with_limits(r#"<a x="abcd">abcd</a>"#, limits.clone()).is_ok());
+    let err = parse_with_limits(r#"<a x="abcde"/>"#, limits.clone()).unwrap_err();
+    assert!(matches!(
+        err,
+        Error::LimitExceeded(ParseLimit::TextLength, _)
+    ));
+    assert_eq!(err.span(), Some(Span::new(6, 11)));
+    // consolidated text counts as a whole
+    let err = parse_with_limits("<a>abc<![CDATA[de]]></a>", limits).unwrap_err();
+    assert!(matches!(
+        err,
+        Error::LimitExceeded(ParseLimit::TextLength, _)
+    ));
+}
+
+#[test]
+fn test_limit_text_length_after_expansion() {
+    let limits = ParseLimits {
+        max_text_length: Some(4),
+        ..Default::default()
+    };
+    // the length of the expanded value counts
+    assert!(parse_with_limits(
+        r#"<a x="&amp;&amp;&amp;&amp;">&lt;&lt;&lt;&lt;</a>"#,
+        limits.clone()
+    )
+    .is_ok());
+    let err =
+        parse_with_limits(r#"<!DOCTYPE a [<!ENTITY e "abc">]><a>&e;&e;</a>"#, limits).unwrap_err();
+    assert!(matches!(
+        err,
+        Error::LimitExceeded(ParseLimit::TextLength, _)
+    ));
+}
+
+#[test]
+fn test_limit_text_length_entity_bomb() {
+    let mut dtd = r#"<!ENTITY e0 "lol">"#.to_string();
+    for i in 1..=6 {
+        let references = format!("&e{};", i - 1).repeat(10);
+        dtd.push_str(&format!(r#"<!ENTITY e{} "{}">"#, i, references));
+    }
+    let limits = ParseLimits {
+        max_text_length: Some(100),
+        ..Default::default()
+    };
+    let xml = format!(r#"<!DOCTYPE a [{}]><a x="&e6;"/>"#, dtd);
+    let err = parse_with_limits(&xml, limits.clone()).unwrap_err();
+    assert!(matches!(
+        err,
+        Error::LimitExceeded(ParseLimit::TextLength, _)
+    ));
+    assert_eq!(&xml[err.span().unwrap().range()], "&e6;");
+    let xml = format!(r#"<!DOCTYPE a [{}]><a>&e6;</a>"#, dtd);
+    let err = parse_with_limits(&xml, limits).unwrap_err();
+    assert!(matches!(
+        err,
+        Error::LimitExceeded(ParseLimit::TextLength, _)
+    ));
+    assert_eq!(&xml[err.span().unwrap().range()], "&e6;");
+}
+
+#[test]
+fn test_limit_names() {
+    let limits = ParseLimits {
+        max_names: Some(2),
+        ..Default::default()
+    };
+    assert!(parse_with_limits("<a><b/><a/><b/></a>", limits.clone()).is_ok());
+    let err = parse_with_limits("<a><b/><c/></a>", limits).unwrap_err();
+    assert!(matches!(err, Error::LimitExceeded(ParseLimit::Names, _)));
+    assert_eq!(err.span(), Some(Span::new(8, 9)));
+}
+
+#[test]
+fn test_limit_names_already_known() {
+    let mut xot = Xot::new();
+    xot.add_name("a");
+    xot.add_name("b");
+    let options = ParseOptions {
+        limits: ParseLimits {
+            max_names: Some(0),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(xot.parse_with_options("<a><b/></a>", options).is_ok());
+}
+
+#[test]
+fn test_limit_namespaces_and_prefixes() {
+    let limits = ParseLimits {
+        max_namespaces: Some(1),
+        ..Default::default()
+    };
+    assert!(
+        parse_with_limits(r#"<a xmlns="urn:a"><b xmlns="urn:a"/></a>"#, limits.clone()).is_ok()
+    );
+    let err = parse_with_limits(r#"<a xmlns="urn:a"><b xmlns="urn:b"/></a>"#, limits).unwrap_err();
+    assert!(matches!(
+        err,
+        Error::LimitExceeded(ParseLimit::Namespaces, _)
+    ));
+
+    let limits = ParseLimits {
+        max_prefixes: Some(1),
+        ..Default::default()
+    };
+    let err = parse_with_limits(r#"<a xmlns:p="urn:a" xmlns:q="urn:a"/>"#, limits).unwrap_err();
+    assert!(matches!(err, Error::LimitExceeded(ParseLimit::Prefixes, _)));
+}
+
+#[test]
+fn test_limit_nodes() {
+    let limits = ParseLimits {
+        max_nodes: Some(4),
+        ..Default::default()
+    };
+    assert!(parse_with_limits(r#"<a x="1"><b/>text</a>"#, limits.clone()).is_ok());
+    let err = parse_with_limits(r#"<a x="1"><b/>text<!--c--></a>"#, limits).unwrap_err();
+    assert!(matches!(err, Error::LimitExceeded(ParseLimit::Nodes, _)));
+    assert_eq!(err.span(), Some(Span::new(17, 25)));
+}
+
+#[test]
+fn test_limits_push_parser() {
+    let mut xot = Xot::new();
+    let mut parser = xot
+        .push_parser_with_options(ParseOptions {
+            limits: ParseLimits {
+                max_depth: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+    let result = parser
+        .feed_str("<a><b>")
+        .and_then(|_| parser.feed_str("</b></a>"))
+        .and_then(|_| parser.finish());
+    assert!(matches!(
+        result,
+        Err(Error::LimitExceeded(ParseLimit::Depth, _))
+    ));
+}
+
+#[test]
+fn test_duplicate_expanded_attribute() {
+    let mut xot = Xot::new();
+    let xml = r#"<a xmlns:p="urn:x" xmlns:q="urn:x" p:b="1" q:b="2"/>"#;
+    let err = xot.parse(xml).unwrap_err();
+    assert!(matches!(err, Error::DuplicateExpandedAttribute(_, _)));
+    assert_eq!(err.span(), Some(Span::new(43, 46)));
+}
+
+#[test]
+fn test_same_local_name_different_namespaces() {
+    let mut xot = Xot::new();
+    let xml = r#"<a xmlns:p="urn:x" xmlns:q="urn:y" b="0" p:b="1" q:b="2"/>"#;
+    assert!(xot.parse(xml).is_ok());
+}
+
+#[test]
+fn test_rebind_xml_prefix() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(r#"<a xmlns:xml="http://example.com"/>"#)
+        .unwrap_err();
+    assert!(matches!(err, Error::ReservedPrefix(_, _)));
+    assert_eq!(err.span(), Some(Span::new(3, 12)));
+    // binding it to the XML namespace is allowed
+    assert!(xot
+        .parse(r#"<a xmlns:xml="http://www.w3.org/XML/1998/namespace"/>"#)
+        .is_ok());
+}
+
+#[test]
+fn test_declare_xmlns_prefix() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(r#"<a xmlns:xmlns="http://www.w3.org/2000/xmlns/"/>"#)
+        .unwrap_err();
+    assert!(matches!(err, Error::ReservedPrefix(_, _)));
+}
+
+#[test]
+fn test_bind_xml_namespace_to_other_prefix() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse(r#"<a xmlns:x="http://www.w3.org/XML/1998/namespace"/>"#)
+        .unwrap_err();
+    assert!(matches!(err, Error::ReservedNamespace(_, _)));
+    assert_eq!(err.span(), Some(Span::new(12, 48)));
+    let err = xot
+        .parse(r#"<a xmlns="http://www.w3.org/XML/1998/namespace"/>"#)
+        .unwrap_err();
+    assert!(matches!(err, Error::ReservedNamespace(_, _)));
+    let err = xot
+        .parse(r#"<a xmlns:x="http://www.w3.org/2000/xmlns/"/>"#)
+        .unwrap_err();
+    assert!(matches!(err, Error::ReservedNamespace(_, _)));
+}
+
+#[test]
+fn test_empty_prefixed_namespace() {
+    let mut xot = Xot::new();
+    let err = xot.parse(r#"<a xmlns:p=""/>"#).unwrap_err();
+    assert!(matches!(err, Error::EmptyPrefixedNamespace(_, _)));
+    assert_eq!(err.span(), Some(Span::new(12, 12)));
+    // undeclaring the default namespace is fine
+    assert!(xot.parse(r#"<a xmlns="urn:x"><b xmlns=""/></a>"#).is_ok());
+}
+
+#[test]
+fn test_parse_with_recovery_namespace_errors() {
+    let mut xot = Xot::new();
+    let (root, diagnostics) = xot
+        .parse_with_recovery(r#"<a xmlns:p="" xmlns:q="urn:x" xmlns:r="urn:x" q:b="1" r:b="2"/>"#);
+    assert_eq!(
+        xot.to_string(root).unwrap(),
+        r#"<a xmlns:q="urn:x" xmlns:r="urn:x" r:b="1"/>"#
+    );
+    assert_eq!(diagnostics.len(), 2);
+    assert!(matches!(
+        diagnostics[0].error,
+        Error::EmptyPrefixedNamespace(_, _)
+    ));
+    assert!(matches!(
+        diagnostics[1].error,
+        Error::DuplicateExpandedAttribute(_, _)
+    ));
+}
+
+fn utf16(s: &str, big_endian: bool, bom: bool) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let units = bom.then_some(0xFEFF).into_iter().chain(s.encode_utf16());
+    for unit in units {
+        if big_endian {
+            bytes.extend(unit.to_be_bytes());
+        } else {
+            bytes.extend(unit.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+#[test]
+fn test_parse_bytes_strict_utf16() {
+    let mut xot = Xot::new();
+    let xml = r#"<?xml version="1.0" encoding="UTF-16"?><p>ĳ</p>"#;
+    for (big_endian, bom, name) in [
+        (false, true, "UTF-16LE"),
+        (true, true, "UTF-16BE"),
+        (false, false, "UTF-16LE"),
+        (true, false, "UTF-16BE"),
+    ] {
+        let document = xot
+            .parse_bytes_strict(&utf16(xml, big_endian, bom), None)
+            .unwrap();
+        let doc_el = xot.document_element(document).unwrap();
+        assert_eq!(xot.text_content_str(doc_el), Some("ĳ"));
+        assert_eq!(
+            xot.document_metadata(document)
+                .unwrap()
+                .detected_encoding
+                .as_deref(),
+            Some(name)
+        );
+    }
+}
+
+#[test]
+fn test_parse_bytes_strict_bom_over_hint() {
+    let mut xot = Xot::new();
+    let document = xot
+        .parse_bytes_strict(&utf16("<p/>", true, true), Some("ISO-8859-1"))
+        .unwrap();
+    assert_eq!(xot.to_string(document).unwrap(), "<p/>");
+}
+
+#[test]
+fn test_parse_bytes_strict_malformed() {
+    let mut xot = Xot::new();
+    let err = xot
+        .parse_bytes_strict(b"<p>ok</p>\n<!-- \xff -->", None)
+        .unwrap_err();
+    assert!(matches!(err, Error::MalformedEncoding(ref name, 15) if name == "UTF-8"));
+    // with a BOM, the offset still counts from the start of the bytes
+    let err = xot
+        .parse_bytes_strict(b"\xef\xbb\xbf<p>\xff</p>", None)
+        .unwrap_err();
+    assert!(matches!(err, Error::MalformedEncoding(_, 6)));
+}
+
+#[test]
+fn test_parse_bytes_unsupported_encoding() {
+    let mut xot = Xot::new();
+    let xml = br#"<?xml version="1.0" encoding="unknown"?><p/>"#;
+    assert!(matches!(
+        xot.parse_bytes(xml),
+        Err(Error::UnsupportedEncoding(ref label)) if label == "unknown"
+    ));
+    assert!(matches!(
+        xot.parse_bytes_strict(xml, None),
+        Err(Error::UnsupportedEncoding(_))
+    ));
+    assert!(matches!(
+        xot.parse_bytes_strict(b"<p/>", Some("unknown")),
+        Err(Error::UnsupportedEncoding(_))
+    ));
+}
@@ -0,0 +1,190 @@
+use xot::{Error, Event, Xot};
+
+fn events(xot: &mut Xot, xml: &str) -> Result<Vec<Event>, Error> {
+    xot.reader(xml).collect()
+}
+
+#[test]
+fn test_reader_events() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let doc = xot.add_name("doc");
+    let a = xot.add_name("a");
+    let pi = xot.add_name("pi");
+    let events = events(
+        &mut xot,
+        r#"<?xml version="1.0"?><doc a="1 &amp; 2"><!--c--><?pi data?>x<![CDATA[<y>]]></doc>"#,
+    )?;
+    assert_eq!(
+        events,
+        vec![
+            Event::ElementStart {
+                name: doc,
+                namespaces: vec![],
+                attributes: vec![(a, "1 & 2".to_string())],
+            },
+            Event::Comment("c".to_string()),
+            Event::ProcessingInstruction {
+                target: pi,
+                data: Some("data".to_string()),
+            },
+            Event::Text("x".to_string()),
+            Event::Text("<y>".to_string()),
+            Event::ElementEnd { name: doc },
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_reader_empty_element() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let a = xot.add_name("a");
+    let b = xot.add_name("b");
+    let events = events(&mut xot, "<a><b/></a>")?;
+    assert_eq!(
+        events,
+        vec![
+            Event::ElementStart {
+                name: a,
+                namespaces: vec![],
+                attributes: vec![],
+            },
+            Event::ElementStart {
+                name: b,
+                namespaces: vec![],
+                attributes: vec![],
+            },
+            Event::ElementEnd { name: b },
+            Event::ElementEnd { name: a },
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_reader_namespaces() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let ns = xot.add_namespace("http://example.com");
+    let p = xot.add_prefix("p");
+    let a = xot.add_name_ns("a", ns);
+    let b = xot.add_name_ns("b", ns);
+    let c = xot.add_name("c");
+    let events = events(
+        &mut xot,
+        r#"<p:a xmlns:p="http://example.com" p:b="1" c="2"><p:b/></p:a>"#,
+    )?;
+    assert_eq!(
+        events[0],
+        Event::ElementStart {
+            name: a,
+            namespaces: vec![(p, ns)],
+            attributes: vec![(b, "1".to_string()), (c, "2".to_string())],
+        }
+    );
+    assert_eq!(
+        events[1],
+        Event::ElementStart {
+            name: b,
+            namespaces: vec![],
+            attributes: vec![],
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_reader_dtd_entities() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let events = events(
+        &mut xot,
+        r#"<!DOCTYPE doc [<!ENTITY e "entity">]><doc>&e;</doc>"#,
+    )?;
+    assert_eq!(events[1], Event::Text("entity".to_string()));
+    Ok(())
+}
+
+#[test]
+fn test_reader_event_count() {
+    let mut xot = Xot::new();
+    let count = xot.reader("<doc><a>1</a><a>2</a></doc>").count();
+    assert_eq!(count, 8);
+}
+
+#[test]
+fn test_reader_xot_access() {
+    let mut xot = Xot::new();
+    let mut reader = xot.reader(r#"<x:doc xmlns:x="http://example.com"/>"#);
+    let Some(Ok(Event::ElementStart { name, .. })) = reader.next() else {
+        panic!("expected element start");
+    };
+    assert_eq!(
+        reader.xot().name_ns_str(name),
+        ("doc", "http://example.com")
+    );
+}
+
+#[test]
+fn test_reader_unknown_prefix() {
+    let mut xot = Xot::new();
+    let err = events(&mut xot, "<a><foo:b/></a>").unwrap_err();
+    assert_eq!(err.to_string(), "Unknown prefix: foo at 4..9");
+}
+
+#[test]
+fn test_reader_invalid_close_tag() {
+    let mut xot = Xot::new();
+    let err = events(&mut xot, "<a><b></a>").unwrap_err();
+    assert!(matches!(err, Error::InvalidCloseTag(..)));
+}
+
+#[test]
+fn test_reader_unclosed_tag() {
+    let mut xot = Xot::new();
+    let xml = "<a><b>";
+    let err = events(&mut xot, xml).unwrap_err();
+    assert!(matches!(err, Error::UnclosedTag(_)));
+    assert_eq!(&xml[err.span().unwrap().range()], "b");
+}
+
+#[test]
+fn test_reader_duplicate_attributes() {
+    let mut xot = Xot::new();
+    let err = events(&mut xot, r#"<a b="1" b="2"/>"#).unwrap_err();
+    assert!(matches!(err, Error::DuplicateAttribute(..)));
+    let err = events(
+        &mut xot,
+        r#"<a xmlns:p="http://example.com" xmlns:q="http://example.com" p:b="1" q:b="2"/>"#,
+    )
+    .unwrap_err();
+    match err {
+        Error::DuplicateExpandedAttribute(name, _) => assert_eq!(name, "q:b"),
+        error => panic!("unexpected error: {:?}", error),
+    }
+}
+
+#[test]
+fn test_reader_reserved_prefix() {
+    let mut xot = Xot::new();
+    let err = events(&mut xot, r#"<a xmlns:xmlns="http://example.com"/>"#).unwrap_err();
+    assert!(matches!(err, Error::ReservedPrefix(..)));
+}
+
+#[test]
+fn test_reader_stops_after_error() {
+    let mut xot = Xot::new();
+    let mut reader = xot.reader("<a>&unknown;<b/></a>");
+    assert!(matches!(
+        reader.next(),
+        Some(Ok(Event::ElementStart { .. }))
+    ));
+    assert!(matches!(reader.next(), Some(Err(Error::InvalidEntity(..)))));
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn test_reader_xml11() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let events = events(&mut xot, "<?xml version=\"1.1\"?><a>1\u{85}2&#x1;</a>")?;
+    assert_eq!(events[1], Event::Text("1\n2\u{1}".to_string()));
+    Ok(())
+}
@@ -1,4 +1,6 @@
-use xot::Xot;
+use xot::output;
+use xot::output::xml::DocType;
+use xot::{Error, ParseOptions, Xot};
 
 #[test]
 fn test_serialize_node() {
@@ -194,3 +196,283 @@ fn test_weird_delimiter_unescaped() {
         .unwrap();
     assert_eq!(serialized, r#"<a>]]&gt;</a>"#);
 }
+
+#[test]
+fn test_serialize_parsed_doctype_overridden() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse(r#"<!DOCTYPE doc SYSTEM "old.dtd"><doc/>"#)
+        .unwrap();
+    let xml = xot
+        .serialize_xml_string(
+            output::xml::Parameters {
+                doctype: Some(DocType::System {
+                    system: "new.dtd".to_string(),
+                }),
+                ..Default::default()
+            },
+            doc,
+        )
+        .unwrap();
+    assert_eq!(xml, "<!DOCTYPE doc SYSTEM \"new.dtd\">\n<doc/>");
+}
+
+#[test]
+fn test_serialize_parsed_doctype_removed() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse(r#"<!DOCTYPE doc SYSTEM "doc.dtd"><doc/>"#)
+        .unwrap();
+    xot.remove_document_type(doc);
+    assert_eq!(xot.to_string(doc).unwrap(), "<doc/>");
+}
+
+#[test]
+fn test_serialize_parsed_doctype_not_for_element() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse(r#"<!DOCTYPE doc SYSTEM "doc.dtd"><doc/>"#)
+        .unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    assert_eq!(xot.to_string(doc_el).unwrap(), "<doc/>");
+}
+
+#[test]
+fn test_serialize_cloned_doctype() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse(r#"<!DOCTYPE doc SYSTEM "doc.dtd"><doc/>"#)
+        .unwrap();
+    let cloned = xot.clone(doc);
+    assert_eq!(
+        xot.to_string(cloned).unwrap(),
+        "<!DOCTYPE doc SYSTEM \"doc.dtd\">\n<doc/>"
+    );
+}
+
+#[test]
+fn test_serialize_parsed_declaration_overridden() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse(r#"<?xml version="1.0" standalone="yes"?><doc/>"#)
+        .unwrap();
+    let xml = xot
+        .serialize_xml_string(
+            output::xml::Parameters {
+                declaration: Some(Default::default()),
+                ..Default::default()
+            },
+            doc,
+        )
+        .unwrap();
+    assert_eq!(xml, "<?xml version=\"1.0\"?>\n<doc/>");
+}
+
+#[test]
+fn test_serialize_parsed_declaration_other_encoding() {
+    // output is UTF-8, so we don't want to claim it's in another encoding
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse_bytes(b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><doc>\xe9</doc>")
+        .unwrap();
+    assert_eq!(
+        xot.to_string(doc).unwrap(),
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<doc>\u{e9}</doc>"
+    );
+}
+
+fn encoding_parameters(encoding: &str) -> output::xml::Parameters {
+    output::xml::Parameters {
+        declaration: Some(output::xml::Declaration {
+            encoding: Some(encoding.to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_serialize_encoding_latin1() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse("<doc a=\"caf\u{e9} \u{20ac}\">caf\u{e9} \u{20ac}<![CDATA[\u{e9}\u{20ac}]]></doc>")
+        .unwrap();
+    let mut buf = Vec::new();
+    xot.serialize_xml_write(encoding_parameters("ISO-8859-1"), doc, &mut buf)
+        .unwrap();
+    assert_eq!(
+        buf,
+        b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<doc a=\"caf\xe9 &#x20AC;\">caf\xe9 &#x20AC;\xe9&#x20AC;</doc>"
+    );
+}
+
+#[test]
+fn test_serialize_encoding_cdata_section() {
+    let mut xot = Xot::new();
+    let doc = xot.parse("<doc>\u{e9}\u{20ac}</doc>").unwrap();
+    let mut parameters = encoding_parameters("ISO-8859-1");
+    parameters.cdata_section_elements = vec![xot.name("doc").unwrap()];
+    let mut buf = Vec::new();
+    xot.serialize_xml_write(parameters, doc, &mut buf).unwrap();
+    assert_eq!(
+        buf,
+        b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<doc><![CDATA[\xe9]]>&#x20AC;</doc>"
+    );
+}
+
+#[test]
+fn test_serialize_encoding_shift_jis() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse("<\u{6587}\u{66f8}>\u{3042}\u{e9}</\u{6587}\u{66f8}>")
+        .unwrap();
+    let mut buf = Vec::new();
+    xot.serialize_xml_write(encoding_parameters("Shift_JIS"), doc, &mut buf)
+        .unwrap();
+    assert_eq!(
+        buf,
+        b"<?xml version=\"1.0\" encoding=\"Shift_JIS\"?>\n<\x95\xb6\x8f\x91>\x82\xa0&#xE9;</\x95\xb6\x8f\x91>"
+    );
+}
+
+#[test]
+fn test_serialize_encoding_string() {
+    let mut xot = Xot::new();
+    let doc = xot.parse("<doc>\u{e9}\u{20ac}</doc>").unwrap();
+    assert_eq!(
+        xot.serialize_xml_string(encoding_parameters("latin1"), doc)
+            .unwrap(),
+        "<?xml version=\"1.0\" encoding=\"latin1\"?>\n<doc>\u{e9}&#x20AC;</doc>"
+    );
+}
+
+#[test]
+fn test_serialize_encoding_unencodable_comment() {
+    let mut xot = Xot::new();
+    let doc = xot.parse("<doc><!--\u{20ac}--></doc>").unwrap();
+    let mut buf = Vec::new();
+    let err = xot
+        .serialize_xml_write(encoding_parameters("ISO-8859-1"), doc, &mut buf)
+        .unwrap_err();
+    assert!(matches!(err, Error::UnencodableCharacter('\u{20ac}', _)));
+}
+
+#[test]
+fn test_serialize_encoding_unencodable_name() {
+    let mut xot = Xot::new();
+    let doc = xot.parse("<\u{3042}/>").unwrap();
+    let err = xot
+        .serialize_xml_string(encoding_parameters("ISO-8859-1"), doc)
+        .unwrap_err();
+    assert!(matches!(err, Error::UnencodableCharacter('\u{3042}', _)));
+}
+
+#[test]
+fn test_serialize_encoding_unsupported() {
+    let mut xot = Xot::new();
+    let doc = xot.parse("<doc/>").unwrap();
+    let err = xot
+        .serialize_xml_string(encoding_parameters("UTF-16"), doc)
+        .unwrap_err();
+    assert!(matches!(err, Error::UnsupportedEncoding(_)));
+}
+
+#[test]
+fn test_serialize_parsed_declaration_removed() {
+    let mut xot = Xot::new();
+    let doc = xot.parse(r#"<?xml version="1.0"?><doc/>"#).unwrap();
+    xot.remove_document_metadata(doc);
+    assert_eq!(xot.to_string(doc).unwrap(), "<doc/>");
+}
+
+#[test]
+fn test_serialize_cdata_roundtrip() {
+    let mut xot = Xot::new();
+    let xml = "<doc>a &amp; b<![CDATA[<c> & ]]>&lt;d&gt;<![CDATA[e]]></doc>";
+    let doc = xot
+        .parse_with_options(
+            xml,
+            ParseOptions {
+                cdata_boundaries: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(xot.to_string(doc).unwrap(), xml);
+}
+
+#[test]
+fn test_serialize_cdata_consolidated_by_default() {
+    let mut xot = Xot::new();
+    let doc = xot.parse("<doc>a<![CDATA[<b>]]></doc>").unwrap();
+    assert_eq!(xot.to_string(doc).unwrap(), "<doc>a&lt;b&gt;</doc>");
+}
+
+#[test]
+fn test_serialize_text_set_cdata() {
+    let mut xot = Xot::new();
+    let doc = xot.parse("<doc>a<![CDATA[<b>]]></doc>").unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    let text = xot.first_child(doc_el).unwrap();
+    xot.text_mut(text).unwrap().set_cdata(true);
+    assert_eq!(xot.to_string(doc).unwrap(), "<doc><![CDATA[a<b>]]></doc>");
+    xot.text_mut(text).unwrap().set_cdata(false);
+    assert_eq!(xot.to_string(doc).unwrap(), "<doc>a&lt;b&gt;</doc>");
+}
+
+#[test]
+fn test_append_text_after_cdata_not_consolidated() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse_with_options(
+            "<doc><![CDATA[x<y]]></doc>",
+            ParseOptions {
+                cdata_boundaries: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    xot.append_text(doc_el, " & more").unwrap();
+    assert_eq!(xot.children(doc_el).count(), 2);
+    assert_eq!(
+        xot.to_string(doc).unwrap(),
+        "<doc><![CDATA[x<y]]> &amp; more</doc>"
+    );
+}
+
+#[test]
+fn test_remove_between_text_and_cdata_not_consolidated() {
+    let mut xot = Xot::new();
+    let doc = xot
+        .parse_with_options(
+            "<doc>a &lt; b<!--c--><![CDATA[x<y]]></doc>",
+            ParseOptions {
+                cdata_boundaries: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    let comment = xot.children(doc_el).nth(1).unwrap();
+    xot.remove(comment).unwrap();
+    assert_eq!(xot.children(doc_el).count(), 2);
+    assert_eq!(
+        xot.to_string(doc).unwrap(),
+        "<doc>a &lt; b<![CDATA[x<y]]></doc>"
+    );
+}
+
+#[test]
+fn test_serialize_cdata_end_delimiter() {
+    let mut xot = Xot::new();
+    let doc = xot.parse("<doc/>").unwrap();
+    let doc_el = xot.document_element(doc).unwrap();
+    let text = xot.new_text("a]]>b");
+    xot.append(doc_el, text).unwrap();
+    xot.text_mut(text).unwrap().set_cdata(true);
+    assert_eq!(
+        xot.to_string(doc).unwrap(),
+        "<doc><![CDATA[a]]]]><![CDATA[>b]]></doc>"
+    );
+}
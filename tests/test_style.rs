@@ -1,5 +1,5 @@
 use xot::{
-    output::{Output, TokenSerializeParameters},
+    output::{Indentation, Output, TokenSerializeParameters},
     Error, Xot,
 };
 
@@ -18,11 +18,11 @@ fn test_style() -> Result<(), Error> {
     // now we unwrap the style
     xot.element_unwrap(style).unwrap();
 
-    let suppress = vec![];
+    let indentation = Indentation::default();
     let pretty_tokens = xot.pretty_tokens(
         root,
         TokenSerializeParameters::default(),
-        &suppress,
+        &indentation,
         xot::output::NoopNormalizer,
     );
 
@@ -77,12 +77,12 @@ fn test_style_element() -> Result<(), Error> {
     let a = xot.first_child(root).unwrap();
     let b = xot.first_child(a).unwrap();
 
-    let suppress = vec![];
+    let indentation = Indentation::default();
 
     let pretty_tokens = xot.pretty_tokens(
         root,
         TokenSerializeParameters::default(),
-        &suppress,
+        &indentation,
         xot::output::NoopNormalizer,
     );
 
@@ -141,11 +141,11 @@ fn test_style_attribute() -> Result<(), Error> {
     let root = xot.parse(r#"<doc a="A" b="B"/>"#)?;
     let name_a = xot.add_name("a");
 
-    let suppress = vec![];
+    let indentation = Indentation::default();
     let pretty_tokens = xot.pretty_tokens(
         root,
         TokenSerializeParameters::default(),
-        &suppress,
+        &indentation,
         xot::output::NoopNormalizer,
     );
 
@@ -200,3 +200,37 @@ fn test_style_attribute() -> Result<(), Error> {
     );
     Ok(())
 }
+
+#[test]
+fn test_pretty_tokens_wrap_attributes() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let root = xot.parse(r#"<doc a="A" b="B"><p/></doc>"#)?;
+
+    let indentation = Indentation {
+        indent: "\t".to_string(),
+        max_width: Some(10),
+        wrap_attributes: true,
+        ..Default::default()
+    };
+    let pretty_tokens = xot.pretty_tokens(
+        root,
+        TokenSerializeParameters::default(),
+        &indentation,
+        xot::output::NoopNormalizer,
+    );
+
+    let mut result = String::new();
+    for (_node, _output, token) in pretty_tokens {
+        result.push_str(&indentation.indent.repeat(token.indentation));
+        if token.space {
+            result.push(' ');
+        }
+        result.push_str(&token.text);
+        if token.newline {
+            result.push('\n');
+        }
+    }
+
+    assert_eq!(result, "<doc\n\ta=\"A\"\n\tb=\"B\">\n\t<p/>\n</doc>\n");
+    Ok(())
+}
@@ -0,0 +1,234 @@
+use xot::{Error, Xot};
+
+#[test]
+fn test_tree_builder() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let mut builder = xot.tree_builder();
+    builder.comment("before")?;
+    builder.start_element("", "doc")?;
+    builder.attribute("", "a", "<&>")?;
+    builder.start_element("", "p")?;
+    builder.text("a")?;
+    builder.text("b")?;
+    builder.end_element()?;
+    builder.processing_instruction("pi", Some("data"))?;
+    builder.end_element()?;
+    builder.text("\n")?;
+    let document = builder.finish()?;
+    assert_eq!(
+        xot.to_string(document)?,
+        r#"<!--before--><doc a="&lt;&amp;>"><p>ab</p><?pi data?></doc>"#
+    );
+    let doc_el = xot.document_element(document)?;
+    let p = xot.first_child(doc_el).unwrap();
+    // adjacent text is consolidated
+    assert_eq!(xot.children(p).count(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_tree_builder_namespaces() -> Result<(), Error> {
+    let mut xot = Xot::new();
+    let ns = xot.add_namespace("http://example.com");
+    let other = xot.add_namespace("http://example.com/other");
+    let a = xot.add_name_ns("a", ns);
+    let b = xot.add_name_ns("b", other);
+    let c = xot.add_name_ns("c", other);
+    let mut builder = xot.tree_builder();
+    builder.start_element("", "a")?;
+    builder.namespace("", "http://example.com")?;
+    builder.namespace("o", "http://example.com/other")?;
+    builder.start_element("o", "b")?;
+    builder.attribute("o", "c", "1")?;
+    builder.end_element()?;
+    builder.end_element()?;
+    let document = builder.finish()?;
+
+    let a_el = xot.document_element(document)?;
+    assert_eq!(xot.element(a_el).unwrap().name(), a);
+    let b_el = xot.first_child(a_el).unwrap();
+    assert_eq!(xot.element(b_el).unwrap().name(), b);
+    assert_eq!(xot.get_attribute(b_el, c), Some("1"));
+    Ok(())
+}
+
+#[test]
+fn test_tree_builder_unknown_prefix() {
+    let mut xot = Xot::new();
+    let mut builder = xot.tree_builder();
+    builder.start_element("x", "a").unwrap();
+    let err = builder.end_element().unwrap_err();
+    // there's no source text for a span to refer to
+    assert!(matches!(err, Error::UnknownPrefix(_, None)));
+}
+
+#[test]
+fn test_tree_builder_namespace_constraints() {
+    let mut xot = Xot::new();
+    let mut builder = xot.tree_builder();
+    builder.start_element("", "a").unwrap();
+    let err = builder
+        .namespace("xmlns", "http://example.com")
+        .unwrap_err();
+    assert!(matches!(err, Error::ReservedPrefix(_, None)));
+    let err = builder.namespace("p", "").unwrap_err();
+    assert!(matches!(err, Error::EmptyPrefixedNamespace(_, None)));
+}
+
+#[test]
+fn test_tree_builder_duplicate_attribute() {
+    let mut xot = Xot::new();
+    let mut builder = xot.tree_builder();
+    builder.start_element("", "a").unwrap();
+    builder.attribute("", "b", "1").unwrap();
+    let err = builder.attribute("", "b", "2").unwrap_err();
+    assert!(matches!(err, Error::DuplicateAttribute(_, None)));
+
+    let mut builder = xot.tree_builder();
+    builder.start_element("", "a").unwrap();
+    builder.namespace("p", "http://example.com").unwrap();
+    builder.namespace("q", "http://example.com").unwrap();
+    builder.attribute("p", "b", "1").unwrap();
+    builder.attribute("q", "b", "2").unwrap();
+    let err = builder.end_element().unwrap_err();
+    assert!(matches!(err, Error::DuplicateExpandedAttribute(_, None)));
+}
+
+#[test]
+fn test_tree_builder_attribute_outside_start_tag() {
+    let mut xot = Xot::new();
+    let mut builder = xot.tree_builder();
+    let err = builder.attribute("", "b", "1").unwrap_err();
+    assert!(matches!(err, Error::InvalidOperation(_)));
+    builder.start_element("", "a").unwrap();
+    builder.text("text").unwrap();
+    let err = builder.namespace("p", "http://example.com").unwrap_err();
+    assert!(matches!(err, Error::InvalidOperation(_)));
+}
+
+#[test]
+fn test_tree_builder_structure_errors() {
+    let mut xot = Xot::new();
+    let mut builder = xot.tree_builder();
+    assert!(matches!(
+        builder.text("text"),
+        Err(Error::InvalidOperation(_))
+    ));
+    assert!(matches!(
+        builder.end_element(),
+        Err(Error::InvalidOperation(_))
+    ));
+    builder.start_element("", "a").unwrap();
+    builder.end_element().unwrap();
+    assert!(matches!(
+        builder.start_element("", "b"),
+        Err(Error::InvalidOperation(_))
+    ));
+
+    let mut builder = xot.tree_builder();
+    builder.start_element("", "a").unwrap();
+    assert!(matches!(builder.finish(), Err(Error::InvalidOperation(_))));
+}
+
+#[test]
+fn test_tree_builder_same_as_parse() -> Result<(), Error> {
+    let xml =
+        r#"<doc xml:space="preserve" xmlns:x="http://example.com"><x:p x:a="1"> </x:p></doc>"#;
+    let mut xot = Xot::new();
+    let parsed = xot.parse(xml)?;
+    let mut builder = xot.tree_builder();
+    builder.start_element("", "doc")?;
+    builder.attribute("xml", "space", "preserve")?;
+    builder.namespace("x", "http://example.com")?;
+    builder.start_element("x", "p")?;
+    builder.attribute("x", "a", "1")?;
+    builder.text(" ")?;
+    builder.end_element()?;
+    builder.end_element()?;
+    let built = builder.finish()?;
+    assert!(xot.deep_equal(parsed, built));
+    Ok(())
+}
+
+#[test]
+fn test_tree_builder_invalid_names() {
+    let mut xot = Xot::new();
+    let mut builder = xot.tree_builder();
+    assert!(matches!(
+        builder.start_element("", "1 bad<"),
+        Err(Error::InvalidName(_))
+    ));
+    assert!(matches!(
+        builder.start_element("a:b", "c"),
+        Err(Error::InvalidPrefix(_))
+    ));
+    builder.start_element("", "a").unwrap();
+    assert!(matches!(
+        builder.attribute("", "a b", "1"),
+        Err(Error::InvalidName(_))
+    ));
+    assert!(matches!(
+        builder.namespace("1", "http://example.com"),
+        Err(Error::InvalidPrefix(_))
+    ));
+}
+
+#[test]
+fn test_tree_builder_invalid_characters() {
+    let mut xot = Xot::new();
+    let mut builder = xot.tree_builder();
+    builder.start_element("", "a").unwrap();
+    assert!(matches!(
+        builder.attribute("", "b", "\0"),
+        Err(Error::InvalidCharacter('\0'))
+    ));
+    assert!(matches!(
+        builder.text("\u{1}"),
+        Err(Error::InvalidCharacter('\u{1}'))
+    ));
+    assert!(matches!(
+        builder.comment("\u{FFFE}"),
+        Err(Error::InvalidCharacter('\u{FFFE}'))
+    ));
+}
+
+#[test]
+fn test_tree_builder_invalid_comment() {
+    let mut xot = Xot::new();
+    let mut builder = xot.tree_builder();
+    assert!(matches!(
+        builder.comment("a--b"),
+        Err(Error::InvalidComment(_))
+    ));
+    assert!(matches!(
+        builder.comment("a-"),
+        Err(Error::InvalidComment(_))
+    ));
+}
+
+#[test]
+fn test_tree_builder_invalid_processing_instruction() {
+    let mut xot = Xot::new();
+    let mut builder = xot.tree_builder();
+    assert!(matches!(
+        builder.processing_instruction("xml", None),
+        Err(Error::InvalidTarget(_))
+    ));
+    assert!(matches!(
+        builder.processing_instruction("XmL", None),
+        Err(Error::InvalidTarget(_))
+    ));
+    assert!(matches!(
+        builder.processing_instruction("a:b", None),
+        Err(Error::InvalidName(_))
+    ));
+    assert!(matches!(
+        builder.processing_instruction("pi", Some("?>")),
+        Err(Error::InvalidProcessingInstructionData(_))
+    ));
+    // nothing invalid ended up in the tree
+    builder.start_element("", "doc").unwrap();
+    builder.end_element().unwrap();
+    let document = builder.finish().unwrap();
+    assert_eq!(xot.to_string(document).unwrap(), "<doc/>");
+}
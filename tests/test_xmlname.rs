@@ -88,3 +88,15 @@ fn test_create_attribute() {
         .insert(attr_name, "value".to_string());
     assert_eq!(xot.to_string(doc_el).unwrap(), r#"<local attr="value"/>"#);
 }
+
+#[test]
+fn test_unknown_prefix_has_no_span() {
+    let mut xot = Xot::new();
+    let err = xmlname::CreateName::prefixed(&mut xot, "foo", "local", |_| None).unwrap_err();
+    assert!(matches!(err, xot::Error::UnknownPrefix(ref prefix, None) if prefix == "foo"));
+    assert_eq!(err.span(), None);
+
+    let err = xmlname::OwnedName::prefixed("foo", "local", |_| None).unwrap_err();
+    assert_eq!(err.span(), None);
+    assert_eq!(err.to_string(), "Unknown prefix: foo");
+}